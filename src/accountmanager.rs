@@ -1,24 +1,33 @@
 use crate::accountmanager::legacy::SdaManifest;
 pub use crate::encryption::EncryptionScheme;
-use crate::encryption::EntryEncryptor;
+use crate::encryption::{EntryEncryptionError, EntryEncryptor, PasskeyProvider};
 use log::*;
 use rayon::prelude::*;
 use secrecy::{ExposeSecret, SecretString};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use steamguard::SteamGuardAccount;
+use std::time::Duration;
+use steamguard::{SteamGuardAccount, TimeSync};
 use thiserror::Error;
 
+pub mod backup;
+pub mod export;
 mod legacy;
+pub mod lock;
 pub mod manifest;
 pub mod migrate;
+mod rekey;
+mod selection;
 mod steamv2;
 mod winauth;
 
+pub use lock::*;
 pub use manifest::*;
+pub use selection::*;
 
 #[derive(Debug, Default)]
 pub struct AccountManager {
@@ -26,13 +35,32 @@ pub struct AccountManager {
 	accounts: HashMap<String, Arc<Mutex<SteamGuardAccount>>>,
 	folder: String,
 	passkey: Option<SecretString>,
+	lock: Option<ManifestLock>,
+	/// How long to wait for the lock when saving, if `lock` is not held.
+	lock_timeout: Duration,
+	time_sync: TimeSync,
+	/// What this process last read from or wrote to disk, so that saving only writes what it changed.
+	snapshot: Mutex<Snapshot>,
 }
 
+#[derive(Debug, Default)]
+struct Snapshot {
+	/// The manifest as of the last load or save. `None` if it hasn't been read from disk.
+	manifest: Option<Manifest>,
+	/// Digests of the serialized accounts as of the last load or save.
+	accounts: HashMap<String, [u8; 32]>,
+}
+
+/// The offset between the local clock and Steam's clock is cached in this file, in the same folder as the manifest.
+const TIME_SYNC_FILENAME: &str = ".steamguard-cli.timesync.json";
+
 impl AccountManager {
 	/// `path` should be the path to manifest.json
 	pub fn new(path: &Path) -> Self {
+		let folder = String::from(path.parent().unwrap().to_str().unwrap());
 		Self {
-			folder: String::from(path.parent().unwrap().to_str().unwrap()),
+			time_sync: TimeSync::with_file(Path::new(&folder).join(TIME_SYNC_FILENAME)),
+			folder,
 			..Default::default()
 		}
 	}
@@ -40,6 +68,7 @@ impl AccountManager {
 	pub fn from_manifest(manifest: Manifest, folder: String) -> Self {
 		Self {
 			manifest,
+			time_sync: TimeSync::with_file(Path::new(&folder).join(TIME_SYNC_FILENAME)),
 			folder,
 			..Default::default()
 		}
@@ -68,12 +97,12 @@ impl AccountManager {
 		if manifest.version != CURRENT_MANIFEST_VERSION {
 			return Err(ManifestLoadError::MigrationNeeded)?;
 		}
-		let accountmanager = Self {
+		let manager = Self::from_manifest(
 			manifest,
-			folder: String::from(path.parent().unwrap().to_str().unwrap()),
-			..Default::default()
-		};
-		Ok(accountmanager)
+			String::from(path.parent().unwrap().to_str().unwrap()),
+		);
+		manager.snapshot.lock().unwrap().manifest = Some(manager.manifest.clone());
+		Ok(manager)
 	}
 
 	/// Tells the manager to keep track of the encryption passkey, and use it for encryption when loading or saving accounts.
@@ -91,6 +120,12 @@ impl AccountManager {
 		self.passkey = passkey;
 	}
 
+	/// Hold `lock` for as long as the manager is alive. If no lock is given, the maFiles directory is only locked while saving, waiting up to `timeout` for other processes to release it.
+	pub fn set_lock(&mut self, lock: Option<ManifestLock>, timeout: Duration) {
+		self.lock = lock;
+		self.lock_timeout = timeout;
+	}
+
 	pub fn keyring_id(&self) -> Option<&String> {
 		self.manifest.keyring_id.as_ref()
 	}
@@ -103,6 +138,27 @@ impl AccountManager {
 		self.manifest.keyring_id = None;
 	}
 
+	pub fn api_token(&self) -> Option<&String> {
+		self.manifest.api_token.as_ref()
+	}
+
+	pub fn set_api_token(&mut self, api_token: String) {
+		self.manifest.api_token = Some(api_token);
+	}
+
+	/// Keeps track of Steam's time, shared by everything that uses this manager.
+	pub fn time_sync(&self) -> &TimeSync {
+		&self.time_sync
+	}
+
+	pub fn passkey_provider(&self) -> Option<&PasskeyProvider> {
+		self.manifest.passkey_provider.as_ref()
+	}
+
+	pub fn set_passkey_provider(&mut self, provider: Option<PasskeyProvider>) {
+		self.manifest.passkey_provider = provider;
+	}
+
 	/// Loads all accounts, and registers them.
 	pub fn load_accounts(&mut self) -> anyhow::Result<(), ManifestAccountLoadError> {
 		let accounts = self
@@ -117,6 +173,28 @@ impl AccountManager {
 		Ok(())
 	}
 
+	/// Loads the specified accounts in parallel, and registers them. Accounts that are already loaded are skipped.
+	pub fn load_accounts_by_name(
+		&mut self,
+		account_names: &[String],
+	) -> anyhow::Result<(), ManifestAccountLoadError> {
+		let account_names: HashSet<&String> = account_names.iter().collect();
+		let accounts = self
+			.manifest
+			.entries
+			.par_iter()
+			.filter(|entry| {
+				account_names.contains(&entry.account_name)
+					&& !self.accounts.contains_key(&entry.account_name)
+			})
+			.map(|entry| self.load_account_by_entry(entry))
+			.collect::<Vec<_>>();
+		for account in accounts {
+			self.register_loaded_account(account?);
+		}
+		Ok(())
+	}
+
 	/// Loads an account by account name.
 	/// Must call `register_loaded_account` after loading the account.
 	fn load_account(
@@ -134,11 +212,18 @@ impl AccountManager {
 		entry: &ManifestEntry,
 	) -> anyhow::Result<Arc<Mutex<SteamGuardAccount>>, ManifestAccountLoadError> {
 		let path = Path::new(&self.folder).join(&entry.filename);
-		let account = entry.load(
+		let account: SteamGuardAccount = entry.load(
 			path.as_path(),
 			self.passkey.as_ref(),
 			entry.encryption.as_ref(),
 		)?;
+		let digest =
+			Sha256::digest(serde_json::to_vec(&account).map_err(anyhow::Error::from)?).into();
+		self.snapshot
+			.lock()
+			.unwrap()
+			.accounts
+			.insert(entry.account_name.clone(), digest);
 		let account = Arc::new(Mutex::new(account));
 		Ok(account)
 	}
@@ -165,6 +250,9 @@ impl AccountManager {
 			steam_id: account.steam_id,
 			account_name: account.account_name.clone(),
 			encryption: None,
+			tags: Default::default(),
+			note: None,
+			password_command: None,
 		});
 		self.accounts
 			.insert(account.account_name.clone(), Arc::new(Mutex::new(account)));
@@ -208,25 +296,63 @@ impl AccountManager {
 	}
 
 	/// Saves the manifest and all loaded accounts.
+	///
+	/// Other processes may have saved since this manager was loaded, so only the manifest entries and accounts that this process changed are written. Everything else is kept as it is on disk.
 	pub fn save(&self) -> anyhow::Result<()> {
 		info!("Saving manifest and accounts...");
+		let _lock = self.lock_for_writing()?;
+		let mut snapshot = self.snapshot.lock().unwrap();
+		let manifest = self.merge_with_disk(snapshot.manifest.as_ref())?;
+		let base = snapshot.manifest.as_ref();
+		let digests = &snapshot.accounts;
 		let save_results: Vec<_> = self
 			.accounts
 			.values()
 			.par_bridge()
-			.map(|account| -> anyhow::Result<()> {
+			.map(|account| -> anyhow::Result<Option<(String, [u8; 32])>> {
 				let account = account.lock().unwrap();
 				let entry = self.get_entry(&account.account_name)?.clone();
-				debug!("saving {}", entry.filename);
 				let serialized = serde_json::to_vec(&account.clone())?;
 				ensure!(
 					serialized.len() > 2,
 					"Something extra weird happened and the account was serialized into nothing."
 				);
+				let digest: [u8; 32] = Sha256::digest(&serialized).into();
 
+				let Some(saved_entry) = find_entry(&manifest, &entry.account_name) else {
+					warn!(
+						"{} was removed by another process, not saving it",
+						entry.account_name
+					);
+					return Ok(None);
+				};
+				if let Some(base_entry) = base.and_then(|m| find_entry(m, &entry.account_name)) {
+					let entry_changed = !same(base_entry, &entry);
+					if !entry_changed
+						&& !same(
+							(&base_entry.filename, &base_entry.encryption),
+							(&saved_entry.filename, &saved_entry.encryption),
+						) {
+						warn!(
+							"{} was re-encrypted by another process, not saving it",
+							entry.account_name
+						);
+						return Ok(None);
+					}
+					if !entry_changed && digests.get(&entry.account_name) == Some(&digest) {
+						return Ok(None);
+					}
+				}
+
+				debug!("saving {}", entry.filename);
 				let final_buffer: Vec<u8> = match (&self.passkey, entry.encryption.as_ref()) {
-					(Some(passkey), Some(scheme)) => {
-						scheme.encrypt(passkey.expose_secret(), serialized)?
+					(Some(passkey), Some(scheme)) => scheme.encrypt_with_ad(
+						passkey.expose_secret(),
+						serialized,
+						&entry.associated_data(),
+					)?,
+					(None, Some(scheme)) if scheme.is_public_key() => {
+						scheme.encrypt_with_ad("", serialized, &entry.associated_data())?
 					}
 					(None, Some(_)) => {
 						bail!("maFiles are encrypted, but no passkey was provided.");
@@ -235,25 +361,64 @@ impl AccountManager {
 				};
 
 				let path = Path::new(&self.folder).join(&entry.filename);
-				let mut file = File::create(path)?;
-				file.write_all(final_buffer.as_slice())?;
-				file.sync_data()?;
-
-				Ok(())
+				write_atomic(&path, &final_buffer)?;
+				Ok(Some((entry.account_name, digest)))
 			})
 			.collect();
+		let mut saved = vec![];
 		for result in save_results {
-			result?;
+			saved.extend(result?);
 		}
-		debug!("saving manifest");
-		let manifest_serialized = serde_json::to_string(&self.manifest)?;
-		let path = Path::new(&self.folder).join("manifest.json");
-		let mut file = File::create(path)?;
-		file.write_all(manifest_serialized.as_bytes())?;
-		file.sync_data()?;
+		// the manifest is written last, so it never references maFiles that haven't been written yet
+		write_manifest(Path::new(&self.folder), &manifest)?;
+		snapshot.manifest = Some(self.manifest.clone());
+		snapshot.accounts.extend(saved);
 		Ok(())
 	}
 
+	/// Write the manifest as it is in memory. Only safe while holding the lock, and nothing else could have changed the manifest since it was loaded.
+	fn write_manifest(&self) -> anyhow::Result<()> {
+		write_manifest(Path::new(&self.folder), &self.manifest)?;
+		self.snapshot.lock().unwrap().manifest = Some(self.manifest.clone());
+		Ok(())
+	}
+
+	/// Apply the changes this process made to the manifest since `base` was loaded to the manifest currently on disk. Must be called while holding the lock.
+	fn merge_with_disk(&self, base: Option<&Manifest>) -> anyhow::Result<Manifest> {
+		let Some(base) = base else {
+			return Ok(self.manifest.clone());
+		};
+		let path = Path::new(&self.folder).join("manifest.json");
+		let text = match std::fs::read_to_string(&path) {
+			Ok(text) => text,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+				return Ok(self.manifest.clone());
+			}
+			Err(err) => return Err(err.into()),
+		};
+		match serde_json::from_str::<Manifest>(&text) {
+			Ok(theirs) => Ok(merge_manifests(base, &self.manifest, theirs)),
+			Err(err) => {
+				warn!(
+					"Failed to read the manifest on disk, overwriting it: {}",
+					err
+				);
+				Ok(self.manifest.clone())
+			}
+		}
+	}
+
+	/// Lock the maFiles directory, unless the manager is already holding the lock.
+	fn lock_for_writing(&self) -> Result<Option<ManifestLock>, LockError> {
+		match self.lock {
+			Some(_) => Ok(None),
+			None => Ok(Some(ManifestLock::acquire(
+				Path::new(&self.folder),
+				self.lock_timeout,
+			)?)),
+		}
+	}
+
 	/// Return all loaded accounts. Order is not guarenteed.
 	#[allow(dead_code)]
 	pub fn get_all_loaded(&self) -> Vec<Arc<Mutex<SteamGuardAccount>>> {
@@ -324,6 +489,22 @@ impl AccountManager {
 			.any(|e| e.account_name.is_empty())
 	}
 
+	/// Determine if any entries are encrypted with a scheme that can't detect tampering. `Argon2idAes256` entries are upgraded by [`Self::auto_upgrade`], but `LegacySdaCompatible` ones are not, because SteamDesktopAuthenticator can only read those. `steamguard rekey` upgrades them.
+	pub fn has_unauthenticated_encryption(&self) -> bool {
+		self.manifest
+			.entries
+			.iter()
+			.any(|e| matches!(&e.encryption, Some(scheme) if !scheme.is_authenticated()))
+	}
+
+	/// Determine if any entries use `Argon2idAes256`, which SteamDesktopAuthenticator can't read anyway, so they can be upgraded automatically.
+	fn has_upgradable_encryption(&self) -> bool {
+		self.manifest
+			.entries
+			.iter()
+			.any(|e| matches!(&e.encryption, Some(EncryptionScheme::Argon2idAes256(_))))
+	}
+
 	fn has_any_uppercase_in_account_names(&self) -> bool {
 		self.manifest
 			.entries
@@ -346,6 +527,16 @@ impl AccountManager {
 			upgraded = true;
 		}
 
+		if self.has_upgradable_encryption() {
+			let Some(passkey) = self.passkey.clone() else {
+				return Err(ManifestAccountLoadError::MissingPasskey);
+			};
+			info!("Upgrading maFiles to authenticated encryption");
+			self.load_accounts()?;
+			self.upgrade_encryption(&passkey)?;
+			upgraded = true;
+		}
+
 		if self.has_any_uppercase_in_account_names() {
 			debug!("Lowercasing account names");
 			for i in 0..self.manifest.entries.len() {
@@ -383,31 +574,152 @@ impl EntryLoader<SteamGuardAccount> for ManifestEntry {
 		passkey: Option<&SecretString>,
 		encryption_params: Option<&EncryptionScheme>,
 	) -> anyhow::Result<SteamGuardAccount, ManifestAccountLoadError> {
-		debug!("loading entry: {:?}", path);
-		let file = File::open(path)?;
-		let mut reader = BufReader::new(file);
-		let account: SteamGuardAccount = match (&passkey, encryption_params.as_ref()) {
-			(Some(passkey), Some(scheme)) => {
-				let mut ciphertext: Vec<u8> = vec![];
-				reader.read_to_end(&mut ciphertext)?;
-				let plaintext = scheme.decrypt(passkey.expose_secret(), ciphertext)?;
-				if plaintext[0] != b'{' && plaintext[plaintext.len() - 1] != b'}' {
-					return Err(ManifestAccountLoadError::IncorrectPasskey);
-				}
-				let s = std::str::from_utf8(&plaintext).unwrap();
-				let mut deser = serde_json::Deserializer::from_str(s);
-				serde_path_to_error::deserialize(&mut deser)?
-			}
-			(None, Some(_)) => {
-				return Err(ManifestAccountLoadError::MissingPasskey);
-			}
-			(_, None) => {
-				let mut deser = serde_json::Deserializer::from_reader(reader);
-				serde_path_to_error::deserialize(&mut deser)?
-			}
+		load_entry_file(path, passkey, encryption_params, &self.associated_data())
+	}
+}
+
+fn load_entry_file(
+	path: &Path,
+	passkey: Option<&SecretString>,
+	encryption_params: Option<&EncryptionScheme>,
+	associated_data: &[u8],
+) -> anyhow::Result<SteamGuardAccount, ManifestAccountLoadError> {
+	debug!("loading entry: {:?}", path);
+	let mut buf = vec![];
+	File::open(path)?.read_to_end(&mut buf)?;
+	decode_entry(buf, passkey, encryption_params, associated_data)
+}
+
+/// Save the manifest to `manifest.json` in `folder`.
+fn write_manifest(folder: &Path, manifest: &Manifest) -> anyhow::Result<()> {
+	debug!("saving manifest");
+	let manifest_serialized = serde_json::to_string(manifest)?;
+	write_atomic(
+		&folder.join("manifest.json"),
+		manifest_serialized.as_bytes(),
+	)
+}
+
+/// Three-way merge of manifests: `ours` is what this process has in memory, `base` is what it was when it was loaded, and `theirs` is what is on disk now, possibly saved by other processes in the meantime.
+///
+/// Entries and fields that this process didn't change are taken from `theirs`.
+fn merge_manifests(base: &Manifest, ours: &Manifest, mut theirs: Manifest) -> Manifest {
+	if !same(&base.keyring_id, &ours.keyring_id) {
+		theirs.keyring_id.clone_from(&ours.keyring_id);
+	}
+	if !same(&base.api_token, &ours.api_token) {
+		theirs.api_token.clone_from(&ours.api_token);
+	}
+	if !same(&base.passkey_provider, &ours.passkey_provider) {
+		theirs.passkey_provider.clone_from(&ours.passkey_provider);
+	}
+
+	theirs.entries.retain(|e| {
+		find_entry(base, &e.account_name).is_none() || find_entry(ours, &e.account_name).is_some()
+	});
+	for entry in &ours.entries {
+		let base_entry = find_entry(base, &entry.account_name);
+		if base_entry.is_some_and(|b| same(b, entry)) {
+			continue;
+		}
+		match theirs
+			.entries
+			.iter_mut()
+			.find(|e| e.account_name == entry.account_name)
+		{
+			Some(e) => *e = entry.clone(),
+			None if base_entry.is_none() => theirs.entries.push(entry.clone()),
+			None => {}
+		}
+	}
+	theirs
+}
+
+fn find_entry<'a>(manifest: &'a Manifest, account_name: &str) -> Option<&'a ManifestEntry> {
+	manifest
+		.entries
+		.iter()
+		.find(|e| e.account_name == account_name)
+}
+
+/// Compare by serialized value, since not everything in the manifest implements `PartialEq`.
+fn same<T: serde::Serialize>(a: T, b: T) -> bool {
+	serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Write `buf` to a temporary file next to `path`, then rename it over `path`, so `path` is never left partially written.
+///
+/// The file keeps the permissions of the file it replaces. New files are only readable by the owner.
+pub(crate) fn write_atomic(path: &Path, buf: &[u8]) -> anyhow::Result<()> {
+	let file_name = path
+		.file_name()
+		.ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+	let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+	// a leftover temp file from a crash would keep its old permissions
+	match std::fs::remove_file(&tmp_path) {
+		Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+		_ => {}
+	}
+	let mut options = File::options();
+	options.write(true).create_new(true);
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+	let mut file = options.open(&tmp_path)?;
+	if let Ok(metadata) = std::fs::metadata(path) {
+		file.set_permissions(metadata.permissions())?;
+	}
+	file.write_all(buf)?;
+	file.sync_all()?;
+	drop(file);
+	std::fs::rename(&tmp_path, path)?;
+	// make sure the rename itself survives a crash
+	#[cfg(unix)]
+	if let Some(parent) = path.parent() {
+		let parent = match parent.as_os_str().is_empty() {
+			true => Path::new("."),
+			false => parent,
 		};
-		Ok(account)
+		File::open(parent)?.sync_all()?;
 	}
+	Ok(())
+}
+
+/// Deserialize the contents of a maFile, decrypting it if needed.
+///
+/// `associated_data` must be the [`ManifestEntry::associated_data`] of the entry the maFile belongs to.
+fn decode_entry(
+	buf: Vec<u8>,
+	passkey: Option<&SecretString>,
+	encryption_params: Option<&EncryptionScheme>,
+	associated_data: &[u8],
+) -> anyhow::Result<SteamGuardAccount, ManifestAccountLoadError> {
+	let account: SteamGuardAccount = match (&passkey, encryption_params.as_ref()) {
+		(Some(passkey), Some(scheme)) => {
+			let plaintext = scheme
+				.decrypt_with_ad(passkey.expose_secret(), buf, associated_data)
+				.map_err(|err| match err {
+					EntryEncryptionError::IncorrectPasskey => {
+						ManifestAccountLoadError::IncorrectPasskey
+					}
+					EntryEncryptionError::Corrupted => ManifestAccountLoadError::Corrupted,
+					err => err.into(),
+				})?;
+			if plaintext[0] != b'{' && plaintext[plaintext.len() - 1] != b'}' {
+				return Err(ManifestAccountLoadError::IncorrectPasskey);
+			}
+			let s = std::str::from_utf8(&plaintext).unwrap();
+			let mut deser = serde_json::Deserializer::from_str(s);
+			serde_path_to_error::deserialize(&mut deser)?
+		}
+		(None, Some(_)) => {
+			return Err(ManifestAccountLoadError::MissingPasskey);
+		}
+		(_, None) => {
+			let mut deser = serde_json::Deserializer::from_slice(&buf);
+			serde_path_to_error::deserialize(&mut deser)?
+		}
+	};
+	Ok(account)
 }
 
 #[derive(Debug, Error)]
@@ -426,10 +738,14 @@ pub enum ManifestLoadError {
 pub enum ManifestAccountLoadError {
 	#[error("Could not find an entry in the manifest for this account. Check your spelling.")]
 	MissingManifestEntry,
+	#[error("No accounts in the manifest match the selection.")]
+	NoMatchingAccounts,
 	#[error("Manifest accounts are encrypted, but no passkey was provided.")]
 	MissingPasskey,
 	#[error("Incorrect passkey provided.")]
 	IncorrectPasskey,
+	#[error("The maFile is corrupted, or has been tampered with. Restore it from a backup.")]
+	Corrupted,
 	#[error("Failed to decrypt account. {self:?}")]
 	DecryptionFailed(#[from] crate::encryption::EntryEncryptionError),
 	#[error("Failed to deserialize the account. {self:?}")]
@@ -481,6 +797,136 @@ mod tests {
 		assert!(manager.save().is_ok());
 	}
 
+	#[test]
+	fn test_save_is_atomic_and_locked() -> anyhow::Result<()> {
+		let tmp_dir = TempDir::new()?;
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(manifest_path.as_path());
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "asdf1234".into();
+		manager.add_account(account);
+		manager.save()?;
+		let mut files: Vec<_> = std::fs::read_dir(tmp_dir.path())?
+			.map(|e| e.unwrap().file_name().into_string().unwrap())
+			.collect();
+		files.sort();
+		assert_eq!(
+			files,
+			vec![".steamguard-cli.lock", "asdf1234.maFile", "manifest.json"]
+		);
+
+		let lock = ManifestLock::acquire(tmp_dir.path(), Duration::ZERO)?;
+		assert!(manager.save().is_err());
+		manager.set_lock(Some(lock), Duration::ZERO);
+		manager.save()?;
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn test_save_keeps_file_permissions() -> anyhow::Result<()> {
+		use std::os::unix::fs::PermissionsExt;
+
+		let tmp_dir = TempDir::new()?;
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let mafile_path = tmp_dir.path().join("example.maFile");
+		let mode = |path: &Path| -> anyhow::Result<u32> {
+			Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+		};
+		let mut manager = AccountManager::new(manifest_path.as_path());
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		manager.add_account(account);
+		manager.save()?;
+		assert_eq!(mode(&manifest_path)?, 0o600);
+		assert_eq!(mode(&mafile_path)?, 0o600);
+
+		std::fs::set_permissions(&manifest_path, std::fs::Permissions::from_mode(0o640))?;
+		std::fs::set_permissions(&mafile_path, std::fs::Permissions::from_mode(0o400))?;
+		manager
+			.get_account("example")?
+			.lock()
+			.unwrap()
+			.revocation_code = String::from("R12345").into();
+		manager
+			.get_entry_mut("example")?
+			.tags
+			.insert("group".into(), "main".into());
+		manager.save()?;
+		assert_eq!(mode(&manifest_path)?, 0o640);
+		assert_eq!(mode(&mafile_path)?, 0o400);
+		Ok(())
+	}
+
+	#[test]
+	fn test_save_keeps_changes_from_other_processes() -> anyhow::Result<()> {
+		let tmp_dir = TempDir::new()?;
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(manifest_path.as_path());
+		for account_name in ["account1", "account2", "account3"] {
+			let mut account = SteamGuardAccount::new();
+			account.account_name = account_name.into();
+			manager.add_account(account);
+		}
+		manager.save()?;
+
+		// eg. `serve`, which doesn't hold the lock while other commands run
+		let mut long_running = AccountManager::load(manifest_path.as_path())?;
+		long_running.load_accounts()?;
+		let mut other = AccountManager::load(manifest_path.as_path())?;
+		other.load_accounts()?;
+
+		other
+			.get_entry_mut("account1")?
+			.tags
+			.insert("group".into(), "storage".into());
+		other
+			.get_account("account2")?
+			.lock()
+			.unwrap()
+			.revocation_code = String::from("R11111").into();
+		other.remove_account(&"account3".into());
+		other.save()?;
+
+		long_running
+			.get_account("account1")?
+			.lock()
+			.unwrap()
+			.revocation_code = String::from("R22222").into();
+		long_running
+			.get_account("account3")?
+			.lock()
+			.unwrap()
+			.revocation_code = String::from("R33333").into();
+		long_running.save()?;
+
+		let mut reloaded = AccountManager::load(manifest_path.as_path())?;
+		assert_eq!(
+			reloaded
+				.iter()
+				.map(|e| e.account_name.as_str())
+				.collect::<Vec<_>>(),
+			vec!["account1", "account2"]
+		);
+		assert_eq!(
+			reloaded.get_entry("account1")?.tags.get("group").unwrap(),
+			"storage"
+		);
+		reloaded.load_accounts()?;
+		let revocation_code = |name: &str| -> anyhow::Result<String> {
+			Ok(reloaded
+				.get_account(name)?
+				.lock()
+				.unwrap()
+				.revocation_code
+				.expose_secret()
+				.clone())
+		};
+		assert_eq!(revocation_code("account1")?, "R22222");
+		assert_eq!(revocation_code("account2")?, "R11111");
+		Ok(())
+	}
+
 	#[test]
 	fn test_should_save_and_load_manifest() -> anyhow::Result<()> {
 		let tmp_dir = TempDir::new()?;
@@ -661,7 +1107,7 @@ mod tests {
 	}
 
 	#[test]
-	fn should_load_manifest_v1() -> anyhow::Result<()> {
+	fn should_load_manifest_v2() -> anyhow::Result<()> {
 		#[derive(Debug)]
 		struct Test {
 			manifest: &'static str,
@@ -669,19 +1115,19 @@ mod tests {
 		}
 		let cases = vec![
 			Test {
-				manifest: "src/fixtures/maFiles/manifest-v1/1-account/manifest.json",
+				manifest: "src/fixtures/maFiles/manifest-v2/1-account/manifest.json",
 				passkey: None,
 			},
 			Test {
-				manifest: "src/fixtures/maFiles/manifest-v1/1-account-encrypted/manifest.json",
+				manifest: "src/fixtures/maFiles/manifest-v2/1-account-encrypted/manifest.json",
 				passkey: Some(SecretString::new("password".into())),
 			},
 			Test {
-				manifest: "src/fixtures/maFiles/manifest-v1/2-account/manifest.json",
+				manifest: "src/fixtures/maFiles/manifest-v2/2-account/manifest.json",
 				passkey: None,
 			},
 			Test {
-				manifest: "src/fixtures/maFiles/manifest-v1/missing-account-name/manifest.json",
+				manifest: "src/fixtures/maFiles/manifest-v2/tags/manifest.json",
 				passkey: None,
 			},
 		];
@@ -700,4 +1146,164 @@ mod tests {
 		}
 		Ok(())
 	}
+
+	#[test]
+	fn should_only_load_selected_accounts() -> anyhow::Result<()> {
+		let mut manager = AccountManager::load(Path::new(
+			"src/fixtures/maFiles/manifest-v2/2-account/manifest.json",
+		))?;
+		manager.load_accounts_by_name(&["example2".to_owned()])?;
+		assert!(manager.get_account("example2").is_ok());
+		assert!(manager.get_account("example").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn should_load_tags_and_note() -> anyhow::Result<()> {
+		let manager = AccountManager::load(Path::new(
+			"src/fixtures/maFiles/manifest-v2/tags/manifest.json",
+		))?;
+		let entry = manager.get_entry("example")?;
+		assert!(entry.has_tag("group", Some("storage")));
+		assert!(entry.has_tag("region", None));
+		assert!(!entry.has_tag("group", Some("trade")));
+		assert_eq!(entry.note.as_deref(), Some("main storage bot"));
+		assert!(manager.get_entry("example2")?.note.is_none());
+		Ok(())
+	}
+
+	#[test]
+	fn should_require_migration_for_manifest_v1() {
+		let result = AccountManager::load(Path::new(
+			"src/fixtures/maFiles/manifest-v1/1-account/manifest.json",
+		));
+		assert!(matches!(result, Err(ManifestLoadError::MigrationNeeded)));
+	}
+
+	#[test]
+	fn should_only_upgrade_to_authenticated_encryption_when_rekeyed() -> anyhow::Result<()> {
+		let tmp_dir = TempDir::new()?;
+		let fixture = Path::new("src/fixtures/maFiles/manifest-v2/1-account-encrypted");
+		for file in ["manifest.json", "1234.maFile"] {
+			std::fs::copy(fixture.join(file), tmp_dir.path().join(file))?;
+		}
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let passkey = SecretString::new("password".into());
+
+		let mut manager = AccountManager::load(&manifest_path)?;
+		assert!(manager.has_unauthenticated_encryption());
+		assert!(!manager.auto_upgrade()?);
+		assert!(manager.has_unauthenticated_encryption());
+
+		manager.submit_passkey(Some(passkey.clone()));
+		manager.load_accounts()?;
+		manager.rekey(passkey.clone(), None)?;
+
+		let mut manager = AccountManager::load(&manifest_path)?;
+		assert!(!manager.has_unauthenticated_encryption());
+		manager.submit_passkey(Some(passkey));
+		manager.load_accounts()?;
+		assert_eq!(
+			manager.get_account("example")?.lock().unwrap().steam_id,
+			1234
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn should_auto_upgrade_argon2id_aes256_but_keep_sda_compatible() -> anyhow::Result<()> {
+		use crate::encryption::{Argon2idAes256, LegacySdaCompatible};
+
+		let tmp_dir = TempDir::new()?;
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let passkey = Some(SecretString::new("password".into()));
+		let mut manager = AccountManager::new(manifest_path.as_path());
+		for account_name in ["modern", "sda"] {
+			let mut account = SteamGuardAccount::new();
+			account.account_name = account_name.into();
+			manager.add_account(account);
+		}
+		manager.get_entry_mut("modern")?.encryption =
+			Some(EncryptionScheme::Argon2idAes256(Argon2idAes256::generate()));
+		manager.get_entry_mut("sda")?.encryption = Some(EncryptionScheme::LegacySdaCompatible(
+			LegacySdaCompatible::generate(),
+		));
+		manager.submit_passkey(passkey.clone());
+		manager.save()?;
+		let sda_file = std::fs::read(tmp_dir.path().join("sda.maFile"))?;
+
+		let mut manager = AccountManager::load(&manifest_path)?;
+		assert!(matches!(
+			manager.auto_upgrade(),
+			Err(ManifestAccountLoadError::MissingPasskey)
+		));
+		manager.submit_passkey(passkey.clone());
+		assert!(manager.auto_upgrade()?);
+		assert!(!manager.auto_upgrade()?);
+
+		let mut manager = AccountManager::load(&manifest_path)?;
+		assert!(matches!(
+			manager.get_entry("modern")?.encryption,
+			Some(EncryptionScheme::Argon2idXChaCha20Poly1305(_))
+		));
+		assert!(matches!(
+			manager.get_entry("sda")?.encryption,
+			Some(EncryptionScheme::LegacySdaCompatible(_))
+		));
+		assert_eq!(std::fs::read(tmp_dir.path().join("sda.maFile"))?, sda_file);
+		assert!(manager.has_unauthenticated_encryption());
+		manager.submit_passkey(passkey);
+		manager.load_accounts()?;
+		Ok(())
+	}
+
+	#[test]
+	fn should_distinguish_incorrect_passkey_from_corruption() -> anyhow::Result<()> {
+		let passkey = Some(SecretString::new("password".into()));
+		let tmp_dir = TempDir::new()?;
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(manifest_path.as_path());
+		for name in ["example1", "example2"] {
+			let mut account = SteamGuardAccount::new();
+			account.account_name = name.into();
+			manager.add_account(account);
+		}
+		for entry in manager.iter_mut() {
+			entry.encryption = Some(EncryptionScheme::generate());
+		}
+		manager.submit_passkey(passkey.clone());
+		manager.save()?;
+
+		let load = |passkey: &Option<SecretString>| {
+			let mut manager = AccountManager::load(manifest_path.as_path()).unwrap();
+			manager.submit_passkey(passkey.clone());
+			manager.load_accounts()
+		};
+		assert!(matches!(
+			load(&Some(SecretString::new("wrong".into()))),
+			Err(ManifestAccountLoadError::IncorrectPasskey)
+		));
+
+		// a maFile moved into place of another account's maFile
+		let path1 = tmp_dir.path().join("example1.maFile");
+		let path2 = tmp_dir.path().join("example2.maFile");
+		let original = std::fs::read(&path1)?;
+		std::fs::copy(&path2, &path1)?;
+		assert!(matches!(
+			load(&passkey),
+			Err(ManifestAccountLoadError::Corrupted)
+		));
+
+		let mut truncated = original.clone();
+		truncated.truncate(original.len() - 4);
+		std::fs::write(&path1, truncated)?;
+		assert!(matches!(
+			load(&passkey),
+			Err(ManifestAccountLoadError::Corrupted)
+		));
+
+		std::fs::write(&path1, original)?;
+		assert!(load(&passkey).is_ok());
+		Ok(())
+	}
 }
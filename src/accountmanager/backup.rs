@@ -0,0 +1,348 @@
+//! Portable, encrypted backups of the whole maFiles directory, created with `steamguard backup`.
+//!
+//! Files are packed exactly as they are on disk, so maFiles that were encrypted stay encrypted with their original passkey inside the backup.
+
+use std::{
+	collections::HashSet,
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use base64::Engine;
+use log::*;
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use steamguard::SteamGuardAccount;
+use thiserror::Error;
+
+use super::{
+	decode_entry, migrate::backup_file, write_atomic, AccountManager, Manifest,
+	ManifestAccountLoadError,
+};
+use crate::encryption::{Envelope, EnvelopeError, EnvelopeKind};
+
+const MANIFEST_FILENAME: &str = "manifest.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupArchive {
+	pub version: u32,
+	/// Unix timestamp of when the backup was created.
+	pub created_at: u64,
+	/// Version of steamguard-cli that created the backup.
+	pub created_by: String,
+	pub files: Vec<BackupFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupFile {
+	/// File name, relative to the maFiles directory.
+	pub name: String,
+	/// Hex encoded SHA-256 of the file contents.
+	pub sha256: String,
+	/// Base64 encoded file contents.
+	pub contents: String,
+}
+
+impl BackupFile {
+	fn new(name: String, buf: &[u8]) -> Self {
+		Self {
+			name,
+			sha256: format!("{:x}", Sha256::digest(buf)),
+			contents: base64::engine::general_purpose::STANDARD.encode(buf),
+		}
+	}
+
+	/// Returns the file contents, after verifying the checksum.
+	fn decode(&self) -> Result<Vec<u8>, BackupError> {
+		let buf = base64::engine::general_purpose::STANDARD
+			.decode(&self.contents)
+			.map_err(|_| BackupError::Corrupted(self.name.clone()))?;
+		if format!("{:x}", Sha256::digest(&buf)) != self.sha256 {
+			return Err(BackupError::Corrupted(self.name.clone()));
+		}
+		Ok(buf)
+	}
+}
+
+impl BackupArchive {
+	pub const CURRENT_VERSION: u32 = 1;
+
+	pub fn seal(&self, passkey: &str) -> anyhow::Result<Envelope> {
+		let plaintext = serde_json::to_vec(self)?;
+		Ok(Envelope::seal(EnvelopeKind::Backup, passkey, plaintext)?)
+	}
+
+	/// Decrypt a backup, and verify its integrity.
+	pub fn open(envelope: &Envelope, passkey: &str) -> anyhow::Result<Self> {
+		let plaintext = envelope.open(EnvelopeKind::Backup, passkey)?;
+		// the encryption is not authenticated, so a wrong passkey can occasionally produce garbage instead of an error
+		if plaintext.first() != Some(&b'{') {
+			return Err(EnvelopeError::IncorrectPasskey.into());
+		}
+		let mut deser = serde_json::Deserializer::from_slice(&plaintext);
+		let archive: Self =
+			serde_path_to_error::deserialize(&mut deser).context("parsing backup contents")?;
+		archive.verify()?;
+		Ok(archive)
+	}
+
+	/// Checks that every file is intact, and that every maFile in the manifest is present.
+	pub fn verify(&self) -> Result<(), BackupError> {
+		if self.version > Self::CURRENT_VERSION {
+			return Err(BackupError::UnsupportedVersion(self.version));
+		}
+		let mut names = HashSet::new();
+		for file in self.files.iter() {
+			if !is_plain_file_name(&file.name) || !names.insert(file.name.as_str()) {
+				return Err(BackupError::InvalidFileName(file.name.clone()));
+			}
+			file.decode()?;
+		}
+		for entry in self.manifest()?.entries.iter() {
+			if !names.contains(entry.filename.as_str()) {
+				return Err(BackupError::MissingFile(entry.filename.clone()));
+			}
+		}
+		Ok(())
+	}
+
+	/// The manifest, as it was when the backup was created.
+	pub fn manifest(&self) -> Result<Manifest, BackupError> {
+		let buf = self.file(MANIFEST_FILENAME)?;
+		Ok(serde_json::from_slice(&buf)?)
+	}
+
+	fn file(&self, name: &str) -> Result<Vec<u8>, BackupError> {
+		self.files
+			.iter()
+			.find(|f| f.name == name)
+			.ok_or_else(|| BackupError::MissingFile(name.to_owned()))?
+			.decode()
+	}
+
+	/// Load all accounts in the backup.
+	///
+	/// `passkey` is the passkey the maFiles were encrypted with when the backup was created, not the one used to encrypt the backup itself.
+	pub fn load_accounts(
+		&self,
+		passkey: Option<&SecretString>,
+	) -> anyhow::Result<Vec<SteamGuardAccount>, ManifestAccountLoadError> {
+		let manifest = self.manifest().map_err(anyhow::Error::from)?;
+		manifest
+			.entries
+			.iter()
+			.map(|entry| {
+				let buf = self.file(&entry.filename).map_err(anyhow::Error::from)?;
+				decode_entry(
+					buf,
+					passkey,
+					entry.encryption.as_ref(),
+					&entry.associated_data(),
+				)
+			})
+			.collect()
+	}
+}
+
+impl AccountManager {
+	/// Pack `manifest.json` and every maFile it references into a [`BackupArchive`].
+	pub fn backup(&self) -> anyhow::Result<BackupArchive> {
+		let folder = Path::new(&self.folder);
+		let mut files = vec![];
+		for name in std::iter::once(MANIFEST_FILENAME)
+			.chain(self.manifest.entries.iter().map(|e| e.filename.as_str()))
+		{
+			let path = folder.join(name);
+			let buf =
+				std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+			files.push(BackupFile::new(name.to_owned(), &buf));
+		}
+		Ok(BackupArchive {
+			version: BackupArchive::CURRENT_VERSION,
+			created_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+			created_by: env!("CARGO_PKG_VERSION").to_owned(),
+			files,
+		})
+	}
+
+	/// Names of the accounts in `archive` that are already in this manifest.
+	pub fn backup_collisions(&self, archive: &BackupArchive) -> Result<Vec<String>, BackupError> {
+		Ok(archive
+			.manifest()?
+			.entries
+			.into_iter()
+			.filter(|e| self.account_exists(&e.account_name))
+			.map(|e| e.account_name)
+			.collect())
+	}
+
+	/// Replace the manifest and all maFiles with the ones in `archive`. The existing files are kept around with a `.bak` extension.
+	///
+	/// This writes directly to disk, so the manager should not be used or saved afterwards.
+	pub fn restore_replace(&self, archive: &BackupArchive) -> anyhow::Result<()> {
+		archive.verify()?;
+		let folder = Path::new(&self.folder);
+		let manifest_path = folder.join(MANIFEST_FILENAME);
+		for entry in self.manifest.entries.iter() {
+			let path = folder.join(&entry.filename);
+			if path.exists() {
+				backup_file(&path)?;
+				std::fs::remove_file(&path)?;
+			}
+		}
+		if manifest_path.exists() {
+			backup_file(&manifest_path)?;
+		}
+
+		// the manifest is written last, so an interrupted restore never references missing maFiles
+		for file in archive.files.iter().filter(|f| f.name != MANIFEST_FILENAME) {
+			debug!("restoring {}", file.name);
+			write_atomic(&folder.join(&file.name), &file.decode()?)?;
+		}
+		write_atomic(&manifest_path, &archive.file(MANIFEST_FILENAME)?)?;
+		Ok(())
+	}
+}
+
+/// Make sure a restore can't write outside of the maFiles directory.
+fn is_plain_file_name(name: &str) -> bool {
+	!name.is_empty()
+		&& name != "."
+		&& name != ".."
+		&& !name.contains(['/', '\\'])
+		&& Path::new(name).file_name().is_some_and(|n| n == name)
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+	#[error("Backup is missing {0}.")]
+	MissingFile(String),
+	#[error("{0} is corrupted, its checksum does not match.")]
+	Corrupted(String),
+	#[error("Backup contains an invalid or duplicate file name: {0}")]
+	InvalidFileName(String),
+	#[error("This backup was created by a newer version of steamguard-cli (version {0}). Update steamguard-cli and try again.")]
+	UnsupportedVersion(u32),
+	#[error("Failed to parse the manifest in the backup: {0}")]
+	InvalidManifest(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::encryption::EncryptionScheme;
+	use secrecy::ExposeSecret;
+	use tempfile::TempDir;
+
+	fn account(name: &str) -> SteamGuardAccount {
+		let mut account = SteamGuardAccount::new();
+		account.account_name = name.into();
+		account.steam_id = 76561199000000000;
+		account.revocation_code = String::from("R12345").into();
+		account
+	}
+
+	fn manager_with(dir: &TempDir, names: &[&str]) -> AccountManager {
+		let mut manager = AccountManager::new(&dir.path().join(MANIFEST_FILENAME));
+		for name in names {
+			manager.add_account(account(name));
+		}
+		manager.save().unwrap();
+		manager
+	}
+
+	#[test]
+	fn test_backup_roundtrip() -> anyhow::Result<()> {
+		let dir = TempDir::new()?;
+		let manager = manager_with(&dir, &["example1", "example2"]);
+
+		let envelope = manager.backup()?.seal("backup password")?;
+		let serialized = serde_json::to_vec(&envelope)?;
+		let envelope = Envelope::parse(&serialized).unwrap();
+		let archive = BackupArchive::open(&envelope, "backup password")?;
+		assert_eq!(archive.files.len(), 3);
+		let accounts = archive.load_accounts(None)?;
+		assert_eq!(accounts.len(), 2);
+		assert_eq!(accounts[1].account_name, "example2");
+		assert_eq!(accounts[1].revocation_code.expose_secret(), "R12345");
+
+		let other = TempDir::new()?;
+		let other = manager_with(&other, &["example2", "example3"]);
+		assert_eq!(other.backup_collisions(&archive)?, vec!["example2"]);
+		Ok(())
+	}
+
+	#[test]
+	fn test_backup_keeps_mafiles_encrypted() -> anyhow::Result<()> {
+		let dir = TempDir::new()?;
+		let mut manager = manager_with(&dir, &["example"]);
+		let passkey = SecretString::new("mafiles password".into());
+		manager.load_accounts()?;
+		manager.manifest.entries[0].encryption = Some(EncryptionScheme::generate());
+		manager.submit_passkey(Some(passkey.clone()));
+		manager.save()?;
+
+		let archive = manager.backup()?;
+		assert!(matches!(
+			archive.load_accounts(None),
+			Err(ManifestAccountLoadError::MissingPasskey)
+		));
+		let accounts = archive.load_accounts(Some(&passkey))?;
+		assert_eq!(accounts[0].account_name, "example");
+		Ok(())
+	}
+
+	#[test]
+	fn test_verify_detects_corruption() -> anyhow::Result<()> {
+		let dir = TempDir::new()?;
+		let manager = manager_with(&dir, &["example"]);
+
+		let mut archive = manager.backup()?;
+		archive.files[1].contents = base64::engine::general_purpose::STANDARD.encode(b"{}");
+		assert!(matches!(archive.verify(), Err(BackupError::Corrupted(_))));
+
+		let mut archive = manager.backup()?;
+		archive.files.pop();
+		assert!(matches!(archive.verify(), Err(BackupError::MissingFile(_))));
+
+		let mut archive = manager.backup()?;
+		archive.files[1].name = "../example.maFile".into();
+		assert!(matches!(
+			archive.verify(),
+			Err(BackupError::InvalidFileName(_))
+		));
+		Ok(())
+	}
+
+	#[test]
+	fn test_restore_replace() -> anyhow::Result<()> {
+		let dir = TempDir::new()?;
+		let archive = manager_with(&dir, &["example1"]).backup()?;
+
+		let other = TempDir::new()?;
+		let manager = manager_with(&other, &["example2"]);
+		manager.restore_replace(&archive)?;
+		assert!(!other.path().join("example2.maFile").exists());
+		assert!(other.path().join("example2.maFile.bak").exists());
+		assert!(other.path().join("manifest.json.bak").exists());
+
+		let mut restored = AccountManager::load(&other.path().join(MANIFEST_FILENAME))?;
+		restored.load_accounts()?;
+		assert!(restored.account_exists(&"example1".into()));
+		assert!(!restored.account_exists(&"example2".into()));
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_names() {
+		assert!(is_plain_file_name("manifest.json"));
+		assert!(is_plain_file_name("example.maFile"));
+		assert!(!is_plain_file_name(""));
+		assert!(!is_plain_file_name(".."));
+		assert!(!is_plain_file_name("../manifest.json"));
+		assert!(!is_plain_file_name("/etc/passwd"));
+		assert!(!is_plain_file_name("foo\\bar"));
+	}
+}
@@ -0,0 +1,315 @@
+//! Serializes accounts into formats that other tools (and `steamguard import`) understand.
+
+use std::{fs::File, io::Write, path::Path};
+
+use anyhow::Context;
+use reqwest::Url;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use steamguard::SteamGuardAccount;
+
+use crate::encryption::{
+	EntryEncryptor, Envelope, EnvelopeError, EnvelopeKind, LegacySdaCompatible,
+};
+
+/// An account in the format used by SteamDesktopAuthenticator maFiles. Winauth uses the same format in the `data` field of its exports.
+#[derive(Debug, Serialize)]
+struct SdaAccountExport<'a> {
+	shared_secret: &'a steamguard::token::TwoFactorSecret,
+	serial_number: &'a str,
+	revocation_code: &'a str,
+	uri: &'a str,
+	server_time: u64,
+	account_name: &'a str,
+	token_gid: &'a str,
+	identity_secret: &'a str,
+	secret_1: &'a str,
+	status: u32,
+	device_id: &'a str,
+	fully_enrolled: bool,
+	#[serde(rename = "Session")]
+	session: SdaSessionExport,
+}
+
+#[derive(Debug, Serialize)]
+struct SdaSessionExport {
+	#[serde(rename = "SteamID")]
+	steam_id: u64,
+}
+
+impl<'a> From<&'a SteamGuardAccount> for SdaAccountExport<'a> {
+	fn from(account: &'a SteamGuardAccount) -> Self {
+		Self {
+			shared_secret: &account.shared_secret,
+			serial_number: &account.serial_number,
+			revocation_code: account.revocation_code.expose_secret(),
+			uri: account.uri.expose_secret(),
+			server_time: 0,
+			account_name: &account.account_name,
+			token_gid: &account.token_gid,
+			identity_secret: account.identity_secret.expose_secret(),
+			secret_1: account.secret_1.expose_secret(),
+			status: 1,
+			device_id: &account.device_id,
+			fully_enrolled: true,
+			session: SdaSessionExport {
+				steam_id: account.steam_id,
+			},
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct SdaManifestExport {
+	encrypted: bool,
+	first_run: bool,
+	entries: Vec<SdaManifestEntryExport>,
+	periodic_checking: bool,
+	periodic_checking_interval: i32,
+	periodic_checking_checkall: bool,
+	auto_confirm_market_transactions: bool,
+	auto_confirm_trades: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SdaManifestEntryExport {
+	encryption_iv: Option<String>,
+	encryption_salt: Option<String>,
+	filename: String,
+	steamid: u64,
+}
+
+/// Build a Winauth compatible `otpauth://` URI for the account.
+pub fn to_winauth_uri(account: &SteamGuardAccount) -> anyhow::Result<String> {
+	let data = serde_json::to_string(&SdaAccountExport::from(account))?;
+	let mut url = Url::parse("otpauth://totp/")?;
+	url.set_path(&format!("Steam:{}", account.account_name));
+	url.query_pairs_mut()
+		.append_pair(
+			"secret",
+			&base32_encode(account.shared_secret.expose_secret()),
+		)
+		.append_pair("digits", "5")
+		.append_pair("issuer", "Steam")
+		.append_pair("deviceid", &account.device_id)
+		.append_pair("data", &data);
+	Ok(url.into())
+}
+
+/// Write the accounts to `dir` as SteamDesktopAuthenticator maFiles, along with a SDA manifest. If `passkey` is provided, the maFiles are encrypted with [`LegacySdaCompatible`].
+///
+/// Refuses to write into a directory that already has a manifest.
+pub fn write_sda_export(
+	accounts: &[SteamGuardAccount],
+	dir: &Path,
+	passkey: Option<&str>,
+) -> anyhow::Result<()> {
+	let manifest_path = dir.join("manifest.json");
+	ensure!(
+		!manifest_path.exists(),
+		"{} already exists, refusing to overwrite it.",
+		manifest_path.display()
+	);
+	std::fs::create_dir_all(dir)?;
+
+	let mut entries = vec![];
+	for account in accounts {
+		let filename = format!("{}.maFile", account.steam_id);
+		let mut buf = serde_json::to_vec(&SdaAccountExport::from(account))?;
+		let mut entry = SdaManifestEntryExport {
+			encryption_iv: None,
+			encryption_salt: None,
+			filename,
+			steamid: account.steam_id,
+		};
+		if let Some(passkey) = passkey {
+			let scheme = LegacySdaCompatible::generate();
+			buf = scheme.encrypt(passkey, buf)?;
+			entry.encryption_iv = Some(scheme.iv);
+			entry.encryption_salt = Some(scheme.salt);
+		}
+		write_new_file(&dir.join(&entry.filename), &buf)?;
+		entries.push(entry);
+	}
+
+	let manifest = SdaManifestExport {
+		encrypted: passkey.is_some(),
+		first_run: true,
+		entries,
+		periodic_checking: false,
+		periodic_checking_interval: 5,
+		periodic_checking_checkall: false,
+		auto_confirm_market_transactions: false,
+		auto_confirm_trades: false,
+	};
+	write_new_file(&manifest_path, &serde_json::to_vec(&manifest)?)?;
+	Ok(())
+}
+
+/// Accounts packed into a single file, and encrypted with an [`Envelope`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountBundle {
+	pub accounts: Vec<SteamGuardAccount>,
+}
+
+impl AccountBundle {
+	pub fn seal(&self, passkey: &str) -> anyhow::Result<Envelope> {
+		let plaintext = serde_json::to_vec(self)?;
+		Ok(Envelope::seal(EnvelopeKind::Bundle, passkey, plaintext)?)
+	}
+
+	pub fn open(envelope: &Envelope, passkey: &str) -> anyhow::Result<Self> {
+		let plaintext = envelope.open(EnvelopeKind::Bundle, passkey)?;
+		// the encryption is not authenticated, so a wrong passkey can occasionally produce garbage instead of an error
+		if plaintext.first() != Some(&b'{') {
+			return Err(EnvelopeError::IncorrectPasskey.into());
+		}
+		let mut deser = serde_json::Deserializer::from_slice(&plaintext);
+		serde_path_to_error::deserialize(&mut deser).context("parsing bundle contents")
+	}
+}
+
+pub(crate) fn write_new_file(path: &Path, buf: &[u8]) -> anyhow::Result<()> {
+	let mut options = File::options();
+	options.write(true).create_new(true);
+	// exports contain secrets, so don't let other users read them
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+	let mut file = options
+		.open(path)
+		.with_context(|| format!("creating {}", path.display()))?;
+	file.write_all(buf)?;
+	file.sync_data()?;
+	Ok(())
+}
+
+/// RFC 4648 base32, without padding. This is how TOTP secrets are usually encoded in `otpauth://` URIs.
+fn base32_encode(data: &[u8]) -> String {
+	const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+	let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+	let mut buffer: u16 = 0;
+	let mut bits = 0;
+	for &byte in data {
+		buffer = (buffer << 8) | byte as u16;
+		bits += 8;
+		while bits >= 5 {
+			bits -= 5;
+			out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+		}
+	}
+	if bits > 0 {
+		out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::TempDir;
+
+	use super::*;
+	use crate::accountmanager::{migrate::load_and_migrate, winauth::parse_winauth_exports};
+
+	fn example_account() -> SteamGuardAccount {
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		account.steam_id = 1234;
+		account.serial_number = "kljasfhds".into();
+		account.revocation_code = String::from("R12345").into();
+		account.shared_secret = steamguard::token::TwoFactorSecret::parse_shared_secret(
+			"zvIayp3JPvtvX/QGHqsqKBk/44s=".into(),
+		)
+		.unwrap();
+		account.token_gid = "jkkjlhkhjgf".into();
+		account.identity_secret = String::from("kjsdlwowiqe=").into();
+		account.uri = String::from("otpauth://totp/Steam:example?secret=ASDF&issuer=Steam").into();
+		account.device_id = "android:99d2ad0e-4bad-4247-b111-26393aae0be3".into();
+		account.secret_1 = String::from("sklduhfgsdlkjhf=").into();
+		account
+	}
+
+	fn assert_same_account(a: &SteamGuardAccount, b: &SteamGuardAccount) {
+		assert_eq!(a.account_name, b.account_name);
+		assert_eq!(a.steam_id, b.steam_id);
+		assert_eq!(a.shared_secret, b.shared_secret);
+		assert_eq!(
+			a.identity_secret.expose_secret(),
+			b.identity_secret.expose_secret()
+		);
+		assert_eq!(
+			a.revocation_code.expose_secret(),
+			b.revocation_code.expose_secret()
+		);
+		assert_eq!(a.device_id, b.device_id);
+	}
+
+	#[test]
+	fn test_base32_encode() {
+		assert_eq!(base32_encode(b""), "");
+		assert_eq!(base32_encode(b"f"), "MY");
+		assert_eq!(base32_encode(b"fooba"), "MZXW6YTB");
+		assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+	}
+
+	#[test]
+	fn test_winauth_roundtrip() -> anyhow::Result<()> {
+		let account = example_account();
+		let uri = to_winauth_uri(&account)?;
+		assert!(uri.starts_with("otpauth://totp/Steam:example?secret="));
+
+		let imported = parse_winauth_exports(format!("{}\n", uri).into_bytes())?;
+		assert_eq!(imported.len(), 1);
+		let imported: SteamGuardAccount = imported.into_iter().next().unwrap().into();
+		assert_same_account(&account, &imported);
+		Ok(())
+	}
+
+	#[test]
+	fn test_sda_export_can_be_migrated() -> anyhow::Result<()> {
+		for passkey in [None, Some("password")] {
+			let tmp_dir = TempDir::new()?;
+			write_sda_export(&[example_account()], tmp_dir.path(), passkey)?;
+
+			let passkey = passkey.map(|p| secrecy::SecretString::new(p.to_owned()));
+			let (manifest, accounts) =
+				load_and_migrate(&tmp_dir.path().join("manifest.json"), passkey.as_ref())?;
+			assert_eq!(manifest.entries.len(), 1);
+			assert_same_account(&example_account(), &accounts[0]);
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn test_sda_export_refuses_to_overwrite() -> anyhow::Result<()> {
+		let tmp_dir = TempDir::new()?;
+		write_sda_export(&[example_account()], tmp_dir.path(), None)?;
+		assert!(write_sda_export(&[example_account()], tmp_dir.path(), None).is_err());
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_exported_files_are_private() -> anyhow::Result<()> {
+		use std::os::unix::fs::PermissionsExt;
+
+		let tmp_dir = TempDir::new()?;
+		write_sda_export(&[example_account()], tmp_dir.path(), None)?;
+		for entry in std::fs::read_dir(tmp_dir.path())? {
+			let mode = entry?.metadata()?.permissions().mode();
+			assert_eq!(mode & 0o777, 0o600);
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn test_bundle_roundtrip() -> anyhow::Result<()> {
+		let bundle = AccountBundle {
+			accounts: vec![example_account()],
+		};
+		let envelope = bundle.seal("password")?;
+		let opened = AccountBundle::open(&envelope, "password")?;
+		assert_same_account(&example_account(), &opened.accounts[0]);
+		assert!(AccountBundle::open(&envelope, "wrong").is_err());
+		Ok(())
+	}
+}
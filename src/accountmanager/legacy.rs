@@ -14,7 +14,7 @@ use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::encryption::{EntryEncryptor, LegacySdaCompatible};
 
-use super::{EncryptionScheme, EntryLoader, ManifestAccountLoadError, ManifestEntry, ManifestV1};
+use super::{EncryptionScheme, EntryLoader, ManifestAccountLoadError, ManifestEntryV1, ManifestV1};
 
 #[derive(Debug, Deserialize)]
 pub struct SdaManifest {
@@ -43,6 +43,7 @@ impl From<SdaManifest> for ManifestV1 {
 			version: 1,
 			entries: sda.entries.into_iter().map(|e| e.into()).collect(),
 			keyring_id: None,
+			api_token: None,
 		}
 	}
 }
@@ -56,7 +57,7 @@ pub struct SdaManifestEntry {
 	pub encryption: Option<SdaEntryEncryptionParams>,
 }
 
-impl From<SdaManifestEntry> for ManifestEntry {
+impl From<SdaManifestEntry> for ManifestEntryV1 {
 	fn from(sda: SdaManifestEntry) -> Self {
 		Self {
 			filename: sda.filename,
@@ -180,6 +181,7 @@ impl From<SdaAccount> for SteamGuardAccount {
 			device_id: value.device_id,
 			secret_1: value.secret_1,
 			tokens: None,
+			credentials: None,
 		}
 	}
 }
@@ -0,0 +1,103 @@
+//! Advisory locking of the maFiles directory, so that multiple steamguard processes don't clobber each other's changes.
+
+use std::{
+	fs::{File, TryLockError},
+	io::{Read, Seek, Write},
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+use log::*;
+use thiserror::Error;
+
+const LOCK_FILENAME: &str = ".steamguard-cli.lock";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive lock on a maFiles directory. Released when dropped.
+#[derive(Debug)]
+pub struct ManifestLock {
+	file: File,
+	path: PathBuf,
+}
+
+impl ManifestLock {
+	/// Lock the maFiles directory `folder`, waiting up to `timeout` for other processes to release it.
+	pub fn acquire(folder: &Path, timeout: Duration) -> Result<Self, LockError> {
+		let path = folder.join(LOCK_FILENAME);
+		let mut file = File::options()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&path)?;
+		let start = Instant::now();
+		let mut warned = false;
+		loop {
+			match file.try_lock() {
+				Ok(()) => break,
+				Err(TryLockError::WouldBlock) => {
+					if start.elapsed() >= timeout {
+						return Err(LockError::Held {
+							path,
+							holder: read_holder(&mut file),
+						});
+					}
+					if !warned {
+						info!("Waiting for another steamguard process to release the lock on the maFiles directory...");
+						warned = true;
+					}
+					std::thread::sleep(POLL_INTERVAL);
+				}
+				Err(TryLockError::Error(err)) => return Err(err.into()),
+			}
+		}
+		debug!("locked {}", path.display());
+
+		// record who holds the lock, to make the error more helpful for anyone else waiting on it
+		file.set_len(0)?;
+		file.write_all(std::process::id().to_string().as_bytes())?;
+		Ok(Self { file, path })
+	}
+}
+
+impl Drop for ManifestLock {
+	fn drop(&mut self) {
+		debug!("unlocking {}", self.path.display());
+		if let Err(err) = self.file.unlock() {
+			warn!("Failed to unlock {}: {}", self.path.display(), err);
+		}
+	}
+}
+
+fn read_holder(file: &mut File) -> Option<u32> {
+	let mut buf = String::new();
+	file.rewind().ok()?;
+	file.read_to_string(&mut buf).ok()?;
+	buf.trim().parse().ok()
+}
+
+#[derive(Debug, Error)]
+pub enum LockError {
+	#[error("The maFiles directory is locked by another steamguard process{}. Wait for it to finish, or use --lock-timeout to wait longer. Lock file: {}", holder.map(|pid| format!(" (pid {})", pid)).unwrap_or_default(), path.display())]
+	Held { path: PathBuf, holder: Option<u32> },
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_lock_is_exclusive() {
+		let dir = TempDir::new().unwrap();
+		let lock = ManifestLock::acquire(dir.path(), Duration::ZERO).unwrap();
+		match ManifestLock::acquire(dir.path(), Duration::from_millis(200)) {
+			Err(LockError::Held { holder, .. }) => assert_eq!(holder, Some(std::process::id())),
+			other => panic!("expected the lock to be held, got {:?}", other),
+		}
+		drop(lock);
+		assert!(ManifestLock::acquire(dir.path(), Duration::ZERO).is_ok());
+	}
+}
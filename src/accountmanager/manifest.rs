@@ -1,33 +1,206 @@
+use std::{collections::BTreeMap, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
 use super::EncryptionScheme;
+use crate::encryption::PasskeyProvider;
 
-pub const CURRENT_MANIFEST_VERSION: u32 = 1;
-pub type Manifest = ManifestV1;
-pub type ManifestEntry = ManifestEntryV1;
+pub const CURRENT_MANIFEST_VERSION: u32 = 2;
+pub type Manifest = ManifestV2;
+pub type ManifestEntry = ManifestEntryV2;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ManifestV1 {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestV2 {
 	pub version: u32,
 	pub entries: Vec<ManifestEntry>,
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	pub keyring_id: Option<String>,
+	/// Bearer token required to access the local HTTP API. See `steamguard serve`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub api_token: Option<String>,
+	/// Where to get the passkey from when it isn't given on the command line. See `steamguard passkey-provider`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub passkey_provider: Option<PasskeyProvider>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ManifestEntryV1 {
+pub struct ManifestEntryV2 {
 	pub filename: String,
 	pub steam_id: u64,
 	pub account_name: String,
 	pub encryption: Option<EncryptionScheme>,
+	/// Labels used to select accounts, eg. `group=storage`. Tags without a value are stored with an empty value.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub tags: BTreeMap<String, String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub note: Option<String>,
+	/// Command that prints the account's password, used to log in again without prompting. See `steamguard credentials`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub password_command: Option<Vec<String>>,
 }
 
-impl Default for ManifestV1 {
+impl Default for ManifestV2 {
 	fn default() -> Self {
 		Self {
-			version: 1,
+			version: CURRENT_MANIFEST_VERSION,
 			entries: vec![],
 			keyring_id: None,
+			api_token: None,
+			passkey_provider: None,
+		}
+	}
+}
+
+impl From<ManifestV1> for ManifestV2 {
+	fn from(v1: ManifestV1) -> Self {
+		Self {
+			version: 2,
+			entries: v1.entries.into_iter().map(|e| e.into()).collect(),
+			keyring_id: v1.keyring_id,
+			api_token: v1.api_token,
+			passkey_provider: None,
+		}
+	}
+}
+
+impl From<ManifestEntryV1> for ManifestEntryV2 {
+	fn from(v1: ManifestEntryV1) -> Self {
+		Self {
+			filename: v1.filename,
+			steam_id: v1.steam_id,
+			account_name: v1.account_name,
+			encryption: v1.encryption,
+			tags: BTreeMap::new(),
+			note: None,
+			password_command: None,
+		}
+	}
+}
+
+impl ManifestEntryV2 {
+	/// Data that authenticated encryption schemes bind the maFile to, so it can't be swapped with another account's maFile.
+	///
+	/// Only uses fields that never change, and lowercases the account name because `auto_upgrade` may do that.
+	pub fn associated_data(&self) -> Vec<u8> {
+		entry_associated_data(self.steam_id, &self.account_name)
+	}
+
+	/// Whether this entry has the tag `key`, and if `value` is given, whether it has that value.
+	pub fn has_tag(&self, key: &str, value: Option<&str>) -> bool {
+		match (self.tags.get(key), value) {
+			(Some(v), Some(value)) => v == value,
+			(Some(_), None) => true,
+			(None, _) => false,
+		}
+	}
+}
+
+/// A tag, written as `key=value`, or just `key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+	pub key: String,
+	pub value: Option<String>,
+}
+
+impl Tag {
+	pub fn matches(&self, entry: &ManifestEntry) -> bool {
+		entry.has_tag(&self.key, self.value.as_deref())
+	}
+}
+
+impl FromStr for Tag {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (key, value) = match s.split_once('=') {
+			Some((key, value)) => (key, Some(value.to_owned())),
+			None => (s, None),
+		};
+		let key = key.trim();
+		ensure!(!key.is_empty(), "Tag name cannot be empty: {:?}", s);
+		Ok(Self {
+			key: key.to_owned(),
+			value,
+		})
+	}
+}
+
+impl std::fmt::Display for Tag {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.value {
+			Some(value) => write!(f, "{}={}", self.key, value),
+			None => write!(f, "{}", self.key),
 		}
 	}
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestV1 {
+	pub version: u32,
+	pub entries: Vec<ManifestEntryV1>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub keyring_id: Option<String>,
+	/// Bearer token required to access the local HTTP API. See `steamguard serve`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub api_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntryV1 {
+	pub filename: String,
+	pub steam_id: u64,
+	pub account_name: String,
+	pub encryption: Option<EncryptionScheme>,
+}
+
+pub(crate) fn entry_associated_data(steam_id: u64, account_name: &str) -> Vec<u8> {
+	format!(
+		"steamguard-cli maFile\0{}\0{}",
+		steam_id,
+		account_name.to_lowercase()
+	)
+	.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_tag() {
+		assert_eq!(
+			"group=storage".parse::<Tag>().unwrap(),
+			Tag {
+				key: "group".into(),
+				value: Some("storage".into())
+			}
+		);
+		assert_eq!(
+			"vip".parse::<Tag>().unwrap(),
+			Tag {
+				key: "vip".into(),
+				value: None
+			}
+		);
+		assert_eq!("note=a=b".parse::<Tag>().unwrap().value.unwrap(), "a=b");
+		assert!("=storage".parse::<Tag>().is_err());
+		assert!("".parse::<Tag>().is_err());
+	}
+
+	#[test]
+	fn test_upgrade_v1_keeps_everything() {
+		let v1: ManifestV1 = serde_json::from_str(
+			r#"{"version":1,"entries":[{"filename":"1234.maFile","steam_id":1234,"account_name":"example","encryption":null}],"keyring_id":"abc","api_token":"def"}"#,
+		)
+		.unwrap();
+		let v2: ManifestV2 = v1.into();
+		assert_eq!(v2.version, 2);
+		assert_eq!(v2.keyring_id.as_deref(), Some("abc"));
+		assert_eq!(v2.api_token.as_deref(), Some("def"));
+		assert_eq!(v2.entries[0].account_name, "example");
+		assert!(v2.entries[0].tags.is_empty());
+		let serialized = serde_json::to_string(&v2).unwrap();
+		assert!(!serialized.contains("tags"));
+		assert!(!serialized.contains("note"));
+	}
+}
@@ -10,7 +10,8 @@ use crate::encryption::EncryptionScheme;
 
 use super::{
 	legacy::{SdaAccount, SdaManifest},
-	manifest::ManifestV1,
+	load_entry_file,
+	manifest::{entry_associated_data, ManifestV1, ManifestV2},
 	steamv2::SteamMobileV2,
 	winauth::parse_winauth_exports,
 	EntryLoader, Manifest,
@@ -49,7 +50,9 @@ fn do_migrate(
 		deserialize_manifest(buffer).map_err(MigrationError::ManifestDeserializeFailed)?;
 
 	if manifest.is_encrypted() && passkey.is_none() {
-		return Err(MigrationError::MissingPasskey { keyring_id: None });
+		return Err(MigrationError::MissingPasskey {
+			keyring_id: manifest.keyring_id(),
+		});
 	} else if !manifest.is_encrypted() && passkey.is_some() {
 		// no custom error because this is an edge case, mostly user error
 		return Err(MigrationError::UnexpectedError(anyhow::anyhow!("A passkey was provided but the manifest is not encrypted. Aborting migration because it would encrypt the maFiles, and you probably didn't mean to do that.")));
@@ -76,7 +79,7 @@ fn do_migrate(
 	Ok((manifest, accounts))
 }
 
-fn backup_file(path: &Path) -> anyhow::Result<()> {
+pub(crate) fn backup_file(path: &Path) -> anyhow::Result<()> {
 	let backup_path = Path::join(
 		path.parent().unwrap(),
 		format!("{}.bak", path.file_name().unwrap().to_str().unwrap()),
@@ -101,24 +104,35 @@ pub(crate) enum MigrationError {
 enum MigratingManifest {
 	Sda(SdaManifest),
 	ManifestV1(ManifestV1),
+	ManifestV2(ManifestV2),
 }
 
 impl MigratingManifest {
 	pub fn upgrade(self) -> Self {
 		match self {
 			Self::Sda(sda) => Self::ManifestV1(sda.into()),
-			Self::ManifestV1(_) => self,
+			Self::ManifestV1(v1) => Self::ManifestV2(v1.into()),
+			Self::ManifestV2(_) => self,
 		}
 	}
 
 	pub fn is_latest(&self) -> bool {
-		matches!(self, Self::ManifestV1(_))
+		matches!(self, Self::ManifestV2(_))
 	}
 
 	pub fn is_encrypted(&self) -> bool {
 		match self {
 			Self::Sda(manifest) => manifest.entries.iter().any(|e| e.encryption.is_some()),
 			Self::ManifestV1(manifest) => manifest.entries.iter().any(|e| e.encryption.is_some()),
+			Self::ManifestV2(manifest) => manifest.entries.iter().any(|e| e.encryption.is_some()),
+		}
+	}
+
+	pub fn keyring_id(&self) -> Option<String> {
+		match self {
+			Self::Sda(_) => None,
+			Self::ManifestV1(manifest) => manifest.keyring_id.clone(),
+			Self::ManifestV2(manifest) => manifest.keyring_id.clone(),
 		}
 	}
 
@@ -153,40 +167,67 @@ impl MigratingManifest {
 					.map(MigratingAccount::External)
 					.collect()
 			}
-			Self::ManifestV1(manifest) => {
-				let (accounts, errors) = manifest
-					.entries
-					.iter()
-					.map(|e| {
-						e.load(
-							&Path::join(folder, &e.filename),
-							passkey,
-							e.encryption.as_ref(),
-						)
-					})
-					.partition::<Vec<_>, _>(Result::is_ok);
-				let accounts: Vec<_> = accounts.into_iter().map(Result::unwrap).collect();
-				let errors: Vec<_> = errors.into_iter().map(Result::unwrap_err).collect();
-				if !errors.is_empty() {
-					return Err(anyhow::anyhow!(
-						"Failed to load some accounts: {:?}",
-						errors
-					));
-				}
-				accounts
-					.into_iter()
-					.map(MigratingAccount::ManifestV1)
-					.collect()
-			}
+			Self::ManifestV1(manifest) => load_manifest_accounts(
+				manifest.entries.iter().map(|e| {
+					(
+						e.filename.as_str(),
+						e.encryption.as_ref(),
+						entry_associated_data(e.steam_id, &e.account_name),
+					)
+				}),
+				folder,
+				passkey,
+			)?,
+			Self::ManifestV2(manifest) => load_manifest_accounts(
+				manifest.entries.iter().map(|e| {
+					(
+						e.filename.as_str(),
+						e.encryption.as_ref(),
+						entry_associated_data(e.steam_id, &e.account_name),
+					)
+				}),
+				folder,
+				passkey,
+			)?,
 		};
 		Ok(accounts)
 	}
 }
 
+/// Load the accounts of a steamguard-cli manifest, given each entry's filename, encryption params and associated data.
+fn load_manifest_accounts<'a>(
+	entries: impl Iterator<Item = (&'a str, Option<&'a EncryptionScheme>, Vec<u8>)>,
+	folder: &Path,
+	passkey: Option<&SecretString>,
+) -> anyhow::Result<Vec<MigratingAccount>> {
+	let (accounts, errors) = entries
+		.map(|(filename, encryption, associated_data)| {
+			load_entry_file(
+				&Path::join(folder, filename),
+				passkey,
+				encryption,
+				&associated_data,
+			)
+		})
+		.partition::<Vec<_>, _>(Result::is_ok);
+	let accounts: Vec<_> = accounts.into_iter().map(Result::unwrap).collect();
+	let errors: Vec<_> = errors.into_iter().map(Result::unwrap_err).collect();
+	if !errors.is_empty() {
+		return Err(anyhow::anyhow!(
+			"Failed to load some accounts: {:?}",
+			errors
+		));
+	}
+	Ok(accounts
+		.into_iter()
+		.map(MigratingAccount::ManifestV1)
+		.collect())
+}
+
 impl From<MigratingManifest> for Manifest {
 	fn from(migrating: MigratingManifest) -> Self {
 		match migrating {
-			MigratingManifest::ManifestV1(manifest) => manifest,
+			MigratingManifest::ManifestV2(manifest) => manifest,
 			_ => panic!("Manifest is not at the latest version!"),
 		}
 	}
@@ -211,6 +252,10 @@ fn deserialize_manifest(
 			let manifest: ManifestV1 = serde_path_to_error::deserialize(&mut deser)?;
 			Ok(MigratingManifest::ManifestV1(manifest))
 		}
+		Some(2) => {
+			let manifest: ManifestV2 = serde_path_to_error::deserialize(&mut deser)?;
+			Ok(MigratingManifest::ManifestV2(manifest))
+		}
 		None => {
 			let manifest: SdaManifest = serde_path_to_error::deserialize(&mut deser)?;
 			Ok(MigratingManifest::Sda(manifest))
@@ -365,6 +410,22 @@ mod tests {
 				manifest: "src/fixtures/maFiles/compat/missing-unnecessary/manifest.json",
 				passkey: None,
 			},
+			Test {
+				manifest: "src/fixtures/maFiles/manifest-v1/1-account/manifest.json",
+				passkey: None,
+			},
+			Test {
+				manifest: "src/fixtures/maFiles/manifest-v1/1-account-encrypted/manifest.json",
+				passkey: Some(SecretString::new("password".into())),
+			},
+			Test {
+				manifest: "src/fixtures/maFiles/manifest-v1/2-account/manifest.json",
+				passkey: None,
+			},
+			Test {
+				manifest: "src/fixtures/maFiles/manifest-v1/missing-account-name/manifest.json",
+				passkey: None,
+			},
 		];
 		for case in cases {
 			eprintln!("testing: {:?}", case);
@@ -476,6 +537,10 @@ mod tests {
 				dir: "src/fixtures/maFiles/compat/null-oauthtoken/",
 				passkey: None,
 			},
+			Test {
+				dir: "src/fixtures/maFiles/manifest-v1/1-account-encrypted/",
+				passkey: Some(SecretString::new("password".into())),
+			},
 		];
 		for case in cases {
 			eprintln!("testing: {:?}", case);
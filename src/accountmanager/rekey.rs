@@ -0,0 +1,274 @@
+use std::path::Path;
+
+use log::*;
+use rayon::prelude::*;
+use secrecy::{ExposeSecret, SecretString};
+
+use super::{write_atomic, AccountManager, ManifestEntry};
+use crate::encryption::{AgeRecipients, Argon2Params, EncryptionScheme, EntryEncryptor};
+
+impl AccountManager {
+	/// Re-encrypt every account under `new_passkey`, with a fresh salt for each entry. All accounts must be loaded first.
+	///
+	/// Entries are encrypted with the given Argon2 parameters, or if `None`, the parameters they already use.
+	pub fn rekey(
+		&mut self,
+		new_passkey: SecretString,
+		params: Option<Argon2Params>,
+	) -> anyhow::Result<()> {
+		self.reencrypt(new_passkey.expose_secret(), |entry| {
+			let params = params
+				.or_else(|| entry.encryption.as_ref()?.argon2_params())
+				.unwrap_or_default();
+			Some(EncryptionScheme::generate_with_params(params))
+		})?;
+		self.passkey = Some(new_passkey);
+		Ok(())
+	}
+
+	/// Re-encrypt every account to the given age recipients. All accounts must be loaded first.
+	pub fn rekey_to_recipients(&mut self, recipients: AgeRecipients) -> anyhow::Result<()> {
+		self.reencrypt("", |_| {
+			Some(EncryptionScheme::AgeRecipients(recipients.clone()))
+		})
+	}
+
+	/// Re-encrypt the entries that use `Argon2idAes256` with `Argon2idXChaCha20Poly1305`, keeping their Argon2 parameters. All accounts must be loaded first.
+	///
+	/// Other entries are left as they are. In particular, `LegacySdaCompatible` entries are kept so SteamDesktopAuthenticator can still read them.
+	pub fn upgrade_encryption(&mut self, passkey: &SecretString) -> anyhow::Result<()> {
+		self.reencrypt(passkey.expose_secret(), |entry| match &entry.encryption {
+			Some(EncryptionScheme::Argon2idAes256(scheme)) => {
+				Some(EncryptionScheme::generate_with_params(scheme.params))
+			}
+			_ => None,
+		})
+	}
+
+	/// Re-encrypt every account with the scheme returned by `scheme_for`. Entries for which it returns `None` are left as they are.
+	///
+	/// Only the manifest knows how each maFile is encrypted, so maFiles can't simply be overwritten one at a time: a crash in between would leave maFiles that don't match their manifest entries. Instead, the new maFiles are first written under temporary names, and the manifest is switched over to them in a single atomic write. Then they are moved back to their original names the same way.
+	fn reencrypt(
+		&mut self,
+		passkey: &str,
+		scheme_for: impl Fn(&ManifestEntry) -> Option<EncryptionScheme> + Sync,
+	) -> anyhow::Result<()> {
+		ensure!(
+			self.manifest
+				.entries
+				.iter()
+				.all(|e| self.accounts.contains_key(&e.account_name)),
+			"All accounts must be loaded before they can be re-encrypted."
+		);
+		let _lock = self.lock_for_writing()?;
+		let folder = Path::new(&self.folder).to_owned();
+
+		// encrypt everything up front, so nothing is written if any of it fails
+		info!("Encrypting {} accounts", self.manifest.entries.len());
+		let staged = self
+			.manifest
+			.entries
+			.par_iter()
+			.map(
+				|entry| -> anyhow::Result<Option<(EncryptionScheme, Vec<u8>)>> {
+					let Some(scheme) = scheme_for(entry) else {
+						return Ok(None);
+					};
+					let account = self.accounts[&entry.account_name].lock().unwrap();
+					let plaintext = serde_json::to_vec(&*account)?;
+					let ciphertext =
+						scheme.encrypt_with_ad(passkey, plaintext, &entry.associated_data())?;
+					Ok(Some((scheme, ciphertext)))
+				},
+			)
+			.collect::<anyhow::Result<Vec<_>>>()?;
+
+		let filenames: Vec<String> = self
+			.manifest
+			.entries
+			.iter()
+			.map(|e| e.filename.clone())
+			.collect();
+		let temp_filenames: Vec<String> =
+			filenames.iter().map(|f| format!("{}.rekey", f)).collect();
+
+		debug!("writing re-encrypted maFiles to temporary files");
+		for (i, (scheme, ciphertext)) in staged
+			.iter()
+			.enumerate()
+			.filter_map(|(i, s)| Some((i, s.as_ref()?)))
+		{
+			write_atomic(&folder.join(&temp_filenames[i]), ciphertext)?;
+			let entry = &mut self.manifest.entries[i];
+			entry.filename.clone_from(&temp_filenames[i]);
+			entry.encryption = Some(scheme.clone());
+		}
+		self.write_manifest()?;
+
+		debug!("moving re-encrypted maFiles back to their original names");
+		for (i, (_, ciphertext)) in staged
+			.iter()
+			.enumerate()
+			.filter_map(|(i, s)| Some((i, s.as_ref()?)))
+		{
+			write_atomic(&folder.join(&filenames[i]), ciphertext)?;
+			self.manifest.entries[i].filename.clone_from(&filenames[i]);
+		}
+		self.write_manifest()?;
+
+		for (temp_filename, _) in temp_filenames
+			.iter()
+			.zip(&staged)
+			.filter(|(_, s)| s.is_some())
+		{
+			if let Err(err) = std::fs::remove_file(folder.join(temp_filename)) {
+				warn!("Failed to remove {}: {}", temp_filename, err);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::accountmanager::ManifestAccountLoadError;
+	use steamguard::SteamGuardAccount;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_rekey() -> anyhow::Result<()> {
+		let old_passkey = SecretString::new("old".into());
+		let new_passkey = SecretString::new("new".into());
+		let dir = TempDir::new()?;
+		let manifest_path = dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(&manifest_path);
+		for name in ["example1", "example2"] {
+			let mut account = SteamGuardAccount::new();
+			account.account_name = name.into();
+			account.revocation_code = String::from("R12345").into();
+			manager.add_account(account);
+		}
+		for entry in manager.iter_mut() {
+			entry.encryption = Some(EncryptionScheme::generate());
+		}
+		manager.submit_passkey(Some(old_passkey.clone()));
+		manager.save()?;
+		let old_schemes: Vec<_> = manager
+			.iter()
+			.map(|e| serde_json::to_string(&e.encryption).unwrap())
+			.collect();
+
+		manager.rekey(new_passkey.clone(), None)?;
+
+		let mut reloaded = AccountManager::load(&manifest_path)?;
+		reloaded.submit_passkey(Some(old_passkey));
+		assert!(matches!(
+			reloaded.load_accounts(),
+			Err(ManifestAccountLoadError::IncorrectPasskey
+				| ManifestAccountLoadError::DecryptionFailed(_)
+				| ManifestAccountLoadError::DeserializationFailed(_))
+		));
+		reloaded.submit_passkey(Some(new_passkey));
+		reloaded.load_accounts()?;
+		let account = reloaded.get_account("example2")?;
+		assert_eq!(
+			account.lock().unwrap().revocation_code.expose_secret(),
+			"R12345"
+		);
+		for (entry, old) in reloaded.iter().zip(old_schemes) {
+			assert_eq!(entry.filename, format!("{}.maFile", entry.account_name));
+			assert_ne!(serde_json::to_string(&entry.encryption)?, old);
+		}
+
+		let mut files: Vec<_> = std::fs::read_dir(dir.path())?
+			.map(|e| e.unwrap().file_name().into_string().unwrap())
+			.collect();
+		files.sort();
+		assert_eq!(
+			files,
+			vec![
+				".steamguard-cli.lock",
+				"example1.maFile",
+				"example2.maFile",
+				"manifest.json"
+			]
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn test_rekey_requires_loaded_accounts() -> anyhow::Result<()> {
+		let dir = TempDir::new()?;
+		let manifest_path = dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(&manifest_path);
+		manager.add_account(SteamGuardAccount::new());
+		manager.save()?;
+
+		let mut manager = AccountManager::load(&manifest_path)?;
+		assert!(manager
+			.rekey(SecretString::new("new".into()), None)
+			.is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn test_rekey_params() -> anyhow::Result<()> {
+		let dir = TempDir::new()?;
+		let manifest_path = dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(&manifest_path);
+		manager.add_account(SteamGuardAccount::new());
+		manager.iter_mut().next().unwrap().encryption = Some(EncryptionScheme::generate());
+		manager.submit_passkey(Some(SecretString::new("old".into())));
+		manager.save()?;
+
+		let params_of = |manager: &AccountManager| {
+			let entry = manager.iter().next().unwrap();
+			entry.encryption.as_ref().unwrap().argon2_params().unwrap()
+		};
+		manager.rekey(SecretString::new("new".into()), Some(Argon2Params::LOW))?;
+		assert_eq!(params_of(&manager), Argon2Params::LOW);
+		manager.rekey(SecretString::new("newer".into()), None)?;
+		assert_eq!(params_of(&manager), Argon2Params::LOW);
+
+		let mut reloaded = AccountManager::load(&manifest_path)?;
+		assert_eq!(params_of(&reloaded), Argon2Params::LOW);
+		reloaded.submit_passkey(Some(SecretString::new("newer".into())));
+		reloaded.load_accounts()?;
+		Ok(())
+	}
+
+	#[test]
+	fn test_rekey_to_recipients() -> anyhow::Result<()> {
+		use age::secrecy::ExposeSecret as _;
+
+		let dir = TempDir::new()?;
+		let manifest_path = dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(&manifest_path);
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		manager.add_account(account);
+		manager.iter_mut().next().unwrap().encryption = Some(EncryptionScheme::generate());
+		manager.submit_passkey(Some(SecretString::new("password".into())));
+		manager.save()?;
+
+		let identity = age::x25519::Identity::generate();
+		let recipients = AgeRecipients::new(vec![identity.to_public().to_string()])?;
+		manager.rekey_to_recipients(recipients)?;
+
+		let mut reloaded = AccountManager::load(&manifest_path)?;
+		reloaded.submit_passkey(Some(SecretString::new("password".into())));
+		assert!(matches!(
+			reloaded.load_accounts(),
+			Err(ManifestAccountLoadError::IncorrectPasskey)
+		));
+		reloaded.submit_passkey(Some(SecretString::new(
+			identity.to_string().expose_secret().to_owned(),
+		)));
+		reloaded.load_accounts()?;
+		// saving only needs the recipients
+		reloaded.submit_passkey(None);
+		reloaded.save()?;
+		Ok(())
+	}
+}
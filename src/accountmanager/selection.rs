@@ -0,0 +1,135 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+use super::ManifestEntry;
+
+/// Selects accounts by account name, steam ID, or a glob pattern like `bot_*`.
+#[derive(Debug, Clone)]
+pub struct AccountPattern {
+	raw: String,
+	glob: Option<Regex>,
+}
+
+impl AccountPattern {
+	pub fn is_glob(&self) -> bool {
+		self.glob.is_some()
+	}
+
+	pub fn matches(&self, entry: &ManifestEntry) -> bool {
+		match &self.glob {
+			Some(glob) => glob.is_match(&entry.account_name),
+			None => entry.account_name == self.raw || self.raw.parse::<u64>() == Ok(entry.steam_id),
+		}
+	}
+}
+
+impl FromStr for AccountPattern {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		ensure!(!s.is_empty(), "Account name cannot be empty");
+		let glob = match s.contains(['*', '?', '[']) {
+			true => Some(glob_to_regex(s)?),
+			false => None,
+		};
+		Ok(Self {
+			raw: s.to_owned(),
+			glob,
+		})
+	}
+}
+
+impl std::fmt::Display for AccountPattern {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.raw)
+	}
+}
+
+/// Supports `*`, `?` and character classes like `[abc]`, `[a-z]` or `[!0-9]`.
+fn glob_to_regex(glob: &str) -> anyhow::Result<Regex> {
+	let mut re = String::from("^");
+	let mut chars = glob.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'*' => re.push_str(".*"),
+			'?' => re.push('.'),
+			'[' => {
+				re.push('[');
+				if chars.next_if_eq(&'!').is_some() {
+					re.push('^');
+				}
+				let mut closed = false;
+				for c in chars.by_ref() {
+					if c == ']' {
+						closed = true;
+						break;
+					}
+					if c == '\\' || c == '[' {
+						re.push('\\');
+					}
+					re.push(c);
+				}
+				ensure!(closed, "Unclosed character class in pattern: {}", glob);
+				re.push(']');
+			}
+			c => re.push_str(&regex::escape(&c.to_string())),
+		}
+	}
+	re.push('$');
+	Ok(Regex::new(&re)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(account_name: &str, steam_id: u64) -> ManifestEntry {
+		ManifestEntry {
+			filename: format!("{}.maFile", account_name),
+			steam_id,
+			account_name: account_name.to_owned(),
+			encryption: None,
+			tags: Default::default(),
+			note: None,
+			password_command: None,
+		}
+	}
+
+	fn matches(pattern: &str, account_name: &str) -> bool {
+		let pattern: AccountPattern = pattern.parse().unwrap();
+		pattern.matches(&entry(account_name, 1234))
+	}
+
+	#[test]
+	fn test_exact_name() {
+		assert!(matches("bot_1", "bot_1"));
+		assert!(!matches("bot_1", "bot_10"));
+		assert!(!matches("bot.1", "bot_1"));
+	}
+
+	#[test]
+	fn test_steam_id() {
+		let pattern: AccountPattern = "76561199000000000".parse().unwrap();
+		assert!(!pattern.is_glob());
+		assert!(pattern.matches(&entry("example", 76561199000000000)));
+		assert!(!pattern.matches(&entry("example", 76561199000000001)));
+	}
+
+	#[test]
+	fn test_globs() {
+		assert!(matches("bot_*", "bot_1"));
+		assert!(matches("bot_*", "bot_"));
+		assert!(!matches("bot_*", "xbot_1"));
+		assert!(matches("bot_?", "bot_1"));
+		assert!(!matches("bot_?", "bot_10"));
+		assert!(matches("bot_[12]", "bot_2"));
+		assert!(!matches("bot_[12]", "bot_3"));
+		assert!(matches("bot_[0-9]*", "bot_42"));
+		assert!(matches("bot_[!0-9]", "bot_x"));
+		assert!(!matches("bot_[!0-9]", "bot_4"));
+		assert!(matches("a.b*", "a.bc"));
+		assert!(!matches("a.b*", "axbc"));
+		assert!("bot_[12".parse::<AccountPattern>().is_err());
+	}
+}
@@ -58,6 +58,7 @@ impl From<SteamMobileV2> for SteamGuardAccount {
 			device_id: format!("android:{}", Uuid::new_v4()),
 			secret_1: account.secret_1,
 			tokens: None,
+			credentials: None,
 		}
 	}
 }
@@ -2,43 +2,75 @@ use std::sync::{Arc, Mutex};
 
 use clap::{Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 use status::StatusCommand;
 use std::str::FromStr;
 use steamguard::{transport::Transport, SteamGuardAccount};
 
-use crate::AccountManager;
+use crate::{
+	accountmanager::{AccountPattern, ManifestEntry, Tag},
+	encryption::EnvelopeError,
+	errors::PromptRequiredError,
+	AccountManager, LoginOptions, ManifestAccountLoadError,
+};
+use log::*;
 
 pub mod approve;
+pub mod backup;
+pub mod benchmark;
 pub mod code;
 pub mod completions;
 pub mod confirm;
+pub mod credentials;
 pub mod debug;
 pub mod decrypt;
 pub mod encrypt;
+pub mod export;
 pub mod import;
 pub mod list;
+pub mod login;
+pub mod passkey_provider;
 #[cfg(feature = "qr")]
 pub mod qr;
 pub mod qr_login;
+pub mod rekey;
 pub mod remove;
+pub mod restore;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod sessions;
 pub mod setup;
 pub mod status;
+pub mod tag;
+pub mod tokens;
 
 pub use approve::ApproveCommand;
+pub use backup::BackupCommand;
+pub use benchmark::BenchmarkCommand;
 pub use code::CodeCommand;
 pub use completions::CompletionsCommand;
 pub use confirm::ConfirmCommand;
+pub use credentials::CredentialsCommand;
 pub use debug::DebugCommand;
 pub use decrypt::DecryptCommand;
 pub use encrypt::EncryptCommand;
+pub use export::ExportCommand;
 pub use import::ImportCommand;
 pub use list::ListCommand;
+pub use login::LoginCommand;
+pub use passkey_provider::PasskeyProviderCommand;
 #[cfg(feature = "qr")]
 pub use qr::QrCommand;
 pub use qr_login::QrLoginCommand;
+pub use rekey::RekeyCommand;
 pub use remove::RemoveCommand;
-pub use setup::SetupCommand; // export new command
+pub use restore::RestoreCommand;
+#[cfg(feature = "server")]
+pub use serve::ServeCommand;
+pub use sessions::SessionsCommand;
+pub use setup::SetupCommand;
+pub use tag::{TagCommand, UntagCommand};
+pub use tokens::TokensCommand; // export new command
 
 /// A command that does not operate on the manifest or individual accounts.
 pub(crate) trait ConstCommand {
@@ -72,6 +104,67 @@ where
 	) -> anyhow::Result<()>;
 }
 
+/// Loads all accounts in the manifest, prompting for the passkey if needed.
+pub(crate) fn load_accounts_with_prompts(
+	manager: &mut AccountManager,
+	args: &GlobalArgs,
+) -> anyhow::Result<()> {
+	loop {
+		match manager.load_accounts() {
+			Ok(_) => return Ok(()),
+			Err(
+				ManifestAccountLoadError::MissingPasskey
+				| ManifestAccountLoadError::IncorrectPasskey,
+			) => {
+				if manager.has_passkey() {
+					error!("Incorrect passkey");
+				}
+				if args.non_interactive {
+					return Err(PromptRequiredError::Passkey.into());
+				}
+				let passkey = Some(crate::tui::prompt_passkey()?);
+				manager.submit_passkey(passkey);
+			}
+			Err(e) => {
+				error!("Could not load accounts: {}", e);
+				return Err(e.into());
+			}
+		}
+	}
+}
+
+/// Opens an [`Envelope`](crate::encryption::Envelope), prompting for the passkey until the correct one is entered.
+pub(crate) fn open_envelope_with_prompts<R>(
+	passkey: Option<SecretString>,
+	prompt: &str,
+	args: &GlobalArgs,
+	open: impl Fn(&str) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+	let mut passkey = passkey;
+	loop {
+		let Some(key) = &passkey else {
+			if args.non_interactive {
+				return Err(PromptRequiredError::Passkey.into());
+			}
+			passkey = Some(rpassword::prompt_password(prompt).map(SecretString::new)?);
+			continue;
+		};
+		match open(key.expose_secret()) {
+			Ok(r) => return Ok(r),
+			Err(err)
+				if matches!(
+					err.downcast_ref::<EnvelopeError>(),
+					Some(EnvelopeError::IncorrectPasskey)
+				) =>
+			{
+				error!("Incorrect passkey");
+				passkey = None;
+			}
+			Err(err) => return Err(err),
+		}
+	}
+}
+
 pub(crate) enum CommandType<T>
 where
 	T: Transport,
@@ -100,10 +193,10 @@ pub(crate) struct GlobalArgs {
 		short,
 		long,
 		conflicts_with = "all",
-		help = "Steam username, case-sensitive.",
-		long_help = "Select the account you want by steam username. Case-sensitive. By default, the first account in the manifest is selected."
+		help = "Steam username, case-sensitive. Can also be a steam ID or a glob like `bot_*`, and can be repeated.",
+		long_help = "Select the accounts you want by steam username. Case-sensitive. Steam IDs and glob patterns like `bot_*` (with `*`, `?` and `[...]`) are also accepted. Can be repeated to select multiple accounts. By default, the first account in the manifest is selected."
 	)]
-	pub username: Option<String>,
+	pub username: Vec<AccountPattern>,
 	#[clap(
 		long,
 		conflicts_with = "all",
@@ -118,6 +211,24 @@ pub(crate) struct GlobalArgs {
 		help = "Select all accounts in the manifest."
 	)]
 	pub all: bool,
+	#[clap(
+		long,
+		help = "Don't select these accounts. Accepts the same patterns as --username, and can be repeated.",
+		long_help = "Don't select these accounts. Accepts the same patterns as --username, and can be repeated. When used without --username, --all, --group or --tag, all other accounts are selected."
+	)]
+	pub exclude: Vec<AccountPattern>,
+	#[clap(
+		short,
+		long,
+		help = "Select all accounts in a group. Shorthand for `--tag group=<GROUP>`."
+	)]
+	pub group: Option<String>,
+	#[clap(
+		long,
+		help = "Select all accounts with a tag, written as `key=value` or `key`. Can be repeated.",
+		long_help = "Select all accounts with a tag, written as `key=value`, or `key` to match any value. Can be repeated, in which case accounts must have all of the tags. Tags are managed with `steamguard tag` and `steamguard untag`."
+	)]
+	pub tag: Vec<Tag>,
 	/// The path to the maFiles directory.
 	#[clap(
 		short,
@@ -133,8 +244,31 @@ pub(crate) struct GlobalArgs {
 		help = "Specify your encryption passkey."
 	)]
 	pub passkey: Option<SecretString>,
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_AGE_IDENTITY",
+		conflicts_with = "passkey",
+		help = "Decrypt maFiles encrypted to age recipients with the identities in this file.",
+		long_help = "Decrypt maFiles encrypted to age recipients with the identities in this file, as created by `age-keygen`. The file must only be accessible by its owner."
+	)]
+	pub age_identity: Option<std::path::PathBuf>,
 	#[clap(short, long, value_enum, default_value_t=Verbosity::Info, help = "Set the log level. Be warned, trace is capable of printing sensitive data.")]
 	pub verbosity: Verbosity,
+	#[clap(
+		long,
+		value_enum,
+		default_value_t = OutputFormat::Text,
+		help = "Set the output format.",
+		long_help = "Set the output format. With `json`, commands print a single JSON document to stdout instead of human readable text. Logs are still printed to stderr. See docs/json-output.md for the format of each command."
+	)]
+	pub output: OutputFormat,
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_NON_INTERACTIVE",
+		help = "Never prompt. Fail instead if something has to be entered, eg. a password or an email code.",
+		long_help = "Never prompt. Fail instead if something has to be entered, eg. a password, a passkey or an email code. These failures exit with 3, and the error message ends with a stable `(reason: ...)`. Useful for running under systemd or cron. Passwords can be stored for each account with `steamguard credentials`."
+	)]
+	pub non_interactive: bool,
 
 	#[cfg(feature = "updater")]
 	#[clap(
@@ -165,6 +299,75 @@ pub(crate) struct GlobalArgs {
 		long_help = "Accept invalid TLS certificates. Be warned, this is insecure and enables man-in-the-middle attacks."
 	)]
 	pub danger_accept_invalid_certs: bool,
+
+	#[clap(
+		long,
+		default_value_t = 10,
+		env = "STEAMGUARD_CLI_LOCK_TIMEOUT",
+		help = "How long to wait for other steamguard processes to release the maFiles directory, in seconds.",
+		long_help = "How long to wait for other steamguard processes to release the maFiles directory, in seconds. Commands that modify the maFiles lock the directory while they run, so that concurrent processes don't overwrite each other's changes."
+	)]
+	pub lock_timeout: u64,
+}
+
+impl GlobalArgs {
+	/// How to log in to an account when its refresh token can't be used.
+	pub fn login_options(&self, manager: &AccountManager, account_name: &str) -> LoginOptions {
+		LoginOptions {
+			password: self.password.clone(),
+			password_command: manager
+				.get_entry(account_name)
+				.ok()
+				.and_then(|entry| entry.password_command.clone()),
+			non_interactive: self.non_interactive,
+		}
+	}
+
+	/// Tags that selected accounts must have, from `--group` and `--tag`.
+	pub fn tag_filters(&self) -> Vec<Tag> {
+		let group = self.group.as_ref().map(|group| Tag {
+			key: "group".to_owned(),
+			value: Some(group.clone()),
+		});
+		group.into_iter().chain(self.tag.iter().cloned()).collect()
+	}
+}
+
+/// Names of the accounts selected with `--username`, `--all`, `--group`, `--tag` and `--exclude`, in manifest order. Defaults to the first account in the manifest.
+pub(crate) fn selected_account_names(
+	args: &GlobalArgs,
+	manager: &AccountManager,
+) -> Result<Vec<String>, ManifestAccountLoadError> {
+	// account names that don't match anything are most likely typos
+	for pattern in args.username.iter().filter(|p| !p.is_glob()) {
+		if !manager.iter().any(|entry| pattern.matches(entry)) {
+			error!("No account matches {}", pattern);
+			return Err(ManifestAccountLoadError::MissingManifestEntry);
+		}
+	}
+
+	let filters = args.tag_filters();
+	let everything = args.all || !filters.is_empty() || !args.exclude.is_empty();
+	let mut entries: Vec<&ManifestEntry> = match (args.username.is_empty(), everything) {
+		(false, _) => manager
+			.iter()
+			.filter(|entry| args.username.iter().any(|p| p.matches(entry)))
+			.collect(),
+		(true, true) => manager.iter().collect(),
+		(true, false) => manager.iter().take(1).collect(),
+	};
+	if entries.is_empty() && args.username.is_empty() && !everything {
+		return Err(ManifestAccountLoadError::MissingManifestEntry);
+	}
+	entries.retain(|entry| filters.iter().all(|tag| tag.matches(entry)));
+	entries.retain(|entry| !args.exclude.iter().any(|p| p.matches(entry)));
+	if entries.is_empty() {
+		return Err(ManifestAccountLoadError::NoMatchingAccounts);
+	}
+	Ok(entries
+		.into_iter()
+		.map(|e| e.account_name.clone())
+		.collect())
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -174,17 +377,52 @@ pub(crate) enum Subcommands {
 	Completion(CompletionsCommand),
 	Setup(SetupCommand),
 	Import(ImportCommand),
+	Export(ExportCommand),
+	Backup(BackupCommand),
+	Restore(RestoreCommand),
 	List(ListCommand),
+	Tag(TagCommand),
+	Untag(UntagCommand),
 	#[clap(alias = "trade")]
 	Confirm(ConfirmCommand),
 	Remove(RemoveCommand),
 	Encrypt(EncryptCommand),
 	Decrypt(DecryptCommand),
+	Rekey(RekeyCommand),
+	PasskeyProvider(PasskeyProviderCommand),
+	Benchmark(BenchmarkCommand),
 	Code(CodeCommand),
 	#[cfg(feature = "qr")]
 	Qr(QrCommand),
 	QrLogin(QrLoginCommand),
 	Status(StatusCommand),
+	Login(LoginCommand),
+	Sessions(SessionsCommand),
+	Tokens(TokensCommand),
+	Credentials(CredentialsCommand),
+	#[cfg(feature = "server")]
+	Serve(ServeCommand),
+}
+
+impl Subcommands {
+	/// Whether the maFiles directory should stay locked for the whole command.
+	///
+	/// Read-only and long-running commands only lock it while saving, so they don't block other commands.
+	pub fn holds_lock(&self) -> bool {
+		match self {
+			Self::List(_) | Self::Code(_) | Self::QrLogin(_) | Self::Export(_) | Self::Login(_) => {
+				false
+			}
+			#[cfg(feature = "qr")]
+			Self::Qr(_) => false,
+			#[cfg(feature = "server")]
+			Self::Serve(_) => false,
+			Self::Confirm(confirm) => !confirm.daemon,
+			Self::Approve(approve) => !approve.watch,
+			Self::Tokens(tokens) => !matches!(tokens.sub, tokens::TokensSubcommand::Check { .. }),
+			_ => true,
+		}
+	}
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -196,6 +434,12 @@ pub(crate) enum Verbosity {
 	Trace = 4,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+	Text,
+	Json,
+}
+
 impl std::fmt::Display for Verbosity {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.write_fmt(format_args!(
@@ -243,4 +487,85 @@ mod tests {
 		use clap::CommandFactory;
 		Args::command().debug_assert()
 	}
+
+	fn select(args: &[&str]) -> Result<Vec<String>, ManifestAccountLoadError> {
+		let mut manager = AccountManager::default();
+		for (i, name) in ["bot_1", "bot_2", "bot_10", "main"].iter().enumerate() {
+			let mut account = SteamGuardAccount::new();
+			account.account_name = name.to_string();
+			account.steam_id = 76561199000000000 + i as u64;
+			manager.add_account(account);
+		}
+		manager
+			.get_entry_mut("bot_2")?
+			.tags
+			.insert("group".into(), "storage".into());
+
+		let args = Args::try_parse_from(std::iter::once("steamguard").chain(args.iter().copied()))
+			.unwrap();
+		selected_account_names(&args.global, &manager)
+	}
+
+	#[test]
+	fn test_account_selection() {
+		assert_eq!(select(&[]).unwrap(), vec!["bot_1"]);
+		assert_eq!(select(&["-u", "main"]).unwrap(), vec!["main"]);
+		assert_eq!(
+			select(&["-u", "main", "-u", "76561199000000001"]).unwrap(),
+			vec!["bot_2", "main"]
+		);
+		assert_eq!(
+			select(&["-u", "bot_*"]).unwrap(),
+			vec!["bot_1", "bot_2", "bot_10"]
+		);
+		assert_eq!(
+			select(&["-u", "bot_*", "--exclude", "bot_?"]).unwrap(),
+			vec!["bot_10"]
+		);
+		assert_eq!(select(&["--exclude", "bot_*"]).unwrap(), vec!["main"]);
+		assert_eq!(
+			select(&["-u", "bot_*", "-g", "storage"]).unwrap(),
+			vec!["bot_2"]
+		);
+		assert_eq!(select(&["--all"]).unwrap().len(), 4);
+	}
+
+	#[test]
+	fn test_account_selection_errors() {
+		assert!(matches!(
+			select(&["-u", "nope"]),
+			Err(ManifestAccountLoadError::MissingManifestEntry)
+		));
+		assert!(matches!(
+			select(&["-u", "nope_*"]),
+			Err(ManifestAccountLoadError::NoMatchingAccounts)
+		));
+		assert!(matches!(
+			select(&["--all", "--exclude", "*"]),
+			Err(ManifestAccountLoadError::NoMatchingAccounts)
+		));
+	}
+
+	#[test]
+	fn test_open_envelope_non_interactive() {
+		let args = GlobalArgs::try_parse_from(["steamguard", "--non-interactive"]).unwrap();
+		let err =
+			open_envelope_with_prompts(None, "Enter passkey: ", &args, |_| Ok(())).unwrap_err();
+		assert!(matches!(
+			err.downcast_ref::<PromptRequiredError>(),
+			Some(PromptRequiredError::Passkey)
+		));
+
+		let err = open_envelope_with_prompts(
+			Some(SecretString::new("wrong".into())),
+			"Enter passkey: ",
+			&args,
+			|_| -> anyhow::Result<()> { Err(EnvelopeError::IncorrectPasskey.into()) },
+		)
+		.unwrap_err();
+		assert!(matches!(
+			err.downcast_ref::<PromptRequiredError>(),
+			Some(PromptRequiredError::Passkey)
+		));
+	}
 }
@@ -1,12 +1,20 @@
-use crate::commands::AccountCommand;
-use crate::{commands::GlobalArgs, AccountManager};
+use crate::commands::confirm::{backoff, DaemonAccountState};
+use crate::commands::{AccountCommand, OutputFormat};
+use crate::output::{print_json, SessionOutput};
+use crate::rules::{IpRange, SessionPlatform, SessionRule, SessionRuleAction, SessionRules};
+use crate::{commands::GlobalArgs, AccountManager, LoginOptions};
 use clap::Parser;
 use crossterm::tty::IsTty;
 use log::*;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use steamguard::approver::Challenge;
 use steamguard::protobufs::enums::ESessionPersistence;
-use steamguard::protobufs::steammessages_auth_steamclient::EAuthTokenPlatformType;
+use steamguard::protobufs::steammessages_auth_steamclient::CAuthentication_GetAuthSessionInfo_Response;
 use steamguard::transport::Transport;
 use steamguard::{ApproverError, LoginApprover, SteamGuardAccount};
 
@@ -16,16 +24,74 @@ pub struct ApproveCommand {
 	#[clap(
 		long,
 		help = "Blindly approve all pending login sessions without prompting.",
-		conflicts_with = "auto_approve_ip"
+		conflicts_with_all = ["auto_approve_ip", "auto_approve_country", "auto_approve_platform", "auto_approve_device_name", "deny_ip", "deny_country", "rules"]
 	)]
 	pub dangerously_approve_all: bool,
 
 	#[clap(
 		long,
-		help = "Automatically approve all login requests from the given IPv4 addresses non-interactively. Useful for CI/CD systems. Requests that don't match will be ignored.",
-		conflicts_with = "dangerously_approve_all"
+		value_name = "IP",
+		help = "Automatically approve login requests from these IP addresses or CIDR ranges, eg. `10.0.0.0/8` or `2001:db8::/32`. Can be repeated. Useful for CI/CD systems.",
+		long_help = "Automatically approve login requests from these IP addresses or CIDR ranges, eg. `203.0.113.7`, `10.0.0.0/8` or `2001:db8::/32`. Can be repeated. Useful for CI/CD systems.\n\nWhen combined with the other --auto-approve-* options, a request has to match all of them to be approved. Requests that don't match are skipped."
 	)]
-	pub auto_approve_ip: Vec<String>,
+	pub auto_approve_ip: Vec<IpRange>,
+
+	#[clap(
+		long,
+		value_name = "COUNTRY",
+		help = "Automatically approve login requests from these countries, as reported by Steam, eg. `US`. Can be repeated."
+	)]
+	pub auto_approve_country: Vec<String>,
+
+	#[clap(
+		long,
+		value_name = "PLATFORM",
+		help = "Automatically approve login requests from these platforms: `steam_client`, `web_browser`, `mobile_app` or `unknown`. Can be repeated."
+	)]
+	pub auto_approve_platform: Vec<SessionPlatform>,
+
+	#[clap(
+		long,
+		value_name = "REGEX",
+		help = "Automatically approve login requests whose device name matches this regex, eg. `^ci-runner-`."
+	)]
+	pub auto_approve_device_name: Option<Regex>,
+
+	#[clap(
+		long,
+		value_name = "IP",
+		help = "Deny login requests from these IP addresses or CIDR ranges. Can be repeated. Takes precedence over the --auto-approve-* options."
+	)]
+	pub deny_ip: Vec<IpRange>,
+
+	#[clap(
+		long,
+		value_name = "COUNTRY",
+		help = "Deny login requests from these countries, as reported by Steam. Can be repeated. Takes precedence over the --auto-approve-* options."
+	)]
+	pub deny_country: Vec<String>,
+
+	#[clap(
+		long,
+		conflicts_with_all = ["auto_approve_ip", "auto_approve_country", "auto_approve_platform", "auto_approve_device_name", "deny_ip", "deny_country"],
+		help = "Path to a JSON rules file. Sessions are approved or denied according to the first matching rule, and skipped if no rule matches."
+	)]
+	pub rules: Option<PathBuf>,
+
+	#[clap(
+		long,
+		help = "Keep running, and apply the rules to new login requests for all selected accounts periodically. Never prompts.",
+		long_help = "Keep running, and apply the rules to new login requests for all selected accounts periodically. Never prompts. Requires --rules, --dangerously-approve-all, or one of the --auto-approve-* or --deny-* options.\n\nAccounts that aren't logged in are logged in once at startup, which fails instead of prompting, so store the credentials with `steamguard credentials` first. After that, only the access tokens are refreshed."
+	)]
+	pub watch: bool,
+
+	#[clap(
+		long,
+		default_value_t = 5,
+		requires = "watch",
+		help = "How often to check for new login requests with --watch, in seconds."
+	)]
+	pub interval: u64,
 }
 
 impl<T> AccountCommand<T> for ApproveCommand
@@ -39,12 +105,26 @@ where
 		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
 		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
+		let rules = self.session_rules()?;
+		if self.watch {
+			ensure!(
+				args.output == OutputFormat::Text,
+				"JSON output is not supported with --watch. Every decision is logged to stderr instead."
+			);
+			let Some(rules) = rules else {
+				bail!("--watch requires --rules, --dangerously-approve-all, or one of the --auto-approve-* or --deny-* options.");
+			};
+			return self.run_watch(transport, manager, accounts, &rules, args);
+		}
+
+		let mut results = vec![];
 		for a in accounts {
 			let mut account = a.lock().unwrap();
 
 			if !account.is_logged_in() {
 				info!("Account does not have tokens, logging in");
-				crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+				let login = args.login_options(manager, &account.account_name);
+				crate::do_login(transport.clone(), &mut account, &login, manager.time_sync())?;
 			}
 
 			let mut did_relogin = false;
@@ -62,62 +142,43 @@ where
 					Ok(sessions) => break (sessions, approver),
 					Err(ApproverError::Unauthorized) if !did_relogin => {
 						info!("Access token expired, re-logging in...");
-						crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+						let login = args.login_options(manager, &account.account_name);
+						crate::do_login(
+							transport.clone(),
+							&mut account,
+							&login,
+							manager.time_sync(),
+						)?;
 						did_relogin = true;
 					}
 					Err(err) => return Err(err.into()),
 				}
 			};
+			let mut output = ApproveAccountOutput {
+				account_name: account.account_name.clone(),
+				sessions: vec![],
+			};
 			if sessions.is_empty() {
 				info!("No pending sessions to approve");
+				results.push(output);
 				continue;
 			}
 
 			info!("Found {} pending sessions", sessions.len());
 
-			if self.dangerously_approve_all {
-				info!("Approving all pending sessions");
-				for client_id in sessions {
-					let challenge = Challenge::new(1, client_id);
-					approver.approve(
-						&account,
-						challenge,
-						ESessionPersistence::k_ESessionPersistence_Persistent,
-					)?;
+			if let Some(rules) = rules.as_ref() {
+				if self.dangerously_approve_all {
+					info!("Approving all pending sessions");
 				}
-			} else if !self.auto_approve_ip.is_empty() {
-				for client_id in sessions {
-					let session = approver.get_auth_session_info(client_id)?;
-
-					if !session.has_ip() || !self.auto_approve_ip.contains(&session.ip().to_owned())
-					{
-						info!("Skipping session {} from IP {}", client_id, session.ip());
-						continue;
-					}
-
-					info!("Approving session {} from IP {}", client_id, session.ip());
-					let challenge = Challenge::new(1, client_id);
-					approver.approve(
-						&account,
-						challenge,
-						ESessionPersistence::k_ESessionPersistence_Persistent,
-					)?;
-				}
-			} else if std::io::stdout().is_tty() {
+				apply_rules(&account, &mut approver, &sessions, rules, &mut output)?;
+			} else if args.output == OutputFormat::Text
+				&& std::io::stdout().is_tty()
+				&& !args.non_interactive
+			{
 				let total = sessions.len();
 				for (session_idx, client_id) in sessions.iter().enumerate() {
 					let session = approver.get_auth_session_info(*client_id)?;
 
-					let platform_str = match session.platform_type() {
-						EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown => "Unknown",
-						EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient => {
-							"Steam Client"
-						}
-						EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser => {
-							"Web Browser"
-						}
-						EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => "Mobile App",
-					};
 					eprintln!(
 						"[{session_idx}/{total}] Do you recognize this login attempt?
 
@@ -133,7 +194,7 @@ where
 						session.city(),
 						session.state(),
 						session.country(),
-						platform_str,
+						SessionPlatform::from(session.platform_type()),
 						session.device_friendly_name(),
 					);
 
@@ -174,9 +235,406 @@ where
 				}
 			} else {
 				info!("Non-interactive mode, skipping all sessions");
+				if args.output == OutputFormat::Json {
+					for client_id in sessions {
+						let session = approver.get_auth_session_info(client_id)?;
+						output.push(client_id, &session, SessionAction::Skipped);
+					}
+				}
 			}
+			results.push(output);
 		}
 		manager.save()?;
+		if args.output == OutputFormat::Json {
+			print_json(&serde_json::json!({ "accounts": results }))?;
+		}
 		Ok(())
 	}
 }
+
+impl ApproveCommand {
+	/// The rules to decide with, from --rules or the other options. `None` means the user should be asked about every session.
+	fn session_rules(&self) -> anyhow::Result<Option<SessionRules>> {
+		if let Some(path) = &self.rules {
+			return SessionRules::load(path).map(Some);
+		}
+		if self.dangerously_approve_all {
+			// a rule without conditions matches everything
+			return Ok(Some(SessionRules {
+				rules: vec![SessionRule {
+					action: SessionRuleAction::Approve,
+					..Default::default()
+				}],
+			}));
+		}
+
+		let mut rules = vec![];
+		if !self.deny_ip.is_empty() {
+			rules.push(SessionRule {
+				action: SessionRuleAction::Deny,
+				ips: self.deny_ip.clone(),
+				..Default::default()
+			});
+		}
+		if !self.deny_country.is_empty() {
+			rules.push(SessionRule {
+				action: SessionRuleAction::Deny,
+				countries: self.deny_country.clone(),
+				..Default::default()
+			});
+		}
+		let approve = SessionRule {
+			action: SessionRuleAction::Approve,
+			ips: self.auto_approve_ip.clone(),
+			countries: self.auto_approve_country.clone(),
+			platforms: self.auto_approve_platform.clone(),
+			device_name: self.auto_approve_device_name.clone(),
+		};
+		if approve.has_conditions() {
+			rules.push(approve);
+		}
+		Ok((!rules.is_empty()).then_some(SessionRules { rules }))
+	}
+
+	fn run_watch<T>(
+		&self,
+		transport: T,
+		manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		rules: &SessionRules,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()>
+	where
+		T: Transport + Clone,
+	{
+		for a in &accounts {
+			let mut account = a.lock().unwrap();
+			if !account.is_logged_in() {
+				info!(
+					"{}: Account does not have tokens, logging in",
+					account.account_name
+				);
+				let login = LoginOptions {
+					non_interactive: true,
+					..args.login_options(manager, &account.account_name)
+				};
+				crate::do_login(transport.clone(), &mut account, &login, manager.time_sync())?;
+			}
+		}
+		manager.save()?;
+
+		let interval = Duration::from_secs(self.interval.max(1));
+		info!(
+			"Checking for login requests every {} seconds for {} accounts",
+			interval.as_secs(),
+			accounts.len()
+		);
+		let mut handled = vec![HashSet::new(); accounts.len()];
+		let mut states = vec![DaemonAccountState::default(); accounts.len()];
+		loop {
+			for ((a, handled), state) in accounts
+				.iter()
+				.zip(handled.iter_mut())
+				.zip(states.iter_mut())
+			{
+				if state.next_poll.is_some_and(|t| Instant::now() < t) {
+					continue;
+				}
+
+				let mut account = a.lock().unwrap();
+				let account_name = account.account_name.clone();
+				let mut refreshed_tokens = false;
+				let result = watch_poll(
+					transport.clone(),
+					&mut account,
+					rules,
+					handled,
+					&mut refreshed_tokens,
+				);
+				drop(account);
+
+				// save() only writes what this process changed, so other instances sharing the maFiles aren't clobbered
+				if refreshed_tokens {
+					manager.save()?;
+				}
+				match result {
+					Ok(_) => {
+						state.consecutive_failures = 0;
+						state.next_poll = Some(Instant::now() + interval);
+					}
+					Err(err) => {
+						state.consecutive_failures += 1;
+						let wait = backoff(interval, state.consecutive_failures);
+						error!(
+							"{}: Failed to process login requests: {} ({} failures in a row), waiting {} seconds before trying again",
+							account_name,
+							err,
+							state.consecutive_failures,
+							wait.as_secs()
+						);
+						state.next_poll = Some(Instant::now() + wait);
+					}
+				}
+			}
+
+			let now = Instant::now();
+			let wait = states
+				.iter()
+				.filter_map(|s| s.next_poll)
+				.min()
+				.map(|t| t.saturating_duration_since(now))
+				.unwrap_or(interval)
+				.max(Duration::from_secs(1));
+			debug!("sleeping for {} seconds", wait.as_secs());
+			std::thread::sleep(wait);
+		}
+	}
+}
+
+/// Check for login requests once, and apply the rules to the ones that haven't been handled yet. Tokens are refreshed without prompting if needed.
+///
+/// `handled` holds the client IDs of sessions that were already decided on, so skipped sessions aren't logged again on every poll.
+fn watch_poll<T>(
+	transport: T,
+	account: &mut SteamGuardAccount,
+	rules: &SessionRules,
+	handled: &mut HashSet<u64>,
+	refreshed_tokens: &mut bool,
+) -> anyhow::Result<()>
+where
+	T: Transport + Clone,
+{
+	debug!("{}: Checking for login requests", account.account_name);
+	let sessions = loop {
+		let Some(tokens) = account.tokens.as_ref() else {
+			bail!("{} is not logged in", account.account_name);
+		};
+		match LoginApprover::new(transport.clone(), tokens).list_auth_sessions() {
+			Ok(sessions) => break sessions,
+			Err(ApproverError::Unauthorized) if !*refreshed_tokens => {
+				info!("{}: Refreshing access token", account.account_name);
+				crate::refresh_tokens(transport.clone(), account)?;
+				*refreshed_tokens = true;
+			}
+			Err(err) => return Err(err.into()),
+		}
+	};
+
+	handled.retain(|client_id| sessions.contains(client_id));
+	let new_sessions: Vec<u64> = sessions
+		.into_iter()
+		.filter(|client_id| !handled.contains(client_id))
+		.collect();
+	if new_sessions.is_empty() {
+		return Ok(());
+	}
+
+	let Some(tokens) = account.tokens.as_ref() else {
+		bail!("{} is not logged in", account.account_name);
+	};
+	let mut approver = LoginApprover::new(transport, tokens);
+	let mut output = ApproveAccountOutput {
+		account_name: account.account_name.clone(),
+		sessions: vec![],
+	};
+	apply_rules(account, &mut approver, &new_sessions, rules, &mut output)?;
+	handled.extend(new_sessions);
+	Ok(())
+}
+
+/// Approve or deny each session according to the first matching rule, and log every decision. Sessions that don't match any rule are skipped.
+fn apply_rules<T>(
+	account: &SteamGuardAccount,
+	approver: &mut LoginApprover<T>,
+	sessions: &[u64],
+	rules: &SessionRules,
+	output: &mut ApproveAccountOutput,
+) -> anyhow::Result<()>
+where
+	T: Transport,
+{
+	for &client_id in sessions {
+		let session = approver.get_auth_session_info(client_id)?;
+		let description = format!(
+			"from {} ({}), {} \"{}\"",
+			session.ip(),
+			session.country(),
+			SessionPlatform::from(session.platform_type()),
+			session.device_friendly_name()
+		);
+		let challenge = Challenge::new(1, client_id);
+		match rules.decide(&session) {
+			Some((i, SessionRuleAction::Approve)) => {
+				info!(
+					"{}: Approving session {} {}, matched rule #{}",
+					account.account_name,
+					client_id,
+					description,
+					i + 1
+				);
+				approver.approve(
+					account,
+					challenge,
+					ESessionPersistence::k_ESessionPersistence_Persistent,
+				)?;
+				output.push(client_id, &session, SessionAction::Approved);
+			}
+			Some((i, SessionRuleAction::Deny)) => {
+				info!(
+					"{}: Denying session {} {}, matched rule #{}",
+					account.account_name,
+					client_id,
+					description,
+					i + 1
+				);
+				approver.deny(account, challenge)?;
+				output.push(client_id, &session, SessionAction::Denied);
+			}
+			None => {
+				info!(
+					"{}: Skipping session {} {}, no rule matched",
+					account.account_name, client_id, description
+				);
+				output.push(client_id, &session, SessionAction::Skipped);
+			}
+		}
+	}
+	Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SessionAction {
+	Approved,
+	Denied,
+	Skipped,
+}
+
+#[derive(Debug, Serialize)]
+struct ApproveSessionOutput {
+	#[serde(flatten)]
+	session: SessionOutput,
+	action: SessionAction,
+}
+
+#[derive(Debug, Serialize)]
+struct ApproveAccountOutput {
+	account_name: String,
+	sessions: Vec<ApproveSessionOutput>,
+}
+
+impl ApproveAccountOutput {
+	fn push(
+		&mut self,
+		client_id: u64,
+		session: &CAuthentication_GetAuthSessionInfo_Response,
+		action: SessionAction,
+	) {
+		self.sessions.push(ApproveSessionOutput {
+			session: SessionOutput::new(client_id, session),
+			action,
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use steamguard::{
+		protobufs::steammessages_auth_steamclient::{
+			CAuthentication_GetAuthSessionsForAccount_Response,
+			CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request,
+			CAuthentication_UpdateAuthSessionWithMobileConfirmation_Response,
+		},
+		token::Tokens,
+		transport::MockTransport,
+	};
+
+	fn respond_with_sessions(transport: &MockTransport, client_ids: &[u64]) {
+		let mut resp = CAuthentication_GetAuthSessionsForAccount_Response::new();
+		resp.client_ids = client_ids.to_vec();
+		transport.respond_with("IAuthenticationService", "GetAuthSessionsForAccount", resp);
+	}
+
+	fn respond_with_session_info(transport: &MockTransport, ip: &str) {
+		let mut resp = CAuthentication_GetAuthSessionInfo_Response::new();
+		resp.set_ip(ip.to_owned());
+		transport.respond_with("IAuthenticationService", "GetAuthSessionInfo", resp);
+	}
+
+	#[test]
+	fn test_session_rules_deny_first() {
+		let cmd = ApproveCommand::parse_from([
+			"approve",
+			"--auto-approve-ip",
+			"10.0.0.0/8",
+			"--auto-approve-platform",
+			"web-browser",
+			"--deny-ip",
+			"10.1.0.0/16",
+		]);
+		let rules = cmd.session_rules().unwrap().unwrap();
+		assert_eq!(rules.rules.len(), 2);
+		assert_eq!(rules.rules[0].action, SessionRuleAction::Deny);
+		assert_eq!(rules.rules[1].action, SessionRuleAction::Approve);
+		assert_eq!(rules.rules[1].platforms, vec![SessionPlatform::WebBrowser]);
+
+		let cmd = ApproveCommand::parse_from(["approve"]);
+		assert!(cmd.session_rules().unwrap().is_none());
+	}
+
+	#[test]
+	fn test_watch_poll_handles_each_session_once() {
+		let transport = MockTransport::new();
+		respond_with_sessions(&transport, &[1, 2]);
+		respond_with_session_info(&transport, "10.0.0.1");
+		transport.respond_with(
+			"IAuthenticationService",
+			"UpdateAuthSessionWithMobileConfirmation",
+			CAuthentication_UpdateAuthSessionWithMobileConfirmation_Response::new(),
+		);
+		respond_with_session_info(&transport, "192.0.2.1");
+		respond_with_sessions(&transport, &[2]);
+
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		account.tokens = Some(Tokens::new("access".to_owned(), "refresh".to_owned()));
+		let rules = SessionRules {
+			rules: vec![SessionRule {
+				action: SessionRuleAction::Approve,
+				ips: vec!["10.0.0.0/8".parse().unwrap()],
+				..Default::default()
+			}],
+		};
+		let mut handled = HashSet::new();
+		let mut refreshed_tokens = false;
+		for _ in 0..2 {
+			watch_poll(
+				transport.clone(),
+				&mut account,
+				&rules,
+				&mut handled,
+				&mut refreshed_tokens,
+			)
+			.unwrap();
+		}
+
+		let requests = transport.requests();
+		let endpoints: Vec<String> = requests.iter().map(|req| req.endpoint()).collect();
+		assert_eq!(
+			endpoints,
+			vec![
+				"IAuthenticationService/GetAuthSessionsForAccount",
+				"IAuthenticationService/GetAuthSessionInfo",
+				"IAuthenticationService/UpdateAuthSessionWithMobileConfirmation",
+				"IAuthenticationService/GetAuthSessionInfo",
+				"IAuthenticationService/GetAuthSessionsForAccount",
+			]
+		);
+		let req: CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request =
+			requests[2].decode().unwrap();
+		assert_eq!(req.client_id(), 1);
+		assert!(req.confirm());
+		assert_eq!(handled, HashSet::from([2]));
+	}
+}
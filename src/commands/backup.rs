@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use log::*;
+
+use crate::{accountmanager::export::write_new_file, output::print_json, tui, AccountManager};
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Back up the manifest and all maFiles into a single encrypted file. Restore it with `steamguard restore`."
+)]
+pub struct BackupCommand {
+	#[clap(
+		long,
+		help = "Where to write the backup. Existing files are never overwritten."
+	)]
+	pub path: PathBuf,
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_BACKUP_PASSKEY",
+		help = "Passkey to encrypt the backup with. If not provided, you will be prompted for one.",
+		long_help = "Passkey to encrypt the backup with. This is separate from the passkey used to encrypt your maFiles, which stay encrypted with their own passkey inside the backup. If not provided, you will be prompted for one."
+	)]
+	pub backup_passkey: Option<SecretString>,
+}
+
+impl<T> ManifestCommand<T> for BackupCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		let archive = manager.backup()?;
+		let passkey = match &self.backup_passkey {
+			Some(passkey) => passkey.clone(),
+			None if args.non_interactive => return Err(PromptRequiredError::Passkey.into()),
+			None => tui::prompt_new_passkey("backup")?,
+		};
+		let envelope = archive.seal(passkey.expose_secret())?;
+		let mut buf = serde_json::to_vec(&envelope)?;
+		buf.push(b'\n');
+		write_new_file(&self.path, &buf)?;
+
+		let account_names: Vec<&String> = manager.iter().map(|e| &e.account_name).collect();
+		if args.output == OutputFormat::Json {
+			return print_json(&serde_json::json!({
+				"path": self.path,
+				"accounts": account_names,
+			}));
+		}
+		info!(
+			"Backed up {} accounts to {}",
+			account_names.len(),
+			self.path.display()
+		);
+		Ok(())
+	}
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use log::*;
+
+use crate::encryption::Argon2Params;
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Measure how long deriving encryption keys takes on this machine, and recommend Argon2 parameters for `encrypt` and `rekey`."
+)]
+pub struct BenchmarkCommand {
+	#[clap(
+		long,
+		default_value_t = 500,
+		help = "How long deriving the key for one maFile should take, in milliseconds.",
+		long_help = "How long deriving the key for one maFile should take, in milliseconds. Keys for multiple maFiles are derived in parallel, so unlocking many accounts takes roughly this long per CPU core."
+	)]
+	pub target_ms: u64,
+}
+
+impl ConstCommand for BenchmarkCommand {
+	fn execute(&self) -> anyhow::Result<()> {
+		let target = Duration::from_millis(self.target_ms);
+		info!("Benchmarking Argon2 with a target of {:?}...", target);
+		for (name, params) in [
+			("low", Argon2Params::LOW),
+			("default", Argon2Params::DEFAULT),
+			("high", Argon2Params::HIGH),
+		] {
+			let elapsed = params.time_key_derivation()?;
+			println!("Preset {:<8} ({}): {:?}", name, params, elapsed);
+		}
+
+		let (params, elapsed) = Argon2Params::benchmark(target)?;
+		println!("Recommended ({}): {:?}", params, elapsed);
+		println!(
+			"Use with: --argon2-memory {} --argon2-iterations {} --argon2-parallelism {}",
+			params.m_cost, params.t_cost, params.p_cost
+		);
+		Ok(())
+	}
+}
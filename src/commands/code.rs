@@ -1,12 +1,17 @@
 use std::{
+	io::IsTerminal,
+	ops::RangeInclusive,
 	sync::{Arc, Mutex},
 	time::{SystemTime, UNIX_EPOCH},
 };
 
 use log::*;
-use steamguard::{steamapi, SteamGuardAccount};
+use steamguard::SteamGuardAccount;
 
-use crate::AccountManager;
+use crate::{
+	output::{print_json, CodeOutput, CodeStepOutput, CODE_PERIOD},
+	AccountManager,
+};
 
 use super::*;
 
@@ -15,9 +20,88 @@ use super::*;
 pub struct CodeCommand {
 	#[clap(
 		long,
-		help = "Assume the computer's time is correct. Don't ask Steam for the time when generating codes."
+		conflicts_with = "at",
+		help = "Don't ask Steam for the time when generating codes. The last known difference between the computer's clock and Steam's is used, if there is one."
 	)]
 	pub offline: bool,
+	#[clap(
+		long,
+		value_name = "UNIX_TIME",
+		help = "Generate codes for this time, in seconds since the unix epoch, instead of the current time."
+	)]
+	pub at: Option<u64>,
+	#[clap(
+		long,
+		value_name = "RANGE",
+		allow_hyphen_values = true,
+		value_parser = parse_window,
+		conflicts_with = "verify",
+		help = "Generate the codes for a range of 30 second steps around the current one, eg. `-2..2`.",
+		long_help = "Generate the codes for a range of 30 second steps around the current one, eg. `-2..2` for the 2 codes before and after the current code. Useful for diagnosing clock skew."
+	)]
+	pub window: Option<RangeInclusive<i64>>,
+	#[clap(
+		long,
+		value_name = "CODE",
+		help = "Check which time step, if any, produced this code. Fails if no account generated it within the tolerance."
+	)]
+	pub verify: Option<String>,
+	#[clap(
+		long,
+		default_value_t = 10,
+		requires = "verify",
+		value_parser = clap::value_parser!(u32).range(..=MAX_STEPS),
+		value_name = "STEPS",
+		help = "How many 30 second steps before and after the current one to check with --verify."
+	)]
+	pub tolerance: u32,
+	#[clap(
+		short,
+		long,
+		help = "Show a live view of the codes that updates when they change. Press `c` to copy the selected code.",
+		long_help = "Show a live view of the codes, with a countdown to the next code, that updates when they change. Steam's time is only fetched once, then the codes are generated locally. Press `c` to copy the selected code to the clipboard, using an OSC 52 escape sequence, which most terminals support."
+	)]
+	pub watch: bool,
+}
+
+/// How far `--window` and `--tolerance` can reach, in 30 second steps. One day in either direction.
+const MAX_STEPS: i64 = 2880;
+
+fn parse_window(s: &str) -> Result<RangeInclusive<i64>, String> {
+	let (start, end) = s
+		.split_once("..")
+		.ok_or_else(|| format!("expected a range like -2..2, got {}", s))?;
+	let end = end.strip_prefix('=').unwrap_or(end);
+	let start: i64 = start.trim().parse().map_err(|e| format!("{}", e))?;
+	let end: i64 = end.trim().parse().map_err(|e| format!("{}", e))?;
+	if start > end {
+		return Err(format!("range start {} is after its end {}", start, end));
+	}
+	if start < -MAX_STEPS || end > MAX_STEPS {
+		return Err(format!(
+			"range must be within -{}..{} (one day)",
+			MAX_STEPS, MAX_STEPS
+		));
+	}
+	Ok(start..=end)
+}
+
+/// The time `step` 30 second steps away from `time`.
+fn step_time(time: u64, step: i64) -> u64 {
+	time.saturating_add_signed(step.saturating_mul(CODE_PERIOD as i64))
+}
+
+/// Find the step closest to `time` that produces `code`, checking up to `tolerance` steps in either direction.
+fn find_code_step(
+	account: &SteamGuardAccount,
+	code: &str,
+	time: u64,
+	tolerance: u32,
+) -> Option<i64> {
+	let tolerance = tolerance as i64;
+	std::iter::once(0)
+		.chain((1..=tolerance).flat_map(|i| [-i, i]))
+		.find(|step| account.generate_code(step_time(time, *step)) == code)
 }
 
 impl<T> AccountCommand<T> for CodeCommand
@@ -27,24 +111,173 @@ where
 	fn execute(
 		&self,
 		transport: T,
-		_manager: &mut AccountManager,
+		manager: &mut AccountManager,
 		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
-		_args: &GlobalArgs,
+		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
-		let server_time = if self.offline {
-			SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()
+		if self.watch {
+			ensure!(
+				self.at.is_none() && self.window.is_none() && self.verify.is_none(),
+				"--watch can't be used with --at, --window or --verify."
+			);
+			ensure!(
+				args.output == OutputFormat::Text,
+				"--watch can't be used with JSON output."
+			);
+			ensure!(
+				std::io::stdout().is_terminal(),
+				"--watch needs to be run in a terminal."
+			);
+		}
+
+		let local_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+		let server_time = if let Some(at) = self.at {
+			at
+		} else if self.offline {
+			manager.time_sync().offline_time()
 		} else {
-			steamapi::get_server_time(transport)?.server_time()
+			manager.time_sync().server_time(transport)?
 		};
 		debug!("Time used to generate codes: {}", server_time);
 
+		if self.watch {
+			let offset = server_time as i64 - local_time as i64;
+			debug!("Local clock offset: {}s", offset);
+			return crate::tui::watch_codes(&accounts, offset);
+		}
+		if let Some(window) = &self.window {
+			return print_window(&accounts, server_time, window.clone(), args);
+		}
+		if let Some(code) = &self.verify {
+			return verify(&accounts, server_time, code, self.tolerance, args);
+		}
+
+		let mut codes = vec![];
 		for account in accounts {
 			let account = account.lock().unwrap();
 			info!("Generating code for {}", account.account_name);
 			trace!("{:?}", account);
-			let code = account.generate_code(server_time);
-			println!("{}", code);
+			let code = CodeOutput::new(&account, server_time);
+			if args.output == OutputFormat::Text {
+				println!("{}", code.code);
+			}
+			codes.push(code);
+		}
+		if args.output == OutputFormat::Json {
+			print_json(&serde_json::json!({ "codes": codes }))?;
 		}
 		Ok(())
 	}
 }
+
+fn print_window(
+	accounts: &[Arc<Mutex<SteamGuardAccount>>],
+	time: u64,
+	window: RangeInclusive<i64>,
+	args: &GlobalArgs,
+) -> anyhow::Result<()> {
+	let mut codes = vec![];
+	for account in accounts {
+		let account = account.lock().unwrap();
+		if args.output == OutputFormat::Text && accounts.len() > 1 {
+			println!("{}", account.account_name);
+		}
+		for step in window.clone() {
+			let time = step_time(time, step);
+			let code = CodeStepOutput {
+				account_name: account.account_name.clone(),
+				code: account.generate_code(time),
+				step,
+				time,
+			};
+			if args.output == OutputFormat::Text {
+				println!("{:>+4} {} {}", step, code.time, code.code);
+			}
+			codes.push(code);
+		}
+	}
+	if args.output == OutputFormat::Json {
+		print_json(&serde_json::json!({ "codes": codes }))?;
+	}
+	Ok(())
+}
+
+fn verify(
+	accounts: &[Arc<Mutex<SteamGuardAccount>>],
+	time: u64,
+	code: &str,
+	tolerance: u32,
+	args: &GlobalArgs,
+) -> anyhow::Result<()> {
+	let code = code.trim().to_uppercase();
+	let mut matches = vec![];
+	for account in accounts {
+		let account = account.lock().unwrap();
+		let Some(step) = find_code_step(&account, &code, time, tolerance) else {
+			debug!("{} did not generate {}", account.account_name, code);
+			continue;
+		};
+		let step_time = step_time(time, step);
+		if args.output == OutputFormat::Text {
+			match step {
+				0 => println!("{}: {} is the current code", account.account_name, code),
+				_ => println!(
+					"{}: {} matches step {:+}, so the clock that generated it is about {} seconds {} Steam's",
+					account.account_name,
+					code,
+					step,
+					step.unsigned_abs() * CODE_PERIOD,
+					if step < 0 { "behind" } else { "ahead of" },
+				),
+			}
+		}
+		matches.push(CodeStepOutput {
+			account_name: account.account_name.clone(),
+			code: code.clone(),
+			step,
+			time: step_time,
+		});
+	}
+	if args.output == OutputFormat::Json {
+		print_json(&serde_json::json!({ "matches": matches }))?;
+	}
+	ensure!(
+		!matches.is_empty(),
+		"{} was not generated within {} steps of the current time.",
+		code,
+		tolerance
+	);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use steamguard::token::TwoFactorSecret;
+
+	#[test]
+	fn test_parse_window() {
+		assert_eq!(parse_window("-2..2"), Ok(-2..=2));
+		assert_eq!(parse_window("0..=3"), Ok(0..=3));
+		assert_eq!(parse_window("-5..-1"), Ok(-5..=-1));
+		assert!(parse_window("2..-2").is_err());
+		assert!(parse_window("2").is_err());
+		assert!(parse_window("a..b").is_err());
+		assert!(parse_window("-2880..2880").is_ok());
+		assert!(parse_window("-2881..0").is_err());
+		assert!(parse_window("0..9223372036854775807").is_err());
+	}
+
+	#[test]
+	fn test_find_code_step() -> anyhow::Result<()> {
+		let mut account = SteamGuardAccount::new();
+		account.shared_secret =
+			TwoFactorSecret::parse_shared_secret("zvIayp3JPvtvX/QGHqsqKBk/44s=".into())?;
+		let time = 1616374841;
+		assert_eq!(find_code_step(&account, "2F9J5", time, 0), Some(0));
+		assert_eq!(find_code_step(&account, "2F9J5", time + 60, 2), Some(-2));
+		assert_eq!(find_code_step(&account, "2F9J5", time - 30, 2), Some(1));
+		assert_eq!(find_code_step(&account, "2F9J5", time + 90, 2), None);
+		Ok(())
+	}
+}
@@ -1,10 +1,19 @@
-use std::sync::{Arc, Mutex};
+use std::{
+	path::PathBuf,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
 
 use crossterm::tty::IsTty;
 use log::*;
-use steamguard::{Confirmation, Confirmer, ConfirmerError};
+use serde::Serialize;
+use steamguard::{Confirmation, ConfirmationType, Confirmer, ConfirmerError, TimeSync};
 
-use crate::{tui, AccountManager};
+use crate::{
+	output::{print_json, ConfirmationOutput},
+	rules::{parse_duration, ConfirmationRule, ConfirmationRules, RuleAction},
+	tui, AccountManager,
+};
 
 use super::*;
 
@@ -17,6 +26,72 @@ pub struct ConfirmCommand {
 		help = "Accept all open mobile confirmations. Does not open interactive interface."
 	)]
 	pub accept_all: bool,
+	#[clap(
+		long,
+		conflicts_with = "accept_all",
+		help = "Path to a JSON rules file. Confirmations are accepted or denied according to the first matching rule, and ignored if no rule matches. Does not open interactive interface."
+	)]
+	pub rules: Option<PathBuf>,
+	#[clap(
+		long,
+		requires = "rules",
+		help = "Keep running, and apply the rules to new confirmations for all selected accounts periodically."
+	)]
+	pub daemon: bool,
+	#[clap(
+		long,
+		default_value_t = 60,
+		help = "How often to check for new confirmations in daemon mode, in seconds."
+	)]
+	pub interval: u64,
+	#[clap(
+		long,
+		value_name = "TYPE",
+		conflicts_with_all = ["accept_all", "rules"],
+		help = "Accept confirmations of this type, eg. `market-sell` or `trade`. Can be specified multiple times. Does not open interactive interface."
+	)]
+	pub accept_type: Vec<ConfirmationType>,
+	#[clap(
+		long,
+		value_name = "TYPE",
+		conflicts_with_all = ["accept_all", "rules"],
+		help = "Deny confirmations of this type, eg. `api-key-creation`. Can be specified multiple times. Does not open interactive interface."
+	)]
+	pub deny_type: Vec<ConfirmationType>,
+	#[clap(
+		long,
+		conflicts_with_all = ["accept_all", "rules", "deny_matching"],
+		help = "Accept every confirmation that matches --id, --creator-id and --older-than, and isn't selected by --deny-type."
+	)]
+	pub accept_matching: bool,
+	#[clap(
+		long,
+		conflicts_with_all = ["accept_all", "rules"],
+		help = "Deny every confirmation that matches --id, --creator-id and --older-than, and isn't selected by --accept-type."
+	)]
+	pub deny_matching: bool,
+	#[clap(
+		long = "id",
+		value_name = "ID",
+		conflicts_with_all = ["accept_all", "rules"],
+		help = "Only respond to the confirmation with this ID. Can be specified multiple times."
+	)]
+	pub ids: Vec<String>,
+	#[clap(
+		long = "creator-id",
+		value_name = "ID",
+		conflicts_with_all = ["accept_all", "rules"],
+		help = "Only respond to confirmations for this trade offer ID or market transaction ID. Can be specified multiple times."
+	)]
+	pub creator_ids: Vec<String>,
+	#[clap(
+		long,
+		value_name = "DURATION",
+		value_parser = parse_duration,
+		conflicts_with_all = ["accept_all", "rules"],
+		help = "Only respond to confirmations that are at least this old, eg. `30s`, `10m` or `2h`."
+	)]
+	pub older_than: Option<Duration>,
 	#[clap(
 		short,
 		long,
@@ -27,7 +102,7 @@ pub struct ConfirmCommand {
 
 impl<T> AccountCommand<T> for ConfirmCommand
 where
-	T: Transport + Clone,
+	T: Transport + Clone + Sync,
 {
 	fn execute(
 		&self,
@@ -36,18 +111,36 @@ where
 		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
 		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
+		let rules = self
+			.rules
+			.as_ref()
+			.map(|path| ConfirmationRules::load(path))
+			.transpose()?;
+		let selectors = self.selector_rules()?;
+		if self.daemon {
+			ensure!(
+				args.output == OutputFormat::Text,
+				"JSON output is not supported in daemon mode. Every decision is logged to stderr instead."
+			);
+			let rules = rules.expect("clap should require rules in daemon mode");
+			return self.run_daemon(transport, manager, accounts, &rules, args);
+		}
+
+		let mut results = vec![];
 		for a in accounts {
 			let mut account = a.lock().unwrap();
 
 			if !account.is_logged_in() {
 				info!("Account does not have tokens, logging in");
-				crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+				let login = args.login_options(manager, &account.account_name);
+				crate::do_login(transport.clone(), &mut account, &login, manager.time_sync())?;
 			}
 
 			info!("{}: Checking for confirmations", account.account_name);
 			let confirmations: Vec<Confirmation>;
 			loop {
-				let confirmer = Confirmer::new(transport.clone(), &account);
+				let confirmer = Confirmer::new(transport.clone(), &account)
+					.with_time_sync(manager.time_sync().clone());
 
 				match confirmer.get_confirmations() {
 					Ok(confs) => {
@@ -56,7 +149,13 @@ where
 					}
 					Err(ConfirmerError::InvalidTokens) => {
 						info!("obtaining new tokens");
-						crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+						let login = args.login_options(manager, &account.account_name);
+						crate::do_login(
+							transport.clone(),
+							&mut account,
+							&login,
+							manager.time_sync(),
+						)?;
 					}
 					Err(err) => {
 						error!("Failed to get confirmations: {}", err);
@@ -65,107 +164,545 @@ where
 				}
 			}
 
+			let mut output = ConfirmAccountOutput {
+				account_name: account.account_name.clone(),
+				confirmations: confirmations.iter().map(ConfirmationOutput::from).collect(),
+				accepted: vec![],
+				denied: vec![],
+				failed: vec![],
+				error: None,
+			};
 			if confirmations.is_empty() {
 				info!("{}: No confirmations", account.account_name);
+				results.push(output);
 				continue;
 			}
 
-			let confirmer = Confirmer::new(transport.clone(), &account);
-			let mut any_failed = false;
-
-			fn submit_loop(
-				submit: impl Fn() -> Result<(), ConfirmerError>,
-				fail_fast: bool,
-			) -> Result<(), ConfirmerError> {
-				let mut attempts = 0;
-				loop {
-					match submit() {
-						Ok(_) => break,
-						Err(ConfirmerError::InvalidTokens) => {
-							error!("Invalid tokens, but they should be valid already. This is weird, stopping.");
-							return Err(ConfirmerError::InvalidTokens);
-						}
-						Err(ConfirmerError::NetworkFailure(err)) => {
-							error!("{}", err);
-							return Err(ConfirmerError::NetworkFailure(err));
-						}
-						Err(ConfirmerError::DeserializeError(err)) => {
-							error!("Failed to deserialize the response, but the submission may have succeeded: {}", err);
-							return Err(ConfirmerError::DeserializeError(err));
-						}
-						Err(err) => {
-							warn!("submit confirmation result: {}", err);
-							if fail_fast || attempts >= 3 {
-								return Err(err);
-							}
-
-							attempts += 1;
-							let wait = std::time::Duration::from_secs(3 * attempts);
-							info!(
-								"retrying in {} seconds (attempt {})",
-								wait.as_secs(),
-								attempts
-							);
-							std::thread::sleep(wait);
-						}
+			let (accept, deny) = if let Some(rules) = rules.as_ref().or(selectors.as_ref()) {
+				decide_with_rules(
+					&account.account_name,
+					&confirmations,
+					rules,
+					manager.time_sync().offline_time(),
+				)
+			} else if self.accept_all {
+				info!("accepting all confirmations");
+				(confirmations.clone(), vec![])
+			} else if args.output == OutputFormat::Text
+				&& std::io::stdout().is_tty()
+				&& !args.non_interactive
+			{
+				let confirmer = Confirmer::new(transport.clone(), &account)
+					.with_time_sync(manager.time_sync().clone());
+				tui::prompt_confirmation_menu(confirmations.clone(), |conf| {
+					confirmer.get_confirmation_details(conf)
+				})?
+			} else {
+				if args.output == OutputFormat::Text {
+					warn!("not a tty, not showing menu");
+					for conf in &confirmations {
+						println!("{}", conf.description());
 					}
 				}
-				Ok(())
-			}
+				(vec![], vec![])
+			};
 
-			if self.accept_all {
-				info!("accepting all confirmations");
-				match submit_loop(
-					|| confirmer.accept_confirmations_bulk(&confirmations),
-					self.fail_fast,
-				) {
-					Ok(_) => {}
-					Err(err) => {
-						warn!("accept confirmation result: {}", err);
-						if self.fail_fast {
-							return Err(err.into());
-						}
-						any_failed = true;
+			let confirmer = Confirmer::new(transport.clone(), &account)
+				.with_time_sync(manager.time_sync().clone());
+			match submit_loop(
+				|| confirmer.accept_confirmations_bulk(&accept),
+				self.fail_fast,
+			) {
+				Ok(_) => output.accepted = accept.iter().map(|c| c.id.clone()).collect(),
+				Err(err) => {
+					warn!("accept confirmation result: {}", err);
+					if self.fail_fast {
+						return Err(err.into());
 					}
+					output.failed.extend(accept.iter().map(|c| c.id.clone()));
+					output.add_error(format!("failed to accept: {}", err));
 				}
-			} else if std::io::stdout().is_tty() {
-				let (accept, deny) = tui::prompt_confirmation_menu(confirmations)?;
-				match submit_loop(
-					|| confirmer.accept_confirmations_bulk(&accept),
-					self.fail_fast,
-				) {
-					Ok(_) => {}
-					Err(err) => {
-						warn!("accept confirmation result: {}", err);
-						if self.fail_fast {
-							return Err(err.into());
-						}
-						any_failed = true;
+			}
+			match submit_loop(|| confirmer.deny_confirmations_bulk(&deny), self.fail_fast) {
+				Ok(_) => output.denied = deny.iter().map(|c| c.id.clone()).collect(),
+				Err(err) => {
+					warn!("deny confirmation result: {}", err);
+					if self.fail_fast {
+						return Err(err.into());
 					}
+					output.failed.extend(deny.iter().map(|c| c.id.clone()));
+					output.add_error(format!("failed to deny: {}", err));
 				}
-				match submit_loop(|| confirmer.deny_confirmations_bulk(&deny), self.fail_fast) {
-					Ok(_) => {}
-					Err(err) => {
-						warn!("deny confirmation result: {}", err);
-						if self.fail_fast {
-							return Err(err.into());
+			}
+
+			if selectors.is_some() && args.output == OutputFormat::Text {
+				for (confs, action) in [(&accept, "accepted"), (&deny, "denied")] {
+					for conf in confs {
+						if output.failed.contains(&conf.id) {
+							println!("{}: failed to respond to {}", conf.id, conf.description());
+						} else {
+							println!("{}: {} {}", conf.id, action, conf.description());
 						}
-						any_failed = true;
 					}
 				}
-			} else {
-				warn!("not a tty, not showing menu");
-				for conf in &confirmations {
-					println!("{}", conf.description());
-				}
 			}
 
-			if any_failed {
+			if output.error.is_some() {
 				error!("Failed to respond to some confirmations.");
 			}
+			results.push(output);
 		}
 
 		manager.save()?;
+		if args.output == OutputFormat::Json {
+			print_json(&serde_json::json!({ "accounts": results }))?;
+		}
 		Ok(())
 	}
 }
+
+#[derive(Debug, Serialize)]
+struct ConfirmAccountOutput {
+	account_name: String,
+	confirmations: Vec<ConfirmationOutput>,
+	/// IDs of confirmations that were successfully accepted.
+	accepted: Vec<String>,
+	/// IDs of confirmations that were successfully denied.
+	denied: Vec<String>,
+	/// IDs of confirmations that were selected, but responding to them failed.
+	failed: Vec<String>,
+	error: Option<String>,
+}
+
+impl ConfirmAccountOutput {
+	/// Record an error, keeping any earlier ones.
+	fn add_error(&mut self, err: String) {
+		self.error = Some(match self.error.take() {
+			Some(prev) => format!("{}; {}", prev, err),
+			None => err,
+		});
+	}
+}
+
+impl ConfirmCommand {
+	/// Turn the selection flags, like `--accept-type` and `--id`, into rules. Returns `None` if none of them were specified.
+	fn selector_rules(&self) -> anyhow::Result<Option<ConfirmationRules>> {
+		let has_action = !self.accept_type.is_empty()
+			|| !self.deny_type.is_empty()
+			|| self.accept_matching
+			|| self.deny_matching;
+		let has_filter =
+			!self.ids.is_empty() || !self.creator_ids.is_empty() || self.older_than.is_some();
+		if !has_action {
+			ensure!(
+				!has_filter,
+				"--id, --creator-id and --older-than need --accept-type, --deny-type, --accept-matching or --deny-matching to know what to do with the selected confirmations"
+			);
+			return Ok(None);
+		}
+
+		let rule = |action, types: &[ConfirmationType]| ConfirmationRule {
+			action,
+			types: types.to_vec(),
+			creator_ids: self.creator_ids.clone(),
+			ids: self.ids.clone(),
+			headline: None,
+			summary: None,
+			older_than: self.older_than,
+			newer_than: None,
+		};
+		let mut rules = vec![];
+		if !self.accept_type.is_empty() {
+			rules.push(rule(RuleAction::Accept, &self.accept_type));
+		}
+		if !self.deny_type.is_empty() {
+			rules.push(rule(RuleAction::Deny, &self.deny_type));
+		}
+		if self.accept_matching {
+			rules.push(rule(RuleAction::Accept, &[]));
+		}
+		if self.deny_matching {
+			rules.push(rule(RuleAction::Deny, &[]));
+		}
+		Ok(Some(ConfirmationRules { rules }))
+	}
+}
+
+/// The maximum amount of time to wait between polls when Steam keeps failing.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 30);
+
+#[derive(Debug, Clone, Default)]
+pub(super) struct DaemonAccountState {
+	pub consecutive_failures: u32,
+	pub next_poll: Option<Instant>,
+}
+
+impl ConfirmCommand {
+	fn run_daemon<T>(
+		&self,
+		transport: T,
+		manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		rules: &ConfirmationRules,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()>
+	where
+		T: Transport + Clone,
+	{
+		for a in &accounts {
+			let mut account = a.lock().unwrap();
+			if !account.is_logged_in() {
+				info!(
+					"{}: Account does not have tokens, logging in",
+					account.account_name
+				);
+				let login = args.login_options(manager, &account.account_name);
+				crate::do_login(transport.clone(), &mut account, &login, manager.time_sync())?;
+			}
+		}
+		manager.save()?;
+
+		let interval = Duration::from_secs(self.interval.max(1));
+		info!(
+			"Checking for confirmations every {} seconds for {} accounts",
+			interval.as_secs(),
+			accounts.len()
+		);
+		let mut states = vec![DaemonAccountState::default(); accounts.len()];
+		loop {
+			for (a, state) in accounts.iter().zip(states.iter_mut()) {
+				if state.next_poll.is_some_and(|t| Instant::now() < t) {
+					continue;
+				}
+
+				let mut account = a.lock().unwrap();
+				let account_name = account.account_name.clone();
+				let mut refreshed_tokens = false;
+				let result = daemon_poll(
+					transport.clone(),
+					&mut account,
+					manager.time_sync(),
+					rules,
+					self.fail_fast,
+					&mut refreshed_tokens,
+				);
+				drop(account);
+
+				if refreshed_tokens {
+					manager.save()?;
+				}
+
+				match result {
+					Ok(_) => {
+						state.consecutive_failures = 0;
+						state.next_poll = Some(Instant::now() + interval);
+					}
+					Err(err) => {
+						state.consecutive_failures += 1;
+						let wait = backoff(interval, state.consecutive_failures);
+						match err {
+							ConfirmerError::RemoteFailure
+							| ConfirmerError::RemoteFailureWithMessage(_) => warn!(
+								"{}: {} ({} failures in a row), waiting {} seconds before trying again",
+								account_name,
+								err,
+								state.consecutive_failures,
+								wait.as_secs()
+							),
+							_ => error!(
+								"{}: Failed to process confirmations: {} ({} failures in a row), waiting {} seconds before trying again",
+								account_name,
+								err,
+								state.consecutive_failures,
+								wait.as_secs()
+							),
+						}
+						state.next_poll = Some(Instant::now() + wait);
+					}
+				}
+			}
+
+			let now = Instant::now();
+			let wait = states
+				.iter()
+				.filter_map(|s| s.next_poll)
+				.min()
+				.map(|t| t.saturating_duration_since(now))
+				.unwrap_or(interval)
+				.max(Duration::from_secs(1));
+			debug!("sleeping for {} seconds", wait.as_secs());
+			std::thread::sleep(wait);
+		}
+	}
+}
+
+/// Exponential backoff, starting at `interval` and capped at [`MAX_BACKOFF`].
+pub(super) fn backoff(interval: Duration, consecutive_failures: u32) -> Duration {
+	let factor = 2u32.saturating_pow(consecutive_failures.min(16));
+	interval
+		.saturating_mul(factor)
+		.min(MAX_BACKOFF.max(interval))
+}
+
+/// Check for confirmations once, and respond to them according to the rules. Tokens are refreshed without prompting if needed.
+fn daemon_poll<T>(
+	transport: T,
+	account: &mut SteamGuardAccount,
+	time_sync: &TimeSync,
+	rules: &ConfirmationRules,
+	fail_fast: bool,
+	refreshed_tokens: &mut bool,
+) -> Result<(), ConfirmerError>
+where
+	T: Transport + Clone,
+{
+	debug!("{}: Checking for confirmations", account.account_name);
+	let confirmations = loop {
+		let confirmer =
+			Confirmer::new(transport.clone(), account).with_time_sync(time_sync.clone());
+		match confirmer.get_confirmations() {
+			Ok(confs) => break confs,
+			Err(ConfirmerError::InvalidTokens) if !*refreshed_tokens => {
+				info!("{}: Refreshing access token", account.account_name);
+				crate::refresh_tokens(transport.clone(), account)?;
+				*refreshed_tokens = true;
+			}
+			Err(err) => return Err(err),
+		}
+	};
+
+	if confirmations.is_empty() {
+		debug!("{}: No confirmations", account.account_name);
+		return Ok(());
+	}
+
+	let (accept, deny) = decide_with_rules(
+		&account.account_name,
+		&confirmations,
+		rules,
+		time_sync.offline_time(),
+	);
+	let confirmer = Confirmer::new(transport, account).with_time_sync(time_sync.clone());
+	submit_loop(|| confirmer.accept_confirmations_bulk(&accept), fail_fast)?;
+	submit_loop(|| confirmer.deny_confirmations_bulk(&deny), fail_fast)?;
+	Ok(())
+}
+
+/// Decide what to do with each confirmation based on the rules, and log every decision. Returns the confirmations to accept and deny.
+///
+/// `now` is Steam's current time, so the ages of the confirmations aren't off by the local clock skew.
+fn decide_with_rules(
+	account_name: &str,
+	confirmations: &[Confirmation],
+	rules: &ConfirmationRules,
+	now: u64,
+) -> (Vec<Confirmation>, Vec<Confirmation>) {
+	let mut accept = vec![];
+	let mut deny = vec![];
+	for conf in confirmations {
+		match rules.decide(conf, now) {
+			Some((i, RuleAction::Accept)) => {
+				info!(
+					"{}: Accepting confirmation {} ({}), matched rule #{}",
+					account_name,
+					conf.id,
+					conf.description(),
+					i + 1
+				);
+				accept.push(conf.clone());
+			}
+			Some((i, RuleAction::Deny)) => {
+				info!(
+					"{}: Denying confirmation {} ({}), matched rule #{}",
+					account_name,
+					conf.id,
+					conf.description(),
+					i + 1
+				);
+				deny.push(conf.clone());
+			}
+			None => {
+				info!(
+					"{}: Ignoring confirmation {} ({}), no rule matched",
+					account_name,
+					conf.id,
+					conf.description()
+				);
+			}
+		}
+	}
+
+	(accept, deny)
+}
+
+fn submit_loop(
+	submit: impl Fn() -> Result<(), ConfirmerError>,
+	fail_fast: bool,
+) -> Result<(), ConfirmerError> {
+	let mut attempts = 0;
+	loop {
+		match submit() {
+			Ok(_) => break,
+			Err(ConfirmerError::InvalidTokens) => {
+				error!(
+					"Invalid tokens, but they should be valid already. This is weird, stopping."
+				);
+				return Err(ConfirmerError::InvalidTokens);
+			}
+			Err(ConfirmerError::NetworkFailure(err)) => {
+				error!("{}", err);
+				return Err(ConfirmerError::NetworkFailure(err));
+			}
+			Err(ConfirmerError::DeserializeError(err)) => {
+				error!(
+					"Failed to deserialize the response, but the submission may have succeeded: {}",
+					err
+				);
+				return Err(ConfirmerError::DeserializeError(err));
+			}
+			Err(err) => {
+				warn!("submit confirmation result: {}", err);
+				if fail_fast || attempts >= 3 {
+					return Err(err);
+				}
+
+				attempts += 1;
+				let wait = std::time::Duration::from_secs(3 * attempts);
+				info!(
+					"retrying in {} seconds (attempt {})",
+					wait.as_secs(),
+					attempts
+				);
+				std::thread::sleep(wait);
+			}
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn confirmation(id: &str, conf_type: ConfirmationType, creator_id: &str) -> Confirmation {
+		Confirmation {
+			id: id.to_owned(),
+			nonce: "12345".to_owned(),
+			conf_type,
+			creator_id: creator_id.to_owned(),
+			headline: "example confirmation".into(),
+			type_name: "Example".to_owned(),
+			creation_time: 1000,
+			cancel: "Cancel".to_owned(),
+			accept: "Confirm".to_owned(),
+			icon: None,
+			multi: false,
+			summary: vec![],
+		}
+	}
+
+	fn decide(args: &[&str], confs: &[Confirmation], now: u64) -> Vec<Option<RuleAction>> {
+		let cmd =
+			ConfirmCommand::try_parse_from(std::iter::once("confirm").chain(args.iter().copied()))
+				.unwrap();
+		let rules = cmd.selector_rules().unwrap().unwrap();
+		confs
+			.iter()
+			.map(|conf| rules.decide(conf, now).map(|(_, action)| action))
+			.collect()
+	}
+
+	#[test]
+	fn test_selector_rules() {
+		let confs = [
+			confirmation("1", ConfirmationType::MarketSell, "100"),
+			confirmation("2", ConfirmationType::ApiKeyCreation, "200"),
+			confirmation("3", ConfirmationType::Trade, "300"),
+		];
+		assert_eq!(
+			decide(
+				&[
+					"--accept-type",
+					"market-sell",
+					"--deny-type",
+					"api-key-creation"
+				],
+				&confs,
+				1000
+			),
+			vec![Some(RuleAction::Accept), Some(RuleAction::Deny), None]
+		);
+		assert_eq!(
+			decide(
+				&["--accept-type", "3", "--older-than", "10m"],
+				&confs,
+				1000 + 599
+			),
+			vec![None, None, None]
+		);
+		assert_eq!(
+			decide(
+				&["--accept-type", "3", "--older-than", "10m"],
+				&confs,
+				1000 + 600
+			),
+			vec![Some(RuleAction::Accept), None, None]
+		);
+		assert_eq!(
+			decide(
+				&["--deny-matching", "--id", "2", "--creator-id", "300"],
+				&confs,
+				1000
+			),
+			vec![None, None, None]
+		);
+		assert_eq!(
+			decide(
+				&[
+					"--accept-matching",
+					"--creator-id",
+					"300",
+					"--creator-id",
+					"100"
+				],
+				&confs,
+				1000
+			),
+			vec![Some(RuleAction::Accept), None, Some(RuleAction::Accept)]
+		);
+	}
+
+	#[test]
+	fn test_selector_rules_need_action() {
+		let cmd = ConfirmCommand::try_parse_from(["confirm", "--id", "1"]).unwrap();
+		assert!(cmd.selector_rules().is_err());
+		let cmd = ConfirmCommand::try_parse_from(["confirm"]).unwrap();
+		assert!(cmd.selector_rules().unwrap().is_none());
+		assert!(ConfirmCommand::try_parse_from(["confirm", "--accept-type", "bogus"]).is_err());
+		assert!(ConfirmCommand::try_parse_from([
+			"confirm",
+			"--accept-all",
+			"--deny-type",
+			"trade"
+		])
+		.is_err());
+	}
+
+	#[test]
+	fn test_output_keeps_every_error() {
+		let mut output = ConfirmAccountOutput {
+			account_name: "example".to_owned(),
+			confirmations: vec![],
+			accepted: vec![],
+			denied: vec![],
+			failed: vec![],
+			error: None,
+		};
+		output.add_error("failed to accept: timeout".to_owned());
+		output.add_error("failed to deny: timeout".to_owned());
+		assert_eq!(
+			output.error.as_deref(),
+			Some("failed to accept: timeout; failed to deny: timeout")
+		);
+	}
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+
+use log::*;
+use serde::Serialize;
+use steamguard::Credentials;
+
+use crate::{
+	encryption::PasskeyProvider, errors::PromptRequiredError, output::print_json, tui,
+	AccountManager,
+};
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Configure where to get the password from when an account has to log in again, instead of prompting for it.",
+	long_about = "Configure where to get the password from when an account has to log in again, instead of prompting for it. This is needed to log in with --non-interactive. A password given with --password is always used first. Without any options, the current source of each selected account is shown."
+)]
+#[clap(group = clap::ArgGroup::new("source").multiple(false))]
+pub struct CredentialsCommand {
+	#[clap(
+		long,
+		group = "source",
+		help = "Store the password in the maFile. Uses --password, or prompts for it.",
+		long_help = "Store the password in the maFile. Uses --password, or prompts for it. The password is encrypted along with the rest of the maFile, so set up encryption with `steamguard encrypt` first. Unencrypted maFiles are refused, and `steamguard decrypt` will only remove stored passwords, never write them in plain text."
+	)]
+	pub store_password: bool,
+	#[clap(
+		long,
+		group = "source",
+		num_args = 1..,
+		allow_hyphen_values = true,
+		value_name = "COMMAND",
+		help = "Run this command and read the password from its stdout. Must be the last option, eg. `--command pass show steam/bot_1`."
+	)]
+	pub command: Option<Vec<String>>,
+	#[clap(
+		long,
+		group = "source",
+		help = "Remove the stored password and the password command."
+	)]
+	pub clear: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CredentialsOutput {
+	account_name: String,
+	password_stored: bool,
+	password_command: Option<Vec<String>>,
+}
+
+impl<T> AccountCommand<T> for CredentialsCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		if self.store_password || self.command.is_some() {
+			ensure!(
+				accounts.len() == 1,
+				"You can only set the credentials of one account at a time."
+			);
+		}
+
+		let mut results = vec![];
+		for account in accounts {
+			let mut account = account.lock().unwrap();
+			let entry = manager.get_entry_mut(&account.account_name)?;
+			if self.store_password {
+				ensure!(
+					entry.encryption.is_some(),
+					"The maFile of {} is not encrypted, refusing to store the password in plain text. Use `steamguard encrypt` to encrypt your maFiles first, or use --command instead.",
+					account.account_name
+				);
+				let password = match &args.password {
+					Some(password) => password.clone(),
+					None if args.non_interactive => {
+						return Err(
+							PromptRequiredError::Password(account.account_name.clone()).into()
+						);
+					}
+					None => tui::prompt_password()?,
+				};
+				account.credentials = Some(Credentials { password });
+				entry.password_command = None;
+				info!(
+					"{}: Stored the password in the maFile",
+					account.account_name
+				);
+			} else if let Some(command) = &self.command {
+				// make sure the command actually works before relying on it
+				let provider = PasskeyProvider::Command {
+					command: command.clone(),
+				};
+				provider.fetch()?;
+				account.credentials = None;
+				entry.password_command = Some(command.clone());
+				info!(
+					"{}: Password will be read from {}",
+					account.account_name, provider
+				);
+			} else if self.clear {
+				account.credentials = None;
+				entry.password_command = None;
+				info!("{}: Removed the credentials", account.account_name);
+			}
+
+			let output = CredentialsOutput {
+				account_name: account.account_name.clone(),
+				password_stored: account.credentials.is_some(),
+				password_command: entry.password_command.clone(),
+			};
+			if args.output == OutputFormat::Json {
+				results.push(output);
+				continue;
+			}
+			if self.store_password || self.command.is_some() || self.clear {
+				continue;
+			}
+			match (output.password_stored, &output.password_command) {
+				(true, _) => println!("{}: password stored in the maFile", output.account_name),
+				(false, Some(command)) => println!(
+					"{}: password read from command `{}`",
+					output.account_name,
+					command.join(" ")
+				),
+				(false, None) => println!(
+					"{}: no credentials, the password will be prompted for",
+					output.account_name
+				),
+			}
+		}
+
+		if self.store_password || self.command.is_some() || self.clear {
+			manager.save()?;
+		}
+		if args.output == OutputFormat::Json {
+			print_json(&serde_json::json!({ "accounts": results }))?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use steamguard::transport::MockTransport;
+
+	#[test]
+	fn test_refuse_to_store_password_unencrypted() {
+		let mut manager = AccountManager::default();
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		manager.add_account(account);
+		let account = manager.get_account("example").unwrap();
+
+		let args = GlobalArgs::try_parse_from(["steamguard", "--password", "hunter2"]).unwrap();
+		let command =
+			CredentialsCommand::try_parse_from(["credentials", "--store-password"]).unwrap();
+		let result = command.execute(
+			MockTransport::new(),
+			&mut manager,
+			vec![account.clone()],
+			&args,
+		);
+		assert!(result.is_err());
+		assert!(account.lock().unwrap().credentials.is_none());
+	}
+}
@@ -1,5 +1,5 @@
 use log::*;
-use steamguard::{Confirmation, ConfirmationType};
+use steamguard::{Confirmation, ConfirmationDetails, ConfirmationType};
 
 use crate::{debug::parse_json_stripped, tui};
 
@@ -72,36 +72,46 @@ pub fn demo_prompt_char() {
 
 pub fn demo_confirmation_menu() {
 	info!("showing demo menu");
-	let (accept, deny) = tui::prompt_confirmation_menu(vec![
-		Confirmation {
-			id: "1234".to_owned(),
-			nonce: "12345".to_owned(),
-			conf_type: ConfirmationType::Trade,
-			creator_id: "09870987".to_owned(),
-			headline: "example confirmation".into(),
-			type_name: "Trade".to_owned(),
-			creation_time: 1687457923,
-			cancel: "Cancel".to_owned(),
-			accept: "Confirm".to_owned(),
-			icon: Some("".to_owned()),
-			multi: false,
-			summary: vec![],
+	let (accept, deny) = tui::prompt_confirmation_menu(
+		vec![
+			Confirmation {
+				id: "1234".to_owned(),
+				nonce: "12345".to_owned(),
+				conf_type: ConfirmationType::Trade,
+				creator_id: "09870987".to_owned(),
+				headline: "example confirmation".into(),
+				type_name: "Trade".to_owned(),
+				creation_time: 1687457923,
+				cancel: "Cancel".to_owned(),
+				accept: "Confirm".to_owned(),
+				icon: Some("".to_owned()),
+				multi: false,
+				summary: vec![],
+			},
+			Confirmation {
+				id: "1234".to_owned(),
+				nonce: "12345".to_owned(),
+				conf_type: ConfirmationType::MarketSell,
+				creator_id: "09870987".to_owned(),
+				headline: "example confirmation".into(),
+				type_name: "Market Sell".to_owned(),
+				creation_time: 1687457923,
+				cancel: "Cancel".to_owned(),
+				accept: "Confirm".to_owned(),
+				icon: Some("".to_owned()),
+				multi: false,
+				summary: vec![],
+			},
+		],
+		|conf| {
+			// pretend to be a slow request, to show the loading state
+			std::thread::sleep(std::time::Duration::from_secs(1));
+			Ok(ConfirmationDetails {
+				text: vec![format!("example details for {}", conf.type_name)],
+				..Default::default()
+			})
 		},
-		Confirmation {
-			id: "1234".to_owned(),
-			nonce: "12345".to_owned(),
-			conf_type: ConfirmationType::MarketSell,
-			creator_id: "09870987".to_owned(),
-			headline: "example confirmation".into(),
-			type_name: "Market Sell".to_owned(),
-			creation_time: 1687457923,
-			cancel: "Cancel".to_owned(),
-			accept: "Confirm".to_owned(),
-			icon: Some("".to_owned()),
-			multi: false,
-			summary: vec![],
-		},
-	])
+	)
 	.expect("confirmation menu demo failed");
 	println!("accept: {}, deny: {}", accept.len(), deny.len());
 }
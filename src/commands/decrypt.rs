@@ -1,12 +1,16 @@
-use log::*;
-
-use crate::{AccountManager, ManifestAccountLoadError};
+use crate::AccountManager;
 
 use super::*;
 
 #[derive(Debug, Clone, Parser)]
 #[clap(about = "Decrypt all maFiles")]
-pub struct DecryptCommand;
+pub struct DecryptCommand {
+	#[clap(
+		long,
+		help = "Remove the passwords stored with `steamguard credentials --store-password`. Without this, decrypting is refused if any passwords are stored, so they aren't written to disk in plain text."
+	)]
+	pub clear_passwords: bool,
+}
 
 impl<T> ManifestCommand<T> for DecryptCommand
 where
@@ -16,9 +20,25 @@ where
 		&self,
 		_transport: T,
 		manager: &mut AccountManager,
-		_args: &GlobalArgs,
+		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
-		load_accounts_with_prompts(manager)?;
+		load_accounts_with_prompts(manager, args)?;
+
+		let account_names: Vec<String> = manager.iter().map(|e| e.account_name.clone()).collect();
+		for account_name in account_names {
+			let account = manager.get_account(&account_name)?;
+			let mut account = account.lock().unwrap();
+			if account.credentials.is_none() {
+				continue;
+			}
+			ensure!(
+				self.clear_passwords,
+				"{} has a stored password, which would be written to disk in plain text. Use --clear-passwords to remove stored passwords, or use `steamguard credentials --command` instead.",
+				account_name
+			);
+			account.credentials = None;
+			info!("{}: Removed the stored password", account_name);
+		}
 
 		#[cfg(feature = "keyring")]
 		if let Some(keyring_id) = manager.keyring_id() {
@@ -39,24 +59,49 @@ where
 	}
 }
 
-fn load_accounts_with_prompts(manager: &mut AccountManager) -> anyhow::Result<()> {
-	loop {
-		match manager.load_accounts() {
-			Ok(_) => return Ok(()),
-			Err(
-				ManifestAccountLoadError::MissingPasskey
-				| ManifestAccountLoadError::IncorrectPasskey,
-			) => {
-				if manager.has_passkey() {
-					error!("Incorrect passkey");
-				}
-				let passkey = Some(crate::tui::prompt_passkey()?);
-				manager.submit_passkey(passkey);
-			}
-			Err(e) => {
-				error!("Could not load accounts: {}", e);
-				return Err(e.into());
-			}
-		}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use secrecy::SecretString;
+	use steamguard::{transport::MockTransport, Credentials};
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_refuse_to_decrypt_stored_passwords() -> anyhow::Result<()> {
+		let tmp_dir = TempDir::new()?;
+		let manifest_path = tmp_dir.path().join("manifest.json");
+		let mut manager = AccountManager::new(manifest_path.as_path());
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		account.credentials = Some(Credentials {
+			password: SecretString::new("hunter2".into()),
+		});
+		manager.add_account(account);
+		manager.save()?;
+
+		let args = GlobalArgs::try_parse_from(["steamguard"])?;
+		let mut manager = AccountManager::load(manifest_path.as_path())?;
+		let result = DecryptCommand::try_parse_from(["decrypt"])?.execute(
+			MockTransport::new(),
+			&mut manager,
+			&args,
+		);
+		assert!(result.is_err());
+
+		let mut manager = AccountManager::load(manifest_path.as_path())?;
+		DecryptCommand::try_parse_from(["decrypt", "--clear-passwords"])?.execute(
+			MockTransport::new(),
+			&mut manager,
+			&args,
+		)?;
+		let mut manager = AccountManager::load(manifest_path.as_path())?;
+		manager.load_accounts()?;
+		assert!(manager
+			.get_account("example")?
+			.lock()
+			.unwrap()
+			.credentials
+			.is_none());
+		Ok(())
 	}
 }
@@ -1,8 +1,10 @@
 use log::*;
 use secrecy::ExposeSecret;
 
+use std::path::PathBuf;
+
 use crate::{
-	encryption::{EncryptionScheme, EntryEncryptor},
+	encryption::{parse_recipient, AgeRecipients, Argon2Params, EncryptionScheme},
 	tui, AccountManager,
 };
 
@@ -10,7 +12,116 @@ use super::*;
 
 #[derive(Debug, Clone, Parser)]
 #[clap(about = "Encrypt all maFiles")]
-pub struct EncryptCommand;
+pub struct EncryptCommand {
+	#[clap(flatten)]
+	pub argon2: Argon2Args,
+	#[clap(flatten, next_help_heading = "Public key encryption")]
+	pub recipients: RecipientArgs,
+}
+
+/// Options for encrypting maFiles to age recipients instead of a passkey.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct RecipientArgs {
+	#[clap(
+		short = 'r',
+		long = "recipient",
+		value_parser = validate_recipient,
+		help = "Encrypt to this age public key instead of a passkey. Can be repeated.",
+		long_help = "Encrypt to this age public key (`age1...`) instead of a passkey. Can be repeated. The maFiles can then be decrypted by anyone with one of the matching identities, given with --age-identity. Saving changes only needs the public keys."
+	)]
+	pub recipients: Vec<String>,
+	#[clap(
+		short = 'R',
+		long,
+		help = "Encrypt to the age public keys listed in this file, one per line. Can be repeated."
+	)]
+	pub recipients_file: Vec<PathBuf>,
+}
+
+impl RecipientArgs {
+	/// The recipients given on the command line, or `None` if there are none.
+	pub fn recipients(&self) -> anyhow::Result<Option<AgeRecipients>> {
+		let mut recipients = self.recipients.clone();
+		for path in self.recipients_file.iter() {
+			let contents = std::fs::read_to_string(path)?;
+			recipients.extend(
+				contents
+					.lines()
+					.map(str::trim)
+					.filter(|line| !line.is_empty() && !line.starts_with('#'))
+					.map(str::to_owned),
+			);
+		}
+		if recipients.is_empty() {
+			return Ok(None);
+		}
+		Ok(Some(AgeRecipients::new(recipients)?))
+	}
+}
+
+fn validate_recipient(s: &str) -> Result<String, String> {
+	parse_recipient(s).map_err(|err| err.to_string())?;
+	Ok(s.to_owned())
+}
+
+/// Options for choosing how expensive it is to derive encryption keys from the passkey.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct Argon2Args {
+	#[clap(
+		long,
+		value_enum,
+		help = "Argon2 cost preset to use. Defaults to `default`.",
+		long_help = "Argon2 cost preset to use. Stronger presets make brute forcing the passkey harder, but make loading accounts slower and use more memory. Use `steamguard benchmark` to find parameters that suit this machine. Defaults to `default`."
+	)]
+	pub argon2_preset: Option<Argon2Preset>,
+	#[clap(long, help = "Argon2 memory cost in KiB. Overrides the preset.")]
+	pub argon2_memory: Option<u32>,
+	#[clap(long, help = "Argon2 iterations. Overrides the preset.")]
+	pub argon2_iterations: Option<u32>,
+	#[clap(long, help = "Argon2 parallelism. Overrides the preset.")]
+	pub argon2_parallelism: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Argon2Preset {
+	/// 4 MiB of memory, 2 iterations, 1 lane. For low-memory machines.
+	Low,
+	/// 12 MiB of memory, 3 iterations, 12 lanes.
+	Default,
+	/// 64 MiB of memory, 4 iterations, 4 lanes.
+	High,
+}
+
+impl From<Argon2Preset> for Argon2Params {
+	fn from(preset: Argon2Preset) -> Self {
+		match preset {
+			Argon2Preset::Low => Argon2Params::LOW,
+			Argon2Preset::Default => Argon2Params::DEFAULT,
+			Argon2Preset::High => Argon2Params::HIGH,
+		}
+	}
+}
+
+impl Argon2Args {
+	/// The parameters chosen on the command line, or `None` if none of the options were given.
+	pub fn params(&self) -> anyhow::Result<Option<Argon2Params>> {
+		if self.argon2_preset.is_none()
+			&& self.argon2_memory.is_none()
+			&& self.argon2_iterations.is_none()
+			&& self.argon2_parallelism.is_none()
+		{
+			return Ok(None);
+		}
+		let preset: Argon2Params = self.argon2_preset.unwrap_or(Argon2Preset::Default).into();
+		let params = Argon2Params {
+			m_cost: self.argon2_memory.unwrap_or(preset.m_cost),
+			t_cost: self.argon2_iterations.unwrap_or(preset.t_cost),
+			p_cost: self.argon2_parallelism.unwrap_or(preset.p_cost),
+		};
+		params.to_argon2()?;
+		Ok(Some(params))
+	}
+}
 
 impl<T> ManifestCommand<T> for EncryptCommand
 where
@@ -20,9 +131,23 @@ where
 		&self,
 		_transport: T,
 		manager: &mut AccountManager,
-		_args: &GlobalArgs,
+		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
+		if let Some(recipients) = self.recipients.recipients()? {
+			if self.argon2.params()?.is_some() {
+				bail!("Argon2 options can't be used with age recipients.");
+			}
+			load_accounts_with_prompts(manager, args)?;
+			manager.rekey_to_recipients(recipients)?;
+			info!("Encrypted all maFiles to the age recipients");
+			return Ok(());
+		}
+
+		let params = self.argon2.params()?.unwrap_or_default();
 		if !manager.has_passkey() {
+			if args.non_interactive {
+				return Err(PromptRequiredError::Passkey.into());
+			}
 			let passkey: Option<SecretString>;
 			loop {
 				let passkey1 = tui::prompt_passkey()?;
@@ -67,7 +192,7 @@ where
 		}
 		manager.load_accounts()?;
 		for entry in manager.iter_mut() {
-			entry.encryption = Some(EncryptionScheme::generate());
+			entry.encryption = Some(EncryptionScheme::generate_with_params(params));
 		}
 		manager.save()?;
 		Ok(())
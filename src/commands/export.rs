@@ -0,0 +1,149 @@
+use std::{
+	io::Write,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+use log::*;
+use secrecy::ExposeSecret;
+
+use crate::{
+	accountmanager::export::{to_winauth_uri, write_new_file, write_sda_export, AccountBundle},
+	output::print_json,
+	tui, AccountManager,
+};
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+	/// One `otpauth://` URI per line, in the format Winauth uses.
+	Winauth,
+	/// maFiles and a manifest that SteamDesktopAuthenticator can read.
+	Sda,
+	/// A single file, encrypted with a passkey. Can be imported with `steamguard import`.
+	Bundle,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Export accounts to other formats. This *will* write sensitive data, keep the exported files safe."
+)]
+pub struct ExportCommand {
+	#[clap(long, value_enum, default_value_t = ExportFormat::Winauth, help = "The format to export to.")]
+	pub format: ExportFormat,
+	#[clap(
+		long,
+		help = "Where to write the export. Required for the sda format, which writes a directory.",
+		long_help = "Where to write the export. For `winauth` and `bundle`, this is a file, and defaults to stdout. For `sda`, this is a directory, and is required. Existing files are never overwritten."
+	)]
+	pub path: Option<PathBuf>,
+	#[clap(
+		long,
+		help = "Encrypt the exported maFiles. Only valid with the sda format."
+	)]
+	pub encrypt: bool,
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_EXPORT_PASSKEY",
+		help = "Passkey to encrypt the export with. If not provided, you will be prompted for one.",
+		long_help = "Passkey to encrypt the export with. This is not the same as the passkey used to encrypt your maFiles, but you can use the same one if you want. If not provided, you will be prompted for one."
+	)]
+	pub export_passkey: Option<SecretString>,
+}
+
+impl<T> AccountCommand<T> for ExportCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		_manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		let accounts: Vec<SteamGuardAccount> =
+			accounts.iter().map(|a| a.lock().unwrap().clone()).collect();
+		info!("Exporting {} accounts", accounts.len());
+
+		ensure!(
+			!self.encrypt || self.format == ExportFormat::Sda,
+			"--encrypt can only be used with the sda format. Bundles are always encrypted, and winauth exports can't be."
+		);
+
+		let needs_passkey = match self.format {
+			ExportFormat::Winauth => false,
+			ExportFormat::Sda => self.encrypt,
+			ExportFormat::Bundle => true,
+		};
+		let passkey = match (&self.export_passkey, needs_passkey) {
+			(_, false) => None,
+			(Some(passkey), true) => Some(passkey.clone()),
+			(None, true) if args.non_interactive => return Err(PromptRequiredError::Passkey.into()),
+			(None, true) => Some(tui::prompt_new_passkey("export")?),
+		};
+
+		match self.format {
+			ExportFormat::Winauth => {
+				let mut buf = String::new();
+				for account in accounts.iter() {
+					buf.push_str(&to_winauth_uri(account)?);
+					buf.push('\n');
+				}
+				self.write_output(buf.as_bytes())?;
+			}
+			ExportFormat::Sda => {
+				let Some(path) = &self.path else {
+					bail!("--path is required when exporting to the sda format.");
+				};
+				write_sda_export(
+					&accounts,
+					path,
+					passkey.as_ref().map(|p| p.expose_secret().as_str()),
+				)?;
+			}
+			ExportFormat::Bundle => {
+				let bundle = AccountBundle { accounts };
+				let envelope = bundle.seal(passkey.unwrap().expose_secret())?;
+				let mut buf = serde_json::to_vec(&envelope)?;
+				buf.push(b'\n');
+				self.write_output(&buf)?;
+				return self.print_summary(&bundle.accounts, args);
+			}
+		}
+
+		self.print_summary(&accounts, args)
+	}
+}
+
+impl ExportCommand {
+	fn write_output(&self, buf: &[u8]) -> anyhow::Result<()> {
+		match &self.path {
+			Some(path) => write_new_file(path, buf),
+			None => {
+				std::io::stdout().write_all(buf)?;
+				Ok(())
+			}
+		}
+	}
+
+	fn print_summary(
+		&self,
+		accounts: &[SteamGuardAccount],
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		let Some(path) = &self.path else {
+			// the export itself went to stdout
+			return Ok(());
+		};
+		if args.output == OutputFormat::Json {
+			return print_json(&serde_json::json!({
+				"path": path,
+				"accounts": accounts.iter().map(|a| &a.account_name).collect::<Vec<_>>(),
+			}));
+		}
+		info!("Exported {} accounts to {}", accounts.len(), path.display());
+		Ok(())
+	}
+}
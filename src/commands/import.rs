@@ -2,17 +2,27 @@ use std::path::Path;
 
 use log::*;
 
-use crate::{accountmanager::ManifestAccountImportError, AccountManager};
+use crate::{
+	accountmanager::{export::AccountBundle, ManifestAccountImportError},
+	encryption::Envelope,
+	AccountManager,
+};
 
 use super::*;
 
 #[derive(Debug, Clone, Parser, Default)]
 #[clap(
-	about = "Import an account with steamguard already set up. It must not be encrypted. If you haven't used steamguard-cli before, you probably don't need to use this command."
+	about = "Import an account with steamguard already set up. It must not be encrypted, unless it's a bundle created by `steamguard export`. If you haven't used steamguard-cli before, you probably don't need to use this command."
 )]
 pub struct ImportCommand {
 	#[clap(long, help = "Paths to one or more maFiles, eg. \"./gaben.maFile\"")]
 	pub files: Vec<String>,
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_EXPORT_PASSKEY",
+		help = "Passkey for bundles created by `steamguard export`. If not provided, you will be prompted for it."
+	)]
+	pub export_passkey: Option<SecretString>,
 }
 
 impl<T> ManifestCommand<T> for ImportCommand
@@ -23,11 +33,30 @@ where
 		&self,
 		_transport: T,
 		manager: &mut AccountManager,
-		_args: &GlobalArgs,
+		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
 		let mut accounts_added = 0;
 		for file_path in self.files.iter() {
 			debug!("loading entry: {:?}", file_path);
+			if let Some(envelope) = std::fs::read(file_path)
+				.ok()
+				.and_then(|buf| Envelope::parse(&buf))
+			{
+				let bundle = self.open_bundle(&envelope, args)?;
+				for account in bundle.accounts {
+					if manager.account_exists(&account.account_name) {
+						warn!(
+							"Account already exists: {} -- Ignoring",
+							account.account_name
+						);
+						continue;
+					}
+					info!("Imported account: {}", account.account_name);
+					manager.add_account(account);
+					accounts_added += 1;
+				}
+				continue;
+			}
 			match manager.import_account(file_path) {
 				Ok(_) => {
 					info!("Imported account: {}", &file_path);
@@ -69,3 +98,14 @@ where
 		Ok(())
 	}
 }
+
+impl ImportCommand {
+	fn open_bundle(&self, envelope: &Envelope, args: &GlobalArgs) -> anyhow::Result<AccountBundle> {
+		open_envelope_with_prompts(
+			self.export_passkey.clone(),
+			"Enter bundle passkey: ",
+			args,
+			|passkey| AccountBundle::open(envelope, passkey),
+		)
+	}
+}
@@ -1,12 +1,17 @@
+use std::collections::BTreeMap;
+
 use clap::Parser;
+use serde::Serialize;
 use steamguard::transport::Transport;
 
-use crate::{accountmanager::ManifestEntry, AccountManager};
+use crate::{accountmanager::ManifestEntry, output::print_json, AccountManager};
 
-use super::{GlobalArgs, ManifestCommand};
+use super::{GlobalArgs, ManifestCommand, OutputFormat};
 
 #[derive(Debug, Clone, Parser)]
-#[clap(about = "List all accounts from the manifest.")]
+#[clap(
+	about = "List all accounts from the manifest. Use `--group` or `--tag` to only list some of them."
+)]
 pub struct ListCommand;
 
 impl<T> ManifestCommand<T> for ListCommand
@@ -17,9 +22,18 @@ where
 		&self,
 		_transport: T,
 		manager: &mut AccountManager,
-		_args: &GlobalArgs,
+		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
-		let account_names = account_names(manager.iter());
+		let filters = args.tag_filters();
+		let entries = manager
+			.iter()
+			.filter(|entry| filters.iter().all(|tag| tag.matches(entry)));
+		if args.output == OutputFormat::Json {
+			let accounts: Vec<_> = entries.map(AccountListOutput::from).collect();
+			return print_json(&serde_json::json!({ "accounts": accounts }));
+		}
+
+		let account_names = account_names(entries);
 		if account_names.is_empty() {
 			println!("No accounts found in manifest.");
 			return Ok(());
@@ -33,6 +47,27 @@ where
 	}
 }
 
+#[derive(Debug, Serialize)]
+struct AccountListOutput<'a> {
+	account_name: &'a str,
+	steam_id: u64,
+	encrypted: bool,
+	tags: &'a BTreeMap<String, String>,
+	note: Option<&'a str>,
+}
+
+impl<'a> From<&'a ManifestEntry> for AccountListOutput<'a> {
+	fn from(entry: &'a ManifestEntry) -> Self {
+		Self {
+			account_name: &entry.account_name,
+			steam_id: entry.steam_id,
+			encrypted: entry.encryption.is_some(),
+			tags: &entry.tags,
+			note: entry.note.as_deref(),
+		}
+	}
+}
+
 fn account_names<'a>(entries: impl Iterator<Item = &'a ManifestEntry>) -> Vec<&'a str> {
 	entries.map(|entry| entry.account_name.as_str()).collect()
 }
@@ -51,12 +86,18 @@ mod tests {
 				steam_id: 2,
 				account_name: String::from("beta"),
 				encryption: None,
+				tags: Default::default(),
+				note: None,
+				password_command: None,
 			},
 			ManifestEntry {
 				filename: String::from("alpha.maFile"),
 				steam_id: 1,
 				account_name: String::from("alpha"),
 				encryption: None,
+				tags: Default::default(),
+				note: None,
+				password_command: None,
 			},
 		];
 
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use log::*;
+
+use crate::AccountManager;
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Log in to Steam, and store the new tokens for the account.")]
+pub struct LoginCommand {
+	#[cfg(feature = "qr")]
+	#[clap(
+		long,
+		help = "Log in by scanning a QR code with the Steam mobile app on another device, instead of typing the password.",
+		long_help = "Log in by scanning a QR code with the Steam mobile app on another device, instead of typing the password. The QR code is printed to stderr, and is replaced when Steam issues a new one."
+	)]
+	pub qr: bool,
+	#[cfg(feature = "qr")]
+	#[clap(
+		long,
+		requires = "qr",
+		help = "Force using ASCII chars to generate QR codes. Useful for terminals that don't support unicode."
+	)]
+	pub ascii: bool,
+}
+
+impl<T> AccountCommand<T> for LoginCommand
+where
+	T: Transport + Clone,
+{
+	fn execute(
+		&self,
+		transport: T,
+		manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		ensure!(
+			accounts.len() == 1,
+			"You can only log in to one account at a time."
+		);
+
+		let mut account = accounts[0].lock().unwrap();
+
+		#[cfg(feature = "qr")]
+		if self.qr {
+			info!("Logging in to {} with a QR code", account.account_name);
+			let tokens = crate::do_login_qr(transport, |url| self.show_challenge_url(url))?;
+			let steam_id = tokens.access_token().decode()?.steam_id();
+			if account.steam_id != 0 && account.steam_id != steam_id {
+				bail!(
+					"The QR code was approved by a different account ({}) than {} ({}). Not storing the tokens.",
+					steam_id,
+					account.account_name,
+					account.steam_id
+				);
+			}
+			account.set_tokens(tokens);
+			account.steam_id = steam_id;
+			drop(account);
+			manager.save()?;
+			return Ok(());
+		}
+
+		info!("Logging in to {}", account.account_name);
+		// without this, do_login would just refresh the access token
+		account.tokens = None;
+		let login = args.login_options(manager, &account.account_name);
+		crate::do_login(transport, &mut account, &login, manager.time_sync())?;
+		drop(account);
+		manager.save()?;
+		Ok(())
+	}
+}
+
+#[cfg(feature = "qr")]
+impl LoginCommand {
+	fn show_challenge_url(&self, url: &str) {
+		debug!("challenge url: {}", url);
+		match super::qr::render_qr(url, self.ascii) {
+			Ok(qr) => {
+				eprintln!("{}", qr);
+				eprintln!("Scan this QR code with the Steam mobile app to log in. It will be replaced if Steam issues a new one.");
+			}
+			Err(err) => {
+				warn!("Failed to render QR code: {}", err);
+				eprintln!("Open this URL with the Steam mobile app to log in: {}", url);
+			}
+		}
+	}
+}
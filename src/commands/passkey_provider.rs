@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use log::*;
+
+use crate::{encryption::PasskeyProvider, output::print_json, AccountManager};
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Configure where to get the encryption passkey from, instead of prompting for it.",
+	long_about = "Configure where to get the encryption passkey from, instead of prompting for it. The provider is used when no passkey is given with --passkey or found in the keyring. Without any options, the current provider is shown."
+)]
+#[clap(group = clap::ArgGroup::new("provider").multiple(false))]
+pub struct PasskeyProviderCommand {
+	#[clap(
+		long,
+		group = "provider",
+		num_args = 1..,
+		allow_hyphen_values = true,
+		value_name = "COMMAND",
+		help = "Run this command and read the passkey from its stdout. Must be the last option, eg. `--command pass show steamguard`."
+	)]
+	pub command: Option<Vec<String>>,
+	#[clap(
+		long,
+		group = "provider",
+		help = "Read the passkey from this file descriptor. Unix only."
+	)]
+	pub fd: Option<u32>,
+	#[clap(
+		long,
+		group = "provider",
+		help = "Read the passkey from this file. The file must only be accessible by its owner."
+	)]
+	pub file: Option<PathBuf>,
+	#[clap(long, group = "provider", help = "Remove the passkey provider.")]
+	pub clear: bool,
+}
+
+impl<T> ManifestCommand<T> for PasskeyProviderCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		let provider = match (&self.command, self.fd, &self.file) {
+			(Some(command), _, _) => Some(PasskeyProvider::Command {
+				command: command.clone(),
+			}),
+			(_, Some(fd), _) => Some(PasskeyProvider::Fd { fd }),
+			(_, _, Some(path)) => Some(PasskeyProvider::File {
+				path: std::path::absolute(path)?,
+			}),
+			_ if self.clear => None,
+			_ => {
+				if args.output == OutputFormat::Json {
+					return print_json(&serde_json::json!({
+						"passkey_provider": manager.passkey_provider()
+					}));
+				}
+				match manager.passkey_provider() {
+					Some(provider) => println!("Passkey provider: {}", provider),
+					None => println!("No passkey provider is configured."),
+				}
+				return Ok(());
+			}
+		};
+
+		// make sure the provider actually works before relying on it. File descriptors are usually only provided when needed, so they can't be checked here.
+		if let Some(provider) = provider
+			.as_ref()
+			.filter(|p| !matches!(p, PasskeyProvider::Fd { .. }))
+		{
+			let passkey = provider.fetch()?;
+			if manager.iter().any(|e| e.encryption.is_some()) {
+				manager.submit_passkey(Some(passkey));
+				match manager.load_accounts() {
+					Ok(()) => {}
+					Err(ManifestAccountLoadError::IncorrectPasskey) => {
+						bail!("The passkey provider returned an incorrect passkey.")
+					}
+					Err(err) => return Err(err.into()),
+				}
+			}
+		}
+
+		match &provider {
+			Some(provider) => info!("Passkey will be read from {}", provider),
+			None => info!("Removed the passkey provider"),
+		}
+		manager.set_passkey_provider(provider);
+		manager.save()?;
+		if args.output == OutputFormat::Json {
+			return print_json(&serde_json::json!({
+				"passkey_provider": manager.passkey_provider()
+			}));
+		}
+		Ok(())
+	}
+}
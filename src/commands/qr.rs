@@ -4,7 +4,7 @@ use log::*;
 use qrcode::QrCode;
 use secrecy::ExposeSecret;
 
-use crate::AccountManager;
+use crate::{output::print_json, AccountManager};
 
 use super::*;
 
@@ -27,34 +27,55 @@ where
 		_transport: T,
 		_manager: &mut AccountManager,
 		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
-		_args: &GlobalArgs,
+		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
 		use anyhow::Context;
 
+		if args.output == OutputFormat::Json {
+			let accounts: Vec<_> = accounts
+				.iter()
+				.map(|account| {
+					let account = account.lock().unwrap();
+					serde_json::json!({
+						"account_name": account.account_name,
+						"uri": account.uri.expose_secret(),
+					})
+				})
+				.collect();
+			return print_json(&serde_json::json!({ "accounts": accounts }));
+		}
+
 		info!("Generating QR codes for {} accounts", accounts.len());
 
 		for account in accounts {
 			let account = account.lock().unwrap();
-			let qr = QrCode::new(account.uri.expose_secret())
+			let qr_string = render_qr(account.uri.expose_secret(), self.ascii)
 				.context(format!("generating qr code for {}", account.account_name))?;
 
 			info!("Printing QR code for {}", account.account_name);
-			let qr_string = if self.ascii {
-				qr.render()
-					.light_color(' ')
-					.dark_color('#')
-					.module_dimensions(2, 1)
-					.build()
-			} else {
-				use qrcode::render::unicode;
-				qr.render::<unicode::Dense1x2>()
-					.dark_color(unicode::Dense1x2::Light)
-					.light_color(unicode::Dense1x2::Dark)
-					.build()
-			};
-
 			println!("{}", qr_string);
 		}
 		Ok(())
 	}
 }
+
+/// Render `data` as a QR code that can be printed to a terminal.
+///
+/// With `ascii`, only ASCII characters are used, for terminals that don't support unicode.
+pub(crate) fn render_qr(data: &str, ascii: bool) -> anyhow::Result<String> {
+	let qr = QrCode::new(data)?;
+	let qr_string = if ascii {
+		qr.render()
+			.light_color(' ')
+			.dark_color('#')
+			.module_dimensions(2, 1)
+			.build()
+	} else {
+		use qrcode::render::unicode;
+		qr.render::<unicode::Dense1x2>()
+			.dark_color(unicode::Dense1x2::Light)
+			.light_color(unicode::Dense1x2::Dark)
+			.build()
+	};
+	Ok(qr_string)
+}
@@ -26,7 +26,7 @@ where
 	fn execute(
 		&self,
 		transport: T,
-		_manager: &mut AccountManager,
+		manager: &mut AccountManager,
 		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
 		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
@@ -40,7 +40,8 @@ where
 		info!("Approving login to {}", account.account_name);
 
 		if account.tokens.is_none() {
-			crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+			let login = args.login_options(manager, &account.account_name);
+			crate::do_login(transport.clone(), &mut account, &login, manager.time_sync())?;
 		}
 
 		let url = self.login_url_source.url()?;
@@ -66,7 +67,8 @@ where
 				}
 				Err(ApproverError::Unauthorized) => {
 					warn!("tokens are invalid. Attempting to log in again.");
-					crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+					let login = args.login_options(manager, &account.account_name);
+					crate::do_login(transport.clone(), &mut account, &login, manager.time_sync())?;
 				}
 				Err(e) => {
 					error!("Failed to approve login: {}", e);
@@ -0,0 +1,82 @@
+use log::*;
+use secrecy::ExposeSecret;
+
+use crate::{tui, AccountManager};
+
+use super::{
+	encrypt::{Argon2Args, RecipientArgs},
+	*,
+};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Re-encrypt all maFiles with a new passkey, or to new age recipients. The maFiles are never written to disk unencrypted."
+)]
+pub struct RekeyCommand {
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_NEW_PASSKEY",
+		help = "The new passkey. If not provided, you will be prompted for it."
+	)]
+	pub new_passkey: Option<SecretString>,
+	#[clap(
+		flatten,
+		next_help_heading = "Argon2 options (by default, each entry keeps its current parameters)"
+	)]
+	pub argon2: Argon2Args,
+	#[clap(
+		flatten,
+		next_help_heading = "Public key encryption (replaces the current recipients, or the passkey)"
+	)]
+	pub recipients: RecipientArgs,
+}
+
+impl<T> ManifestCommand<T> for RekeyCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		let params = self.argon2.params()?;
+		if !manager.iter().any(|e| e.encryption.is_some()) {
+			bail!("maFiles are not encrypted. Use `steamguard encrypt` to encrypt them.");
+		}
+		load_accounts_with_prompts(manager, args)?;
+
+		if let Some(recipients) = self.recipients.recipients()? {
+			if self.new_passkey.is_some() || params.is_some() {
+				bail!("A new passkey or Argon2 options can't be used with age recipients.");
+			}
+			manager.rekey_to_recipients(recipients)?;
+			info!("Re-encrypted all maFiles to the new age recipients");
+			return Ok(());
+		}
+
+		let new_passkey = match &self.new_passkey {
+			Some(passkey) => passkey.clone(),
+			None if args.non_interactive => return Err(PromptRequiredError::Passkey.into()),
+			None => tui::prompt_new_passkey("new encryption")?,
+		};
+		if new_passkey.expose_secret().is_empty() {
+			bail!("Passkey cannot be empty.");
+		}
+		manager.rekey(new_passkey.clone(), params)?;
+		info!("Re-encrypted all maFiles with the new passkey");
+
+		#[cfg(feature = "keyring")]
+		if let Some(keyring_id) = manager.keyring_id() {
+			match crate::encryption::store_passkey(keyring_id.clone(), new_passkey) {
+				Ok(_) => info!("Updated passkey in keyring"),
+				Err(e) => warn!(
+					"Failed to update passkey in keyring, you will need to enter the new passkey manually: {}",
+					e
+				),
+			}
+		}
+		Ok(())
+	}
+}
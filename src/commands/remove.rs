@@ -3,7 +3,11 @@ use std::sync::{Arc, Mutex};
 use log::*;
 use steamguard::{accountlinker::RemoveAuthenticatorError, transport::TransportError};
 
-use crate::{errors::UserError, tui, AccountManager};
+use crate::{
+	errors::{PromptRequiredError, UserError},
+	output::print_json,
+	tui, AccountManager,
+};
 
 use super::*;
 
@@ -32,6 +36,11 @@ where
 				.join(", ")
 		);
 
+		if args.non_interactive {
+			return Err(
+				PromptRequiredError::Confirmation("Removing the authenticator".into()).into(),
+			);
+		}
 		match tui::prompt_char("Do you want to continue?", "yN") {
 			'y' => {}
 			_ => {
@@ -54,7 +63,13 @@ where
 					}
 					Err(RemoveAuthenticatorError::TransportError(TransportError::Unauthorized)) => {
 						error!("Account {} is not logged in", account.account_name);
-						crate::do_login(transport.clone(), &mut account, args.password.clone())?;
+						let login = args.login_options(manager, &account.account_name);
+						crate::do_login(
+							transport.clone(),
+							&mut account,
+							&login,
+							manager.time_sync(),
+						)?;
 						continue;
 					}
 					Err(RemoveAuthenticatorError::IncorrectRevocationCode {
@@ -92,11 +107,14 @@ where
 			}
 		}
 
-		for account_name in successful {
-			manager.remove_account(&account_name);
+		for account_name in &successful {
+			manager.remove_account(account_name);
 		}
 
 		manager.save()?;
+		if args.output == OutputFormat::Json {
+			print_json(&serde_json::json!({ "removed": successful }))?;
+		}
 		Ok(())
 	}
 }
@@ -0,0 +1,225 @@
+use std::path::PathBuf;
+
+use log::*;
+
+use crate::{
+	accountmanager::backup::BackupArchive,
+	encryption::{AgeRecipients, EncryptionScheme, Envelope},
+	output::print_json,
+	AccountManager,
+};
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RestoreMode {
+	/// Add the accounts in the backup to the existing manifest.
+	Merge,
+	/// Replace the manifest and all maFiles with the ones in the backup.
+	Replace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CollisionAction {
+	/// Don't restore anything.
+	Abort,
+	/// Keep the existing account.
+	Skip,
+	/// Replace the existing account with the one from the backup.
+	Overwrite,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Restore a backup created by `steamguard backup`.")]
+pub struct RestoreCommand {
+	#[clap(long, help = "Path to the backup.")]
+	pub path: PathBuf,
+	#[clap(
+		long,
+		value_enum,
+		default_value_t = RestoreMode::Merge,
+		help = "Whether to merge the backup into the existing manifest, or replace it.",
+		long_help = "Whether to merge the backup into the existing manifest, or replace it. When replacing, the existing manifest and maFiles are kept with a `.bak` extension, and the restored maFiles keep the passkey they were encrypted with when the backup was created."
+	)]
+	pub mode: RestoreMode,
+	#[clap(
+		long,
+		value_enum,
+		default_value_t = CollisionAction::Abort,
+		help = "What to do when an account in the backup is already in the manifest. Only used when merging."
+	)]
+	pub on_collision: CollisionAction,
+	#[clap(
+		long,
+		env = "STEAMGUARD_CLI_BACKUP_PASSKEY",
+		help = "Passkey the backup was encrypted with. If not provided, you will be prompted for it."
+	)]
+	pub backup_passkey: Option<SecretString>,
+}
+
+impl<T> ManifestCommand<T> for RestoreCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		let buf = std::fs::read(&self.path)?;
+		let Some(envelope) = Envelope::parse(&buf) else {
+			bail!("{} is not a backup.", self.path.display());
+		};
+		let archive = open_envelope_with_prompts(
+			self.backup_passkey.clone(),
+			"Enter backup passkey: ",
+			args,
+			|passkey| BackupArchive::open(&envelope, passkey),
+		)?;
+		info!(
+			"Backup was created at {} by steamguard-cli {}",
+			archive.created_at, archive.created_by
+		);
+
+		let mut restored = vec![];
+		let mut skipped = vec![];
+		match self.mode {
+			RestoreMode::Replace => {
+				manager.restore_replace(&archive)?;
+				restored = archive
+					.manifest()?
+					.entries
+					.into_iter()
+					.map(|e| e.account_name)
+					.collect();
+			}
+			RestoreMode::Merge => {
+				let collisions = manager.backup_collisions(&archive)?;
+				if !collisions.is_empty() && self.on_collision == CollisionAction::Abort {
+					bail!(
+						"These accounts are already in the manifest: {}. Use `--on-collision skip`, `--on-collision overwrite` or `--mode replace` to restore anyway.",
+						collisions.join(", ")
+					);
+				}
+				let encryption = new_entry_encryption(manager)?;
+				for account in load_backup_accounts(&archive, args)? {
+					let account_name = account.account_name.clone();
+					if collisions.contains(&account_name) {
+						if self.on_collision == CollisionAction::Skip {
+							warn!("Account already exists: {} -- Skipping", account_name);
+							skipped.push(account_name);
+							continue;
+						}
+						warn!("Account already exists: {} -- Overwriting", account_name);
+						manager.remove_account(&account_name);
+					}
+					manager.add_account(account);
+					manager.get_entry_mut(&account_name)?.encryption = encryption.clone();
+					restored.push(account_name);
+				}
+				manager.save()?;
+			}
+		}
+
+		if args.output == OutputFormat::Json {
+			return print_json(&serde_json::json!({
+				"restored": restored,
+				"skipped": skipped,
+			}));
+		}
+		info!("Restored {} accounts", restored.len());
+		Ok(())
+	}
+}
+
+/// The encryption for restored entries, so they are encrypted the same way as the entries already in the manifest.
+fn new_entry_encryption(manager: &AccountManager) -> anyhow::Result<Option<EncryptionScheme>> {
+	let Some(scheme) = manager.iter().find_map(|e| e.encryption.clone()) else {
+		return Ok(None);
+	};
+	if let EncryptionScheme::AgeRecipients(scheme) = scheme {
+		return Ok(Some(EncryptionScheme::AgeRecipients(AgeRecipients::new(
+			scheme.recipients,
+		)?)));
+	}
+	ensure!(
+		manager.has_passkey(),
+		"The maFiles are encrypted, but no passkey was provided."
+	);
+	Ok(Some(EncryptionScheme::generate_with_params(
+		scheme.argon2_params().unwrap_or_default(),
+	)))
+}
+
+/// Decrypt the accounts in the backup, prompting for the passkey the backed up maFiles were encrypted with if needed.
+fn load_backup_accounts(
+	archive: &BackupArchive,
+	args: &GlobalArgs,
+) -> anyhow::Result<Vec<SteamGuardAccount>> {
+	let mut passkey = args.passkey.clone();
+	loop {
+		match archive.load_accounts(passkey.as_ref()) {
+			Ok(accounts) => return Ok(accounts),
+			Err(
+				ManifestAccountLoadError::MissingPasskey
+				| ManifestAccountLoadError::IncorrectPasskey,
+			) => {
+				if passkey.is_some() {
+					error!("Incorrect passkey");
+				}
+				if args.non_interactive {
+					return Err(PromptRequiredError::Passkey.into());
+				}
+				passkey = Some(
+					rpassword::prompt_password(
+						"Enter the passkey the backed up maFiles are encrypted with: ",
+					)
+					.map(SecretString::new)?,
+				);
+			}
+			Err(e) => return Err(e.into()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use age::secrecy::ExposeSecret;
+
+	#[test]
+	fn test_restored_entries_match_manifest_encryption() -> anyhow::Result<()> {
+		let mut manager = AccountManager::default();
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		manager.add_account(account);
+		assert!(new_entry_encryption(&manager)?.is_none());
+
+		manager.iter_mut().next().unwrap().encryption = Some(EncryptionScheme::generate());
+		assert!(new_entry_encryption(&manager).is_err());
+		manager.submit_passkey(Some(SecretString::new("password".into())));
+		assert!(matches!(
+			new_entry_encryption(&manager)?,
+			Some(EncryptionScheme::Argon2idXChaCha20Poly1305(_))
+		));
+
+		let identity = age::x25519::Identity::generate();
+		let recipient = identity.to_public().to_string();
+		manager.iter_mut().next().unwrap().encryption =
+			Some(EncryptionScheme::AgeRecipients(AgeRecipients::new(vec![
+				recipient.clone(),
+			])?));
+		// `--age-identity` is passed as the passkey
+		manager.submit_passkey(Some(SecretString::new(
+			identity.to_string().expose_secret().to_owned(),
+		)));
+		match new_entry_encryption(&manager)? {
+			Some(EncryptionScheme::AgeRecipients(scheme)) => {
+				assert_eq!(scheme.recipients, vec![recipient])
+			}
+			other => panic!("expected age recipients, got {:?}", other),
+		}
+		Ok(())
+	}
+}
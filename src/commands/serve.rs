@@ -0,0 +1,566 @@
+use std::{io::Read, net::SocketAddr};
+
+use anyhow::Context;
+use log::*;
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use steamguard::{
+	approver::Challenge, protobufs::enums::ESessionPersistence, ApproverError, Confirmation,
+	Confirmer, ConfirmerError, LoginApprover,
+};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+	output::{print_json, CodeOutput, ConfirmationOutput, SessionOutput},
+	AccountManager,
+};
+
+use super::*;
+
+/// Request bodies larger than this are rejected.
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Run a local HTTP server that exposes codes, confirmations and login approvals")]
+pub struct ServeCommand {
+	#[clap(
+		long,
+		default_value = "127.0.0.1:8484",
+		help = "Address to listen on. Must be a loopback address."
+	)]
+	pub listen: SocketAddr,
+
+	#[cfg(unix)]
+	#[clap(
+		long,
+		conflicts_with = "listen",
+		help = "Listen on a unix socket instead of TCP.",
+		long_help = "Listen on a unix socket instead of TCP. The socket is created with permissions 0600, and an existing socket at the same path is replaced."
+	)]
+	pub unix_socket: Option<std::path::PathBuf>,
+
+	#[clap(
+		long,
+		help = "Generate a new API token, replacing the one stored in the manifest."
+	)]
+	pub regenerate_token: bool,
+}
+
+impl<T> ManifestCommand<T> for ServeCommand
+where
+	T: Transport + Clone,
+{
+	fn execute(
+		&self,
+		transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		load_accounts_with_prompts(manager, args)?;
+
+		if self.regenerate_token || manager.api_token().is_none() {
+			let token = generate_api_token();
+			manager.set_api_token(token.clone());
+			manager.save()?;
+			info!("Generated a new API token and saved it to the manifest.");
+			match args.output {
+				OutputFormat::Text => println!("{}", token),
+				OutputFormat::Json => print_json(&serde_json::json!({ "api_token": token }))?,
+			}
+		}
+		let token = manager
+			.api_token()
+			.cloned()
+			.expect("api token should have been generated");
+
+		for entry in manager.iter() {
+			let account = manager.get_account(&entry.account_name)?;
+			if !account.lock().unwrap().is_logged_in() {
+				warn!(
+					"{} is not logged in, confirmations and sessions won't be available for it until you log in with another command.",
+					entry.account_name
+				);
+			}
+		}
+
+		let server = self.bind()?;
+		for mut request in server.incoming_requests() {
+			let result = handle_request(transport.clone(), manager, &token, &mut request);
+			let (status, body) = match result {
+				Ok(body) => (200, body),
+				Err(err) => {
+					if err.status() >= 500 {
+						error!("{} {}: {}", request.method(), request.url(), err);
+					}
+					(
+						err.status(),
+						serde_json::json!({ "error": err.to_string() }),
+					)
+				}
+			};
+			info!("{} {} -> {}", request.method(), request.url(), status);
+
+			let response = Response::from_string(body.to_string())
+				.with_status_code(status)
+				.with_header(
+					Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+				);
+			if let Err(err) = request.respond(response) {
+				warn!("Failed to send response: {}", err);
+			}
+		}
+		Ok(())
+	}
+}
+
+impl ServeCommand {
+	fn bind(&self) -> anyhow::Result<Server> {
+		#[cfg(unix)]
+		if let Some(path) = &self.unix_socket {
+			use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+			if let Ok(metadata) = std::fs::symlink_metadata(path) {
+				ensure!(
+					metadata.file_type().is_socket(),
+					"{} already exists and is not a socket",
+					path.display()
+				);
+				std::fs::remove_file(path)?;
+			}
+			// Bind inside a private directory first, so the socket is never reachable by other users
+			// before its permissions are restricted, then move it into place.
+			let parent = match path.parent() {
+				Some(parent) if !parent.as_os_str().is_empty() => parent,
+				_ => std::path::Path::new("."),
+			};
+			let private_dir = parent.join(format!(".steamguard-serve-{}", std::process::id()));
+			std::fs::DirBuilder::new()
+				.mode(0o700)
+				.create(&private_dir)
+				.with_context(|| format!("Failed to create {}", private_dir.display()))?;
+			let tmp_path = private_dir.join("socket");
+			let bound = Server::http_unix(&tmp_path)
+				.map_err(|e| anyhow!("Failed to listen on {}: {}", path.display(), e))
+				.and_then(|server| {
+					std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))?;
+					std::fs::rename(&tmp_path, path)?;
+					Ok(server)
+				});
+			let _ = std::fs::remove_file(&tmp_path);
+			std::fs::remove_dir(&private_dir)?;
+			let server = bound?;
+			info!("Listening on {}", path.display());
+			return Ok(server);
+		}
+
+		ensure!(
+			self.listen.ip().is_loopback(),
+			"Refusing to listen on {}, only loopback addresses are allowed.",
+			self.listen
+		);
+		let server = Server::http(self.listen)
+			.map_err(|e| anyhow!("Failed to listen on {}: {}", self.listen, e))?;
+		info!("Listening on http://{}", self.listen);
+		Ok(server)
+	}
+}
+
+fn generate_api_token() -> String {
+	let rng = rand::rng();
+	rng.sample_iter(rand::distr::Alphanumeric)
+		.take(48)
+		.map(char::from)
+		.collect()
+}
+
+/// Compares two byte strings in constant time, so that the token can't be guessed by timing the responses.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	if a.len() != b.len() {
+		return false;
+	}
+	a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn is_authorized(request: &Request, token: &str) -> bool {
+	request
+		.headers()
+		.iter()
+		.find(|h| h.field.equiv("Authorization"))
+		.and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+		.is_some_and(|provided| constant_time_eq(provided.trim().as_bytes(), token.as_bytes()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+	Accept,
+	Deny,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route<'a> {
+	ListAccounts,
+	Code(&'a str),
+	ListConfirmations(&'a str),
+	RespondConfirmations(&'a str, Decision),
+	ListSessions(&'a str),
+	RespondSession(&'a str, u64, Decision),
+}
+
+fn route<'a>(method: &Method, url: &'a str) -> Result<Route<'a>, ApiError> {
+	let path = url.split('?').next().unwrap_or_default();
+	let Some(path) = path.strip_prefix("/v1/") else {
+		return Err(ApiError::NotFound);
+	};
+	let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+	let route = match (method, segments.as_slice()) {
+		(Method::Get, ["accounts"]) => Route::ListAccounts,
+		(Method::Get, ["accounts", name, "code"]) => Route::Code(name),
+		(Method::Get, ["accounts", name, "confirmations"]) => Route::ListConfirmations(name),
+		(Method::Post, ["accounts", name, "confirmations", "accept"]) => {
+			Route::RespondConfirmations(name, Decision::Accept)
+		}
+		(Method::Post, ["accounts", name, "confirmations", "deny"]) => {
+			Route::RespondConfirmations(name, Decision::Deny)
+		}
+		(Method::Get, ["accounts", name, "sessions"]) => Route::ListSessions(name),
+		(Method::Post, ["accounts", name, "sessions", client_id, action]) => {
+			let client_id = client_id
+				.parse()
+				.map_err(|_| ApiError::BadRequest(format!("Invalid client id: {}", client_id)))?;
+			let decision = match *action {
+				"approve" => Decision::Accept,
+				"deny" => Decision::Deny,
+				_ => return Err(ApiError::NotFound),
+			};
+			Route::RespondSession(name, client_id, decision)
+		}
+		_ => return Err(ApiError::NotFound),
+	};
+	Ok(route)
+}
+
+#[derive(Debug, Error)]
+enum ApiError {
+	#[error("Missing or invalid API token.")]
+	Unauthorized,
+	#[error("Not found.")]
+	NotFound,
+	#[error("Unknown account: {0}")]
+	UnknownAccount(String),
+	#[error("Bad request: {0}")]
+	BadRequest(String),
+	#[error("{0} is not logged in. Log in with another command first.")]
+	NotLoggedIn(String),
+	#[error(transparent)]
+	Confirmer(#[from] ConfirmerError),
+	#[error(transparent)]
+	Approver(#[from] ApproverError),
+	#[error(transparent)]
+	Unknown(#[from] anyhow::Error),
+}
+
+impl ApiError {
+	fn status(&self) -> u16 {
+		match self {
+			Self::Unauthorized => 401,
+			Self::NotFound | Self::UnknownAccount(_) => 404,
+			Self::BadRequest(_) => 400,
+			Self::NotLoggedIn(_) => 409,
+			Self::Confirmer(_) | Self::Approver(_) => 502,
+			Self::Unknown(_) => 500,
+		}
+	}
+
+	fn is_invalid_tokens(&self) -> bool {
+		matches!(
+			self,
+			Self::Confirmer(ConfirmerError::InvalidTokens)
+				| Self::Approver(ApproverError::Unauthorized)
+		)
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct AccountInfo<'a> {
+	account_name: &'a str,
+	steam_id: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfirmationsRequest {
+	ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApproveSessionRequest {
+	#[serde(default = "default_persistent")]
+	persistent: bool,
+}
+
+impl Default for ApproveSessionRequest {
+	fn default() -> Self {
+		Self {
+			persistent: default_persistent(),
+		}
+	}
+}
+
+fn default_persistent() -> bool {
+	true
+}
+
+fn read_body<B>(request: &mut Request) -> Result<B, ApiError>
+where
+	B: for<'de> Deserialize<'de> + Default,
+{
+	let mut body = String::new();
+	request
+		.as_reader()
+		.take(MAX_BODY_SIZE)
+		.read_to_string(&mut body)
+		.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+	if body.trim().is_empty() {
+		return Ok(B::default());
+	}
+	serde_json::from_str(&body).map_err(|e| ApiError::BadRequest(e.to_string()))
+}
+
+fn handle_request<T>(
+	transport: T,
+	manager: &AccountManager,
+	token: &str,
+	request: &mut Request,
+) -> Result<serde_json::Value, ApiError>
+where
+	T: Transport + Clone,
+{
+	if !is_authorized(request, token) {
+		return Err(ApiError::Unauthorized);
+	}
+
+	let url = request.url().to_owned();
+	let route = route(request.method(), &url)?;
+	debug!("{:?}", route);
+
+	let value = match route {
+		Route::ListAccounts => {
+			let accounts: Vec<_> = manager
+				.iter()
+				.map(|e| AccountInfo {
+					account_name: &e.account_name,
+					steam_id: e.steam_id,
+				})
+				.collect();
+			serde_json::to_value(accounts)
+		}
+		Route::Code(name) => {
+			let account = manager
+				.get_account(name)
+				.map_err(|_| ApiError::UnknownAccount(name.to_owned()))?;
+			let account = account.lock().unwrap();
+			let server_time = manager.time_sync().server_time(transport)?;
+			serde_json::to_value(CodeOutput::new(&account, server_time))
+		}
+		Route::ListConfirmations(name) => {
+			let confirmations = with_account(transport.clone(), manager, name, |account| {
+				Ok(Confirmer::new(transport.clone(), account)
+					.with_time_sync(manager.time_sync().clone())
+					.get_confirmations()?)
+			})?;
+			serde_json::to_value(
+				confirmations
+					.iter()
+					.map(ConfirmationOutput::from)
+					.collect::<Vec<_>>(),
+			)
+		}
+		Route::RespondConfirmations(name, decision) => {
+			let body: ConfirmationsRequest = read_body(request)?;
+			if body.ids.is_empty() {
+				return Err(ApiError::BadRequest("ids must not be empty".into()));
+			}
+			let responded = with_account(transport.clone(), manager, name, |account| {
+				let confirmer = Confirmer::new(transport.clone(), account)
+					.with_time_sync(manager.time_sync().clone());
+				let confirmations = confirmer.get_confirmations()?;
+				let selected = select_confirmations(&confirmations, &body.ids)?;
+				info!(
+					"{}: {} {} confirmations via API",
+					name,
+					match decision {
+						Decision::Accept => "accepting",
+						Decision::Deny => "denying",
+					},
+					selected.len()
+				);
+				match decision {
+					Decision::Accept => confirmer.accept_confirmations_bulk(&selected)?,
+					Decision::Deny => confirmer.deny_confirmations_bulk(&selected)?,
+				}
+				Ok(selected.into_iter().map(|c| c.id).collect::<Vec<_>>())
+			})?;
+			serde_json::to_value(serde_json::json!({ "ids": responded }))
+		}
+		Route::ListSessions(name) => {
+			let sessions = with_account(transport.clone(), manager, name, |account| {
+				let tokens = account.tokens.as_ref().expect("account is logged in");
+				let approver = LoginApprover::new(transport.clone(), tokens);
+				let mut sessions = vec![];
+				for client_id in approver.list_auth_sessions()? {
+					sessions.push((client_id, approver.get_auth_session_info(client_id)?));
+				}
+				Ok(sessions)
+			})?;
+			serde_json::to_value(
+				sessions
+					.iter()
+					.map(|(client_id, info)| SessionOutput::new(*client_id, info))
+					.collect::<Vec<_>>(),
+			)
+		}
+		Route::RespondSession(name, client_id, decision) => {
+			let body: ApproveSessionRequest = read_body(request)?;
+			with_account(transport.clone(), manager, name, |account| {
+				let tokens = account.tokens.as_ref().expect("account is logged in");
+				let mut approver = LoginApprover::new(transport.clone(), tokens);
+				let challenge = Challenge::new(1, client_id);
+				match decision {
+					Decision::Accept => {
+						info!("{}: Approving session {} via API", name, client_id);
+						let persistence = if body.persistent {
+							ESessionPersistence::k_ESessionPersistence_Persistent
+						} else {
+							ESessionPersistence::k_ESessionPersistence_Ephemeral
+						};
+						approver.approve(account, challenge, persistence)?;
+					}
+					Decision::Deny => {
+						info!("{}: Denying session {} via API", name, client_id);
+						approver.deny(account, challenge)?;
+					}
+				}
+				Ok(())
+			})?;
+			serde_json::to_value(serde_json::json!({ "client_id": client_id }))
+		}
+	};
+	Ok(value.map_err(anyhow::Error::from)?)
+}
+
+/// Runs `f` with the account locked, refreshing the access token once if Steam says it's invalid.
+fn with_account<T, R>(
+	transport: T,
+	manager: &AccountManager,
+	name: &str,
+	f: impl Fn(&SteamGuardAccount) -> Result<R, ApiError>,
+) -> Result<R, ApiError>
+where
+	T: Transport + Clone,
+{
+	let account = manager
+		.get_account(name)
+		.map_err(|_| ApiError::UnknownAccount(name.to_owned()))?;
+	let mut account = account.lock().unwrap();
+	if !account.is_logged_in() {
+		return Err(ApiError::NotLoggedIn(name.to_owned()));
+	}
+
+	match f(&account) {
+		Err(err) if err.is_invalid_tokens() => {
+			info!("{}: Refreshing access token", name);
+			crate::refresh_tokens(transport, &mut account)?;
+			let result = f(&account);
+			drop(account);
+			manager.save()?;
+			result
+		}
+		result => result,
+	}
+}
+
+fn select_confirmations(
+	confirmations: &[Confirmation],
+	ids: &[String],
+) -> Result<Vec<Confirmation>, ApiError> {
+	let missing: Vec<&str> = ids
+		.iter()
+		.filter(|id| !confirmations.iter().any(|c| &c.id == *id))
+		.map(String::as_str)
+		.collect();
+	if !missing.is_empty() {
+		return Err(ApiError::BadRequest(format!(
+			"Unknown confirmation ids: {}",
+			missing.join(", ")
+		)));
+	}
+	Ok(confirmations
+		.iter()
+		.filter(|c| ids.contains(&c.id))
+		.cloned()
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_constant_time_eq() {
+		assert!(constant_time_eq(b"abc", b"abc"));
+		assert!(!constant_time_eq(b"abc", b"abd"));
+		assert!(!constant_time_eq(b"abc", b"abcd"));
+		assert!(!constant_time_eq(b"", b"a"));
+	}
+
+	#[test]
+	fn test_route() {
+		assert_eq!(
+			route(&Method::Get, "/v1/accounts").unwrap(),
+			Route::ListAccounts
+		);
+		assert_eq!(
+			route(&Method::Get, "/v1/accounts/example/code?foo=bar").unwrap(),
+			Route::Code("example")
+		);
+		assert_eq!(
+			route(&Method::Post, "/v1/accounts/example/confirmations/deny").unwrap(),
+			Route::RespondConfirmations("example", Decision::Deny)
+		);
+		assert_eq!(
+			route(&Method::Post, "/v1/accounts/example/sessions/1234/approve").unwrap(),
+			Route::RespondSession("example", 1234, Decision::Accept)
+		);
+		assert!(matches!(
+			route(&Method::Post, "/v1/accounts/example/sessions/abc/approve"),
+			Err(ApiError::BadRequest(_))
+		));
+		assert!(matches!(
+			route(&Method::Get, "/v1/accounts/example/confirmations/accept"),
+			Err(ApiError::NotFound)
+		));
+		assert!(matches!(
+			route(&Method::Get, "/accounts"),
+			Err(ApiError::NotFound)
+		));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_bind_unix_socket_is_private() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("steamguard.sock");
+		let command = ServeCommand {
+			listen: "127.0.0.1:0".parse().unwrap(),
+			unix_socket: Some(path.clone()),
+			regenerate_token: false,
+		};
+		let _server = command.bind().unwrap();
+		let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+		assert_eq!(mode & 0o777, 0o600);
+		// the private directory used for binding is cleaned up
+		assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+	}
+}
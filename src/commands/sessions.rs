@@ -0,0 +1,317 @@
+use std::sync::{Arc, Mutex};
+
+use log::*;
+use steamguard::{
+	protobufs::steammessages_auth_steamclient::{
+		CAuthentication_Token_Revoke_Request, EAuthTokenRevokeAction,
+	},
+	steamapi::{AuthenticationClient, EResult},
+	transport::TransportError,
+};
+
+use crate::{
+	output::{print_json, TokenOutput},
+	AccountManager,
+};
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Inspect and revoke the login sessions stored in your maFiles.")]
+pub struct SessionsCommand {
+	#[clap(subcommand)]
+	pub sub: SessionsSubcommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SessionsSubcommand {
+	#[clap(about = "Show the stored access and refresh tokens, and when they expire.")]
+	List,
+	#[clap(
+		about = "Revoke the stored refresh token on Steam's side, and remove the tokens from the maFile."
+	)]
+	Revoke,
+	#[clap(
+		about = "Revoke the stored sessions of every selected account. Use `steamguard --all sessions logout` to log out every account."
+	)]
+	Logout,
+}
+
+impl<T> AccountCommand<T> for SessionsCommand
+where
+	T: Transport + Clone,
+{
+	fn execute(
+		&self,
+		transport: T,
+		manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		match &self.sub {
+			SessionsSubcommand::List => list_sessions(&accounts, args),
+			SessionsSubcommand::Revoke => {
+				ensure!(
+					accounts.len() == 1,
+					"You can only revoke the session of one account at a time. Use `sessions logout` to log out multiple accounts."
+				);
+				logout_accounts(transport, manager, accounts, args)
+			}
+			SessionsSubcommand::Logout => logout_accounts(transport, manager, accounts, args),
+		}
+	}
+}
+
+fn list_sessions(
+	accounts: &[Arc<Mutex<SteamGuardAccount>>],
+	args: &GlobalArgs,
+) -> anyhow::Result<()> {
+	let now = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)?
+		.as_secs();
+
+	let mut results = vec![];
+	for account in accounts {
+		let account = account.lock().unwrap();
+		let Some(tokens) = account.tokens.as_ref() else {
+			if args.output == OutputFormat::Json {
+				results.push(serde_json::json!({
+					"account_name": account.account_name,
+					"access_token": null,
+					"refresh_token": null,
+				}));
+			} else {
+				println!("{}: not logged in", account.account_name);
+			}
+			continue;
+		};
+
+		let access_token = TokenOutput::new(tokens.access_token(), now);
+		let refresh_token = TokenOutput::new(tokens.refresh_token(), now);
+		if args.output == OutputFormat::Json {
+			let token_json = |token: anyhow::Result<TokenOutput>| match token {
+				Ok(token) => serde_json::json!(token),
+				Err(err) => serde_json::json!({ "error": err.to_string() }),
+			};
+			results.push(serde_json::json!({
+				"account_name": account.account_name,
+				"access_token": token_json(access_token),
+				"refresh_token": token_json(refresh_token),
+			}));
+			continue;
+		}
+
+		println!("{}:", account.account_name);
+		for (name, token) in [
+			("Access token", access_token),
+			("Refresh token", refresh_token),
+		] {
+			match token {
+				Ok(token) => {
+					println!("  {}: {}", name, token.jti);
+					println!("    Audience: {}", token.aud.join(", "));
+					println!("    Issued at: {}", token.iat);
+					println!(
+						"    Expires at: {} ({})",
+						token.exp,
+						token.expiry_description()
+					);
+				}
+				Err(err) => println!("  {}: could not decode: {}", name, err),
+			}
+		}
+	}
+
+	if args.output == OutputFormat::Json {
+		print_json(&serde_json::json!({ "accounts": results }))?;
+	}
+	Ok(())
+}
+
+/// Revoke the refresh token of each account, and forget its tokens.
+fn logout_accounts<T>(
+	transport: T,
+	manager: &mut AccountManager,
+	accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+	args: &GlobalArgs,
+) -> anyhow::Result<()>
+where
+	T: Transport + Clone,
+{
+	let mut results = vec![];
+	let mut failed = false;
+	for account in accounts {
+		let mut account = account.lock().unwrap();
+		if account.tokens.is_none() {
+			info!("{}: not logged in, nothing to revoke", account.account_name);
+			results.push(serde_json::json!({
+				"account_name": account.account_name,
+				"revoked": false,
+			}));
+			continue;
+		}
+
+		match revoke_refresh_token(transport.clone(), &mut account) {
+			Ok(_) => {
+				info!("{}: revoked refresh token", account.account_name);
+				account.tokens = None;
+				results.push(serde_json::json!({
+					"account_name": account.account_name,
+					"revoked": true,
+				}));
+			}
+			Err(err) => {
+				error!(
+					"{}: failed to revoke refresh token, keeping the tokens: {}",
+					account.account_name, err
+				);
+				failed = true;
+				results.push(serde_json::json!({
+					"account_name": account.account_name,
+					"error": err.to_string(),
+				}));
+			}
+		}
+	}
+
+	manager.save()?;
+
+	if args.output == OutputFormat::Json {
+		print_json(&serde_json::json!({ "accounts": results }))?;
+	}
+	ensure!(!failed, "Failed to revoke some sessions.");
+	Ok(())
+}
+
+/// Revoke the account's refresh token on Steam's side. If the access token has expired, it's refreshed first.
+///
+/// A refresh token that has already expired can't be used anymore, so there is nothing to revoke.
+fn revoke_refresh_token<T>(transport: T, account: &mut SteamGuardAccount) -> anyhow::Result<()>
+where
+	T: Transport + Clone,
+{
+	if let Some(tokens) = account.tokens.as_ref() {
+		let now = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)?
+			.as_secs();
+		if tokens
+			.refresh_token()
+			.decode()
+			.is_ok_and(|data| data.exp <= now)
+		{
+			info!(
+				"{}: refresh token has already expired, nothing to revoke",
+				account.account_name
+			);
+			return Ok(());
+		}
+	}
+
+	let mut client = AuthenticationClient::new(transport.clone());
+	let mut refreshed = false;
+	loop {
+		let Some(tokens) = account.tokens.as_ref() else {
+			bail!("{} has no tokens", account.account_name);
+		};
+		let mut req = CAuthentication_Token_Revoke_Request::new();
+		req.set_token(tokens.refresh_token().expose_secret().to_owned());
+		req.set_revoke_action(EAuthTokenRevokeAction::k_EAuthTokenRevokePermanent);
+
+		match client.revoke_access_token(req, tokens.access_token()) {
+			Ok(resp) if resp.result() == EResult::OK => return Ok(()),
+			Ok(resp) => bail!("Steam responded with {:?}", resp.result()),
+			Err(TransportError::Unauthorized) if !refreshed => {
+				debug!("access token expired, refreshing");
+				crate::refresh_tokens(transport.clone(), account)?;
+				refreshed = true;
+			}
+			Err(err) => return Err(err.into()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use base64::Engine;
+	use steamguard::{
+		protobufs::steammessages_auth_steamclient::{
+			CAuthentication_AccessToken_GenerateForApp_Response,
+			CAuthentication_Token_Revoke_Response,
+		},
+		token::Tokens,
+		transport::MockTransport,
+	};
+
+	fn jwt(exp: u64) -> String {
+		let payload = serde_json::json!({
+			"iss": "steam",
+			"sub": "76561199000000000",
+			"aud": ["mobile", "renew", "derive"],
+			"exp": exp,
+			"iat": 1700000000,
+			"jti": "18C5_22B3F431_CDF6A",
+		});
+		format!(
+			"eyAidHlwIjogIkpXVCIsICJhbGciOiAiRWREU0EiIH0.{}.c2ln",
+			base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(payload.to_string())
+		)
+	}
+
+	fn account(refresh_exp: u64) -> SteamGuardAccount {
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		account.steam_id = 76561199000000000;
+		account.tokens = Some(Tokens::new(jwt(1), jwt(refresh_exp)));
+		account
+	}
+
+	#[test]
+	fn test_revoke_refreshes_expired_access_token() {
+		let transport = MockTransport::new();
+		transport.respond_unauthorized("IAuthenticationService", "RevokeToken");
+		transport.respond_with(
+			"IAuthenticationService",
+			"RevokeToken",
+			CAuthentication_Token_Revoke_Response::new(),
+		);
+		let mut refreshed = CAuthentication_AccessToken_GenerateForApp_Response::new();
+		refreshed.set_access_token(jwt(u64::MAX >> 1));
+		transport.respond_with(
+			"IAuthenticationService",
+			"GenerateAccessTokenForApp",
+			refreshed,
+		);
+
+		let mut account = account(u64::MAX >> 1);
+		revoke_refresh_token(transport.clone(), &mut account).unwrap();
+
+		let requests = transport.requests();
+		let endpoints: Vec<String> = requests.iter().map(|req| req.endpoint()).collect();
+		assert_eq!(
+			endpoints,
+			vec![
+				"IAuthenticationService/RevokeToken",
+				"IAuthenticationService/GenerateAccessTokenForApp",
+				"IAuthenticationService/RevokeToken",
+			]
+		);
+		let req: CAuthentication_Token_Revoke_Request = requests[2].decode().unwrap();
+		assert_eq!(req.token(), jwt(u64::MAX >> 1));
+	}
+
+	#[test]
+	fn test_revoke_skips_expired_refresh_token() {
+		let transport = MockTransport::new();
+		let mut account = account(1);
+		revoke_refresh_token(transport.clone(), &mut account).unwrap();
+		assert!(transport.requests().is_empty());
+	}
+
+	#[test]
+	fn test_logout_uses_account_selection() {
+		assert!(SessionsCommand::try_parse_from(["sessions", "logout"]).is_ok());
+		// `--all` is a global option that selects accounts, so it goes before the subcommand
+		assert!(SessionsCommand::try_parse_from(["sessions", "logout", "--all"]).is_err());
+	}
+}
@@ -9,7 +9,7 @@ use steamguard::{
 	AccountLinkError, AccountLinker, FinalizeLinkError,
 };
 
-use crate::{tui, AccountManager};
+use crate::{errors::PromptRequiredError, tui, AccountManager};
 
 use super::*;
 
@@ -27,6 +27,9 @@ where
 		manager: &mut AccountManager,
 		args: &GlobalArgs,
 	) -> anyhow::Result<()> {
+		if args.non_interactive {
+			return Err(PromptRequiredError::Username.into());
+		}
 		eprintln!("Log in to the account that you want to link to steamguard-cli");
 		eprint!("Username: ");
 		let username = tui::prompt().to_lowercase();
@@ -38,7 +41,11 @@ where
 			);
 		}
 		info!("Logging in to {}", username);
-		let tokens = crate::do_login_raw(transport.clone(), username, args.password.clone())
+		let login = LoginOptions {
+			password: args.password.clone(),
+			..Default::default()
+		};
+		let tokens = crate::do_login_raw(transport.clone(), username, &login)
 			.expect("Failed to log in. Account has not been linked.");
 
 		info!("Adding authenticator...");
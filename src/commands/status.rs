@@ -4,10 +4,15 @@ use steamguard::{
 	protobufs::service_twofactor::{CTwoFactor_Status_Request, CTwoFactor_Status_Response},
 	steamapi::TwoFactorClient,
 	transport::{Transport, TransportError},
-	SteamGuardAccount,
+	SteamGuardAccount, TimeSync,
 };
 
-use super::AccountCommand;
+use crate::{
+	output::{print_json, StatusOutput},
+	LoginOptions,
+};
+
+use super::{AccountCommand, OutputFormat};
 
 #[derive(Debug, Clone, Parser)]
 #[clap(about = "Query and print the 2FA status of an account.")]
@@ -29,38 +34,64 @@ where
 	) -> anyhow::Result<()> {
 		let client = TwoFactorClient::new(transport.clone());
 
+		let mut results = vec![];
 		for account in accounts {
 			let mut account = account.lock().unwrap();
-			match self.print_account_status(&mut account, &transport, args, &client) {
-				Ok(_) => {}
+			let login = args.login_options(manager, &account.account_name);
+			match self.query_account_status(
+				&mut account,
+				&transport,
+				manager.time_sync(),
+				&login,
+				&client,
+			) {
+				Ok(data) => {
+					if args.output == OutputFormat::Json {
+						results.push(serde_json::json!({
+							"account_name": account.account_name,
+							"status": StatusOutput::from(&data),
+						}));
+					} else {
+						self.print_account_status(&account, data);
+					}
+				}
 				Err(e) => {
 					error!(
 						"Failed to print status for account {}: {}",
 						account.account_name, e
 					);
+					results.push(serde_json::json!({
+						"account_name": account.account_name,
+						"error": e.to_string(),
+					}));
 				}
 			}
 		}
 
 		manager.save()?;
 
+		if args.output == OutputFormat::Json {
+			print_json(&serde_json::json!({ "accounts": results }))?;
+		}
+
 		Ok(())
 	}
 }
 
 impl StatusCommand {
-	fn print_account_status<T>(
+	fn query_account_status<T>(
 		&self,
 		account: &mut SteamGuardAccount,
 		transport: &T,
-		args: &super::GlobalArgs,
+		time_sync: &TimeSync,
+		login: &LoginOptions,
 		client: &TwoFactorClient<T>,
-	) -> anyhow::Result<()>
+	) -> anyhow::Result<CTwoFactor_Status_Response>
 	where
 		T: Transport + Clone,
 	{
 		if account.tokens.is_none() {
-			crate::do_login(transport.clone(), account, args.password.clone())?;
+			crate::do_login(transport.clone(), account, login, time_sync)?;
 		}
 		let Some(tokens) = account.tokens.as_ref() else {
 			bail!(
@@ -74,7 +105,7 @@ impl StatusCommand {
 			Ok(resp) => resp,
 			Err(TransportError::Unauthorized) => {
 				info!("Access token expired, re-logging in...");
-				crate::do_login(transport.clone(), account, args.password.clone())?;
+				crate::do_login(transport.clone(), account, login, time_sync)?;
 				let tokens = account.tokens.as_ref().unwrap();
 				client.query_status(req, tokens.access_token())?
 			}
@@ -82,16 +113,16 @@ impl StatusCommand {
 				return Err(e.into());
 			}
 		};
-		let data = resp.into_response_data();
+		Ok(resp.into_response_data())
+	}
 
+	fn print_account_status(&self, account: &SteamGuardAccount, data: CTwoFactor_Status_Response) {
 		println!("Account: {}", account.account_name);
 		if self.raw {
 			println!("{:#?}", data);
-			return Ok(());
 		} else {
 			self.pretty_print_status(data);
 		}
-		Ok(())
 	}
 
 	fn pretty_print_status(&self, data: CTwoFactor_Status_Response) {
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use log::*;
+use serde::Serialize;
+
+use crate::{
+	accountmanager::{ManifestEntry, Tag},
+	output::print_json,
+	AccountManager,
+};
+
+use super::*;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Add tags or a note to the selected accounts. Tags can be used to select accounts with `--group` and `--tag`."
+)]
+pub struct TagCommand {
+	#[clap(
+		help = "Tags to add, written as `key=value` or `key`, eg. `group=storage region=eu`. Existing values are replaced."
+	)]
+	pub tags: Vec<Tag>,
+	#[clap(long, help = "Set a free-form note.")]
+	pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+#[clap(about = "Remove tags or the note from the selected accounts.")]
+pub struct UntagCommand {
+	#[clap(
+		help = "Tags to remove, written as `key`, or `key=value` to only remove the tag if it has that value."
+	)]
+	pub tags: Vec<Tag>,
+	#[clap(long, help = "Remove the note.")]
+	pub note: bool,
+}
+
+impl<T> ManifestCommand<T> for TagCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		if self.tags.is_empty() && self.note.is_none() {
+			bail!("Nothing to do, specify some tags or a note.");
+		}
+		update_entries(manager, args, |entry| {
+			for tag in self.tags.iter() {
+				entry
+					.tags
+					.insert(tag.key.clone(), tag.value.clone().unwrap_or_default());
+			}
+			if let Some(note) = &self.note {
+				entry.note = Some(note.clone());
+			}
+		})
+	}
+}
+
+impl<T> ManifestCommand<T> for UntagCommand
+where
+	T: Transport,
+{
+	fn execute(
+		&self,
+		_transport: T,
+		manager: &mut AccountManager,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		if self.tags.is_empty() && !self.note {
+			bail!("Nothing to do, specify some tags or --note.");
+		}
+		update_entries(manager, args, |entry| {
+			for tag in self.tags.iter() {
+				if tag.matches(entry) {
+					entry.tags.remove(&tag.key);
+				}
+			}
+			if self.note {
+				entry.note = None;
+			}
+		})
+	}
+}
+
+/// Apply `update` to the manifest entries of the selected accounts, and save the manifest.
+fn update_entries(
+	manager: &mut AccountManager,
+	args: &GlobalArgs,
+	update: impl Fn(&mut ManifestEntry),
+) -> anyhow::Result<()> {
+	let account_names = selected_account_names(args, manager)?;
+	let mut updated = vec![];
+	for account_name in account_names.iter() {
+		let entry = manager.get_entry_mut(account_name)?;
+		update(entry);
+		debug!(
+			"{}: tags {:?}, note {:?}",
+			account_name, entry.tags, entry.note
+		);
+		updated.push(TagOutput::from(&*entry));
+	}
+	manager.save()?;
+
+	if args.output == OutputFormat::Json {
+		return print_json(&serde_json::json!({ "accounts": updated }));
+	}
+	info!("Updated {} accounts", updated.len());
+	Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct TagOutput {
+	account_name: String,
+	tags: BTreeMap<String, String>,
+	note: Option<String>,
+}
+
+impl From<&ManifestEntry> for TagOutput {
+	fn from(entry: &ManifestEntry) -> Self {
+		Self {
+			account_name: entry.account_name.clone(),
+			tags: entry.tags.clone(),
+			note: entry.note.clone(),
+		}
+	}
+}
@@ -0,0 +1,275 @@
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use log::*;
+use serde::Serialize;
+use steamguard::token::Jwt;
+
+use crate::{
+	errors::HealthCheckError,
+	output::{format_duration, print_json},
+	rules::parse_duration,
+	AccountManager,
+};
+
+use super::*;
+
+/// Access tokens only live for a day, so they are only considered to be expiring soon right before they expire.
+const ACCESS_TOKEN_WARN_WITHIN: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Parser)]
+#[clap(
+	about = "Check and refresh the stored tokens. Exits with 1 if anything needs attention soon, and 2 if something is broken."
+)]
+pub struct TokensCommand {
+	#[clap(subcommand)]
+	pub sub: TokensSubcommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum TokensSubcommand {
+	#[clap(
+		about = "Report whether the access and refresh tokens of every selected account are valid, expiring soon or expired.",
+		long_about = "Report whether the access and refresh tokens of every selected account are valid, expiring soon or expired. This only looks at the expiry times stored in the tokens, and doesn't contact Steam, so revoked tokens are not detected.\n\nExits with 1 if a refresh token expires soon, and 2 if an account has no usable refresh token. Expired access tokens are reported, but don't affect the exit code, because `tokens refresh` can renew them."
+	)]
+	Check {
+		#[clap(
+			long,
+			value_name = "DURATION",
+			default_value = "7d",
+			value_parser = parse_duration,
+			help = "Report refresh tokens that expire within this long as expiring soon, eg. `7d` or `12h`."
+		)]
+		warn_within: Duration,
+	},
+	#[clap(
+		about = "Get new access tokens for every selected account using their refresh tokens. Never prompts to log in.",
+		long_about = "Get new access tokens for every selected account using their refresh tokens. Never prompts to log in.\n\nExits with 2 if any account could not be refreshed, for example because its refresh token has expired. Use `login` to log in to those accounts again."
+	)]
+	Refresh,
+}
+
+impl<T> AccountCommand<T> for TokensCommand
+where
+	T: Transport + Clone,
+{
+	fn execute(
+		&self,
+		transport: T,
+		manager: &mut AccountManager,
+		accounts: Vec<Arc<Mutex<SteamGuardAccount>>>,
+		args: &GlobalArgs,
+	) -> anyhow::Result<()> {
+		match &self.sub {
+			TokensSubcommand::Check { warn_within } => check_tokens(&accounts, *warn_within, args),
+			TokensSubcommand::Refresh => refresh_tokens(transport, manager, &accounts, args),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenHealth {
+	Valid,
+	ExpiringSoon,
+	Expired,
+	/// The token is missing, or can't be decoded.
+	Invalid,
+}
+
+impl std::fmt::Display for TokenHealth {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Valid => "valid",
+			Self::ExpiringSoon => "expiring soon",
+			Self::Expired => "expired",
+			Self::Invalid => "invalid",
+		})
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct TokenCheck {
+	status: TokenHealth,
+	/// Seconds until the token expires. Negative if it already has.
+	expires_in: Option<i64>,
+}
+
+impl TokenCheck {
+	fn new(token: Option<&Jwt>, now: u64, warn_within: Duration) -> Self {
+		let Some(Ok(data)) = token.map(|t| t.decode()) else {
+			return Self {
+				status: TokenHealth::Invalid,
+				expires_in: None,
+			};
+		};
+		let expires_in = data.exp as i64 - now as i64;
+		let status = if expires_in <= 0 {
+			TokenHealth::Expired
+		} else if (expires_in as u64) < warn_within.as_secs() {
+			TokenHealth::ExpiringSoon
+		} else {
+			TokenHealth::Valid
+		};
+		Self {
+			status,
+			expires_in: Some(expires_in),
+		}
+	}
+
+	fn describe(&self) -> String {
+		match self.expires_in {
+			Some(secs) if secs > 0 => format!(
+				"{}, expires in {}",
+				self.status,
+				format_duration(secs.unsigned_abs())
+			),
+			Some(secs) => format!("expired {} ago", format_duration(secs.unsigned_abs())),
+			None => self.status.to_string(),
+		}
+	}
+}
+
+fn check_tokens(
+	accounts: &[Arc<Mutex<SteamGuardAccount>>],
+	warn_within: Duration,
+	args: &GlobalArgs,
+) -> anyhow::Result<()> {
+	let now = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)?
+		.as_secs();
+
+	let mut results = vec![];
+	let mut worst = TokenHealth::Valid;
+	for account in accounts {
+		let account = account.lock().unwrap();
+		let tokens = account.tokens.as_ref();
+		let access_token = TokenCheck::new(
+			tokens.map(|t| t.access_token()),
+			now,
+			ACCESS_TOKEN_WARN_WITHIN,
+		);
+		let refresh_token = TokenCheck::new(tokens.map(|t| t.refresh_token()), now, warn_within);
+		worst = worst.max(refresh_token.status);
+
+		if args.output == OutputFormat::Json {
+			results.push(serde_json::json!({
+				"account_name": account.account_name,
+				"access_token": access_token,
+				"refresh_token": refresh_token,
+			}));
+		} else {
+			println!("{}:", account.account_name);
+			println!("  Access token: {}", access_token.describe());
+			println!("  Refresh token: {}", refresh_token.describe());
+		}
+	}
+
+	if args.output == OutputFormat::Json {
+		print_json(&serde_json::json!({ "accounts": results }))?;
+	}
+
+	match worst {
+		TokenHealth::Valid => Ok(()),
+		TokenHealth::ExpiringSoon => Err(HealthCheckError::Warning(
+			"Some refresh tokens expire soon. Log in to those accounts again with `login`.".into(),
+		)
+		.into()),
+		TokenHealth::Expired | TokenHealth::Invalid => Err(HealthCheckError::Critical(
+			"Some accounts don't have a usable refresh token. Log in to those accounts again with `login`.".into(),
+		)
+		.into()),
+	}
+}
+
+fn refresh_tokens<T>(
+	transport: T,
+	manager: &mut AccountManager,
+	accounts: &[Arc<Mutex<SteamGuardAccount>>],
+	args: &GlobalArgs,
+) -> anyhow::Result<()>
+where
+	T: Transport + Clone,
+{
+	let mut results = vec![];
+	let mut failed = 0;
+	for account in accounts {
+		let mut account = account.lock().unwrap();
+		match crate::refresh_tokens(transport.clone(), &mut account) {
+			Ok(_) => {
+				info!("{}: Refreshed access token", account.account_name);
+				results.push(serde_json::json!({
+					"account_name": account.account_name,
+					"refreshed": true,
+				}));
+			}
+			Err(err) => {
+				error!(
+					"{}: Failed to refresh access token: {}",
+					account.account_name, err
+				);
+				failed += 1;
+				results.push(serde_json::json!({
+					"account_name": account.account_name,
+					"refreshed": false,
+					"error": err.to_string(),
+				}));
+			}
+		}
+	}
+
+	manager.save()?;
+
+	if args.output == OutputFormat::Json {
+		print_json(&serde_json::json!({ "accounts": results }))?;
+	}
+	if failed > 0 {
+		return Err(HealthCheckError::Critical(format!(
+			"Failed to refresh the access tokens of {} accounts. Log in to them again with `login`.",
+			failed
+		))
+		.into());
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// exp 1705011955
+	const SAMPLE: &str = "eyAidHlwIjogIkpXVCIsICJhbGciOiAiRWREU0EiIH0.eyAiaXNzIjogInN0ZWFtIiwgInN1YiI6ICI3NjU2MTE5OTE1NTcwNjg5MiIsICJhdWQiOiBbICJ3ZWIiLCAicmVuZXciLCAiZGVyaXZlIiBdLCAiZXhwIjogMTcwNTAxMTk1NSwgIm5iZiI6IDE2Nzg0NjQ4MzcsICJpYXQiOiAxNjg3MTA0ODM3LCAianRpIjogIjE4QzVfMjJCM0Y0MzFfQ0RGNkEiLCAib2F0IjogMTY4NzEwNDgzNywgInBlciI6IDEsICJpcF9zdWJqZWN0IjogIjY5LjEyMC4xMzYuMTI0IiwgImlwX2NvbmZpcm1lciI6ICI2OS4xMjAuMTM2LjEyNCIgfQ.7p5TPj9pGQbxIzWDDNCSP9OkKYSeDnWBE8E-M8hUrxOEPCW0XwrbDUrh199RzjPDw";
+	const EXP: u64 = 1705011955;
+
+	#[test]
+	fn test_token_check() {
+		let jwt: Jwt = SAMPLE.to_owned().into();
+		let week = Duration::from_secs(7 * 86400);
+
+		let check = TokenCheck::new(Some(&jwt), EXP - 8 * 86400, week);
+		assert_eq!(check.status, TokenHealth::Valid);
+		assert_eq!(check.describe(), "valid, expires in 8d");
+
+		let check = TokenCheck::new(Some(&jwt), EXP - 3600, week);
+		assert_eq!(check.status, TokenHealth::ExpiringSoon);
+
+		let check = TokenCheck::new(Some(&jwt), EXP, week);
+		assert_eq!(check.status, TokenHealth::Expired);
+		assert_eq!(check.describe(), "expired 0s ago");
+
+		let check = TokenCheck::new(None, EXP, week);
+		assert_eq!(check.status, TokenHealth::Invalid);
+		let garbage: Jwt = "not a jwt".to_owned().into();
+		let check = TokenCheck::new(Some(&garbage), EXP, week);
+		assert_eq!(check.status, TokenHealth::Invalid);
+	}
+
+	#[test]
+	fn test_health_order() {
+		assert!(TokenHealth::Valid < TokenHealth::ExpiringSoon);
+		assert!(TokenHealth::ExpiringSoon < TokenHealth::Expired);
+		assert!(TokenHealth::Expired < TokenHealth::Invalid);
+	}
+}
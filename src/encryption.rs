@@ -5,13 +5,21 @@ use rand::RngExt;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+mod age_recipients;
 mod argon2id_aes;
+mod argon2id_xchacha;
+mod envelope;
 #[cfg(feature = "keyring")]
 mod keyring;
 mod legacy;
+mod provider;
 
+pub use age_recipients::*;
 pub use argon2id_aes::*;
+pub use argon2id_xchacha::*;
+pub use envelope::*;
 pub use legacy::*;
+pub use provider::*;
 
 #[cfg(feature = "keyring")]
 pub use crate::encryption::keyring::*;
@@ -21,10 +29,45 @@ pub use crate::encryption::keyring::*;
 pub enum EncryptionScheme {
 	Argon2idAes256(Argon2idAes256),
 	LegacySdaCompatible(LegacySdaCompatible),
+	Argon2idXChaCha20Poly1305(Argon2idXChaCha20Poly1305),
+	AgeRecipients(AgeRecipients),
+}
+
+impl EncryptionScheme {
+	/// Generate params for the current default scheme.
+	pub fn generate() -> Self {
+		Self::Argon2idXChaCha20Poly1305(Argon2idXChaCha20Poly1305::generate())
+	}
+
+	/// Generate params for the current default scheme, with the given Argon2 parameters.
+	pub fn generate_with_params(params: Argon2Params) -> Self {
+		Self::Argon2idXChaCha20Poly1305(Argon2idXChaCha20Poly1305::generate_with_params(params))
+	}
+
+	/// The Argon2 parameters used to derive the key, if this scheme uses Argon2.
+	pub fn argon2_params(&self) -> Option<Argon2Params> {
+		match self {
+			Self::Argon2idAes256(scheme) => Some(scheme.params),
+			Self::Argon2idXChaCha20Poly1305(scheme) => Some(scheme.params),
+			Self::LegacySdaCompatible(_) | Self::AgeRecipients(_) => None,
+		}
+	}
+
+	/// Whether this scheme detects tampering and incorrect passkeys. Entries using schemes that don't can be upgraded with `steamguard rekey`.
+	pub fn is_authenticated(&self) -> bool {
+		matches!(
+			self,
+			Self::Argon2idXChaCha20Poly1305(_) | Self::AgeRecipients(_)
+		)
+	}
+
+	/// Whether this scheme encrypts to public keys, so encrypting doesn't need the passkey.
+	pub fn is_public_key(&self) -> bool {
+		matches!(self, Self::AgeRecipients(_))
+	}
 }
 
 pub trait EntryEncryptor {
-	fn generate() -> Self;
 	fn encrypt(
 		&self,
 		passkey: &str,
@@ -35,13 +78,31 @@ pub trait EntryEncryptor {
 		passkey: &str,
 		ciphertext: Vec<u8>,
 	) -> anyhow::Result<Vec<u8>, EntryEncryptionError>;
-}
 
-impl EntryEncryptor for EncryptionScheme {
-	fn generate() -> Self {
-		EncryptionScheme::Argon2idAes256(Argon2idAes256::generate())
+	/// Like `encrypt`, but also authenticates `associated_data`. Schemes without authentication ignore it.
+	fn encrypt_with_ad(
+		&self,
+		passkey: &str,
+		plaintext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		let _ = associated_data;
+		self.encrypt(passkey, plaintext)
 	}
 
+	/// Like `decrypt`, but fails if `associated_data` is not what the ciphertext was encrypted with. Schemes without authentication ignore it.
+	fn decrypt_with_ad(
+		&self,
+		passkey: &str,
+		ciphertext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		let _ = associated_data;
+		self.decrypt(passkey, ciphertext)
+	}
+}
+
+impl EntryEncryptor for EncryptionScheme {
 	fn encrypt(
 		&self,
 		passkey: &str,
@@ -50,6 +111,10 @@ impl EntryEncryptor for EncryptionScheme {
 		match self {
 			EncryptionScheme::Argon2idAes256(scheme) => scheme.encrypt(passkey, plaintext),
 			EncryptionScheme::LegacySdaCompatible(scheme) => scheme.encrypt(passkey, plaintext),
+			EncryptionScheme::Argon2idXChaCha20Poly1305(scheme) => {
+				scheme.encrypt(passkey, plaintext)
+			}
+			EncryptionScheme::AgeRecipients(scheme) => scheme.encrypt(passkey, plaintext),
 		}
 	}
 
@@ -61,6 +126,44 @@ impl EntryEncryptor for EncryptionScheme {
 		match self {
 			EncryptionScheme::Argon2idAes256(scheme) => scheme.decrypt(passkey, ciphertext),
 			EncryptionScheme::LegacySdaCompatible(scheme) => scheme.decrypt(passkey, ciphertext),
+			EncryptionScheme::Argon2idXChaCha20Poly1305(scheme) => {
+				scheme.decrypt(passkey, ciphertext)
+			}
+			EncryptionScheme::AgeRecipients(scheme) => scheme.decrypt(passkey, ciphertext),
+		}
+	}
+
+	fn encrypt_with_ad(
+		&self,
+		passkey: &str,
+		plaintext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		match self {
+			EncryptionScheme::Argon2idXChaCha20Poly1305(scheme) => {
+				scheme.encrypt_with_ad(passkey, plaintext, associated_data)
+			}
+			EncryptionScheme::AgeRecipients(scheme) => {
+				scheme.encrypt_with_ad(passkey, plaintext, associated_data)
+			}
+			_ => self.encrypt(passkey, plaintext),
+		}
+	}
+
+	fn decrypt_with_ad(
+		&self,
+		passkey: &str,
+		ciphertext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		match self {
+			EncryptionScheme::Argon2idXChaCha20Poly1305(scheme) => {
+				scheme.decrypt_with_ad(passkey, ciphertext, associated_data)
+			}
+			EncryptionScheme::AgeRecipients(scheme) => {
+				scheme.decrypt_with_ad(passkey, ciphertext, associated_data)
+			}
+			_ => self.decrypt(passkey, ciphertext),
 		}
 	}
 }
@@ -69,6 +172,10 @@ impl EntryEncryptor for EncryptionScheme {
 pub enum EntryEncryptionError {
 	#[error("Invalid ciphertext length. The ciphertext must be a multiple of 16 bytes.")]
 	InvalidCipherTextLength,
+	#[error("Incorrect passkey.")]
+	IncorrectPasskey,
+	#[error("The encrypted data is corrupted, or has been tampered with.")]
+	Corrupted,
 	#[error(transparent)]
 	Unknown(#[from] anyhow::Error),
 }
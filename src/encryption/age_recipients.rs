@@ -0,0 +1,189 @@
+use std::io::{Read, Write};
+
+use ::age::{
+	armor::{ArmoredReader, ArmoredWriter, Format},
+	x25519, DecryptError, Decryptor, Encryptor, IdentityFile,
+};
+use log::*;
+use zeroize::Zeroize;
+
+use super::*;
+
+/// Encrypts maFiles to age X25519 recipients, so each person can decrypt them with their own identity, instead of sharing a passkey.
+///
+/// Decrypting needs an age identity file, which is given in place of the passkey. Encrypting only needs the recipients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgeRecipients {
+	/// Public keys, eg. `age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p`
+	pub recipients: Vec<String>,
+}
+
+impl AgeRecipients {
+	pub fn new(recipients: Vec<String>) -> anyhow::Result<Self> {
+		let scheme = Self { recipients };
+		scheme.parse_recipients()?;
+		Ok(scheme)
+	}
+
+	fn parse_recipients(&self) -> anyhow::Result<Vec<x25519::Recipient>> {
+		ensure!(!self.recipients.is_empty(), "No age recipients given.");
+		self.recipients.iter().map(|r| parse_recipient(r)).collect()
+	}
+}
+
+/// Parse an age X25519 public key.
+pub fn parse_recipient(recipient: &str) -> anyhow::Result<x25519::Recipient> {
+	recipient
+		.parse()
+		.map_err(|err| anyhow!("Invalid age recipient {}: {}", recipient, err))
+}
+
+impl EntryEncryptor for AgeRecipients {
+	fn encrypt(
+		&self,
+		passkey: &str,
+		plaintext: Vec<u8>,
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		self.encrypt_with_ad(passkey, plaintext, &[])
+	}
+
+	fn decrypt(
+		&self,
+		passkey: &str,
+		ciphertext: Vec<u8>,
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		self.decrypt_with_ad(passkey, ciphertext, &[])
+	}
+
+	/// age has no associated data, so it is prepended to the plaintext, along with its length, and checked when decrypting.
+	fn encrypt_with_ad(
+		&self,
+		_passkey: &str,
+		plaintext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		let recipients = self.parse_recipients()?;
+		let encryptor =
+			Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn ::age::Recipient))
+				.map_err(anyhow::Error::from)?;
+		let ad_len = u32::try_from(associated_data.len()).map_err(anyhow::Error::from)?;
+		let mut ciphertext = vec![];
+		let armor = ArmoredWriter::wrap_output(&mut ciphertext, Format::AsciiArmor)?;
+		let mut writer = encryptor.wrap_output(armor)?;
+		writer.write_all(&ad_len.to_be_bytes())?;
+		writer.write_all(associated_data)?;
+		writer.write_all(&plaintext)?;
+		writer.finish()?.finish()?;
+		Ok(ciphertext)
+	}
+
+	fn decrypt_with_ad(
+		&self,
+		passkey: &str,
+		ciphertext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		let identities = IdentityFile::from_buffer(passkey.as_bytes())
+			.ok()
+			.and_then(|file| file.into_identities().ok())
+			.filter(|identities| !identities.is_empty())
+			.ok_or(EntryEncryptionError::IncorrectPasskey)?;
+		debug!("decrypting with {} age identities", identities.len());
+
+		let decryptor = Decryptor::new_buffered(ArmoredReader::new(ciphertext.as_slice()))
+			.map_err(|_| EntryEncryptionError::Corrupted)?;
+		let mut reader = decryptor
+			.decrypt(
+				identities
+					.iter()
+					.map(|i| i.as_ref() as &dyn ::age::Identity),
+			)
+			.map_err(|err| match err {
+				DecryptError::NoMatchingKeys => EntryEncryptionError::IncorrectPasskey,
+				_ => EntryEncryptionError::Corrupted,
+			})?;
+		let mut payload = vec![];
+		reader
+			.read_to_end(&mut payload)
+			.map_err(|_| EntryEncryptionError::Corrupted)?;
+
+		let (ad_len, rest) = payload
+			.split_first_chunk::<4>()
+			.ok_or(EntryEncryptionError::Corrupted)?;
+		let ad_len = u32::from_be_bytes(*ad_len) as usize;
+		if rest.len() < ad_len || &rest[..ad_len] != associated_data {
+			payload.zeroize();
+			return Err(EntryEncryptionError::Corrupted);
+		}
+		let plaintext = rest[ad_len..].to_vec();
+		payload.zeroize();
+		Ok(plaintext)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::age::secrecy::ExposeSecret;
+
+	#[test]
+	fn test_encrypt_to_multiple_recipients() -> anyhow::Result<()> {
+		let alice = x25519::Identity::generate();
+		let bob = x25519::Identity::generate();
+		let eve = x25519::Identity::generate();
+		let scheme = AgeRecipients::new(vec![
+			alice.to_public().to_string(),
+			bob.to_public().to_string(),
+		])?;
+
+		let encrypted = scheme.encrypt("", b"secret".to_vec())?;
+		assert!(encrypted.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----"));
+		for identity in [&alice, &bob] {
+			let identity_file = format!(
+				"# created: 2024-01-01\n{}\n",
+				identity.to_string().expose_secret()
+			);
+			assert_eq!(
+				scheme.decrypt(&identity_file, encrypted.clone())?,
+				b"secret"
+			);
+		}
+		assert!(matches!(
+			scheme.decrypt(eve.to_string().expose_secret(), encrypted.clone()),
+			Err(EntryEncryptionError::IncorrectPasskey)
+		));
+		assert!(matches!(
+			scheme.decrypt("not an identity", encrypted),
+			Err(EntryEncryptionError::IncorrectPasskey)
+		));
+		Ok(())
+	}
+
+	#[test]
+	fn test_associated_data_must_match() -> anyhow::Result<()> {
+		let identity = x25519::Identity::generate();
+		let scheme = AgeRecipients::new(vec![identity.to_public().to_string()])?;
+		let identity_file = identity.to_string().expose_secret().to_owned();
+
+		let encrypted = scheme.encrypt_with_ad("", b"secret".to_vec(), b"account1")?;
+		assert_eq!(
+			scheme.decrypt_with_ad(&identity_file, encrypted.clone(), b"account1")?,
+			b"secret"
+		);
+		assert!(matches!(
+			scheme.decrypt_with_ad(&identity_file, encrypted.clone(), b"account2"),
+			Err(EntryEncryptionError::Corrupted)
+		));
+		assert!(matches!(
+			scheme.decrypt(&identity_file, encrypted),
+			Err(EntryEncryptionError::Corrupted)
+		));
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_recipients() {
+		assert!(AgeRecipients::new(vec![]).is_err());
+		assert!(AgeRecipients::new(vec!["age1foo".into()]).is_err());
+	}
+}
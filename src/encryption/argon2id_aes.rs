@@ -6,6 +6,7 @@ use argon2::Argon2;
 use base64::Engine;
 use log::*;
 use rand::TryRng;
+use std::time::{Duration, Instant};
 
 use super::*;
 
@@ -13,48 +14,122 @@ use super::*;
 pub struct Argon2idAes256 {
 	pub iv: String,
 	pub salt: String,
+	/// Entries written before these were configurable don't have them, and use the defaults.
+	#[serde(flatten)]
+	pub params: Argon2Params,
 }
 
-impl Argon2idAes256 {
-	const KEY_SIZE_BYTES: usize = 32;
-	const IV_LENGTH: usize = 16;
-	const SALT_LENGTH: usize = 16;
+/// Argon2 cost parameters, using the same names as the Argon2 spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Argon2Params {
+	/// Memory size, in KiB.
+	pub m_cost: u32,
+	/// Number of iterations.
+	pub t_cost: u32,
+	/// Degree of parallelism.
+	pub p_cost: u32,
+}
 
-	fn get_encryption_key(passkey: &str, salt: &str) -> anyhow::Result<[u8; Self::KEY_SIZE_BYTES]> {
-		let password_bytes = passkey.as_bytes();
-		let salt_bytes = base64::engine::general_purpose::STANDARD.decode(salt)?;
-		let mut full_key: [u8; Self::KEY_SIZE_BYTES] = [0u8; Self::KEY_SIZE_BYTES];
+impl Argon2Params {
+	/// For machines with little memory, or when lots of entries need to be decrypted quickly.
+	pub const LOW: Self = Self {
+		m_cost: 4 * 1024,
+		t_cost: 2,
+		p_cost: 1,
+	};
+	pub const DEFAULT: Self = Self {
+		m_cost: 12 * 1024,
+		t_cost: 3,
+		p_cost: 12,
+	};
+	pub const HIGH: Self = Self {
+		m_cost: 64 * 1024,
+		t_cost: 4,
+		p_cost: 4,
+	};
+
+	/// Derive a 256 bit key from `passkey`.
+	pub(crate) fn derive_key(&self, passkey: &str, salt: &[u8]) -> anyhow::Result<[u8; 32]> {
+		let mut key = [0u8; 32];
 		let deriver = Argon2::new(
 			argon2::Algorithm::Argon2id,
 			argon2::Version::V0x13,
-			Self::config(),
+			self.to_argon2()?,
 		);
-		deriver.hash_password_into(password_bytes, &salt_bytes, &mut full_key)?;
-
-		Ok(full_key)
+		deriver.hash_password_into(passkey.as_bytes(), salt, &mut key)?;
+		Ok(key)
 	}
 
-	fn config() -> argon2::Params {
+	/// Validates the parameters, and converts them into the form the `argon2` crate wants.
+	pub fn to_argon2(self) -> anyhow::Result<argon2::Params> {
 		argon2::Params::new(
-			12 * 1024, // 12MB
-			3,
-			12,
-			Some(Self::KEY_SIZE_BYTES),
+			self.m_cost,
+			self.t_cost,
+			self.p_cost,
+			Some(Argon2idAes256::KEY_SIZE_BYTES),
 		)
-		.expect("Unable to create Argon2 config.")
+		.map_err(|err| anyhow!("Invalid Argon2 parameters {}: {}", self, err))
 	}
 
-	fn decode_iv(&self) -> anyhow::Result<[u8; Self::IV_LENGTH]> {
-		let mut iv = [0u8; Self::IV_LENGTH];
-		base64::engine::general_purpose::STANDARD
-			.decode_slice_unchecked(&self.iv, &mut iv)
-			.context("decoding iv")?;
-		Ok(iv)
+	/// Find parameters that take about `target` to derive a key on this machine, returning them along with how long they actually took.
+	///
+	/// Keeps the default parallelism, and prefers more iterations over less memory. Memory is only reduced when a single iteration with the default memory is already too slow.
+	pub fn benchmark(target: Duration) -> anyhow::Result<(Self, Duration)> {
+		let mut params = Self {
+			t_cost: 1,
+			..Self::DEFAULT
+		};
+		let single = params.time_key_derivation()?;
+		debug!("1 iteration with {} took {:?}", params, single);
+		let ratio = target.as_secs_f64() / single.as_secs_f64().max(f64::EPSILON);
+		if ratio >= 1.0 {
+			params.t_cost = (ratio.floor() as u32).max(1);
+		} else {
+			let min_memory = 8 * params.p_cost;
+			params.m_cost = ((params.m_cost as f64 * ratio) as u32).max(min_memory);
+		}
+		let elapsed = params.time_key_derivation()?;
+		Ok((params, elapsed))
+	}
+
+	/// How long deriving a key with these parameters takes on this machine.
+	pub fn time_key_derivation(&self) -> anyhow::Result<Duration> {
+		let salt =
+			base64::engine::general_purpose::STANDARD.encode([0u8; Argon2idAes256::SALT_LENGTH]);
+		let start = Instant::now();
+		Argon2idAes256::get_encryption_key("benchmark", &salt, self)?;
+		Ok(start.elapsed())
 	}
 }
 
-impl EntryEncryptor for Argon2idAes256 {
-	fn generate() -> Self {
+impl Default for Argon2Params {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+impl std::fmt::Display for Argon2Params {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"m={} KiB, t={}, p={}",
+			self.m_cost, self.t_cost, self.p_cost
+		)
+	}
+}
+
+impl Argon2idAes256 {
+	const KEY_SIZE_BYTES: usize = 32;
+	const IV_LENGTH: usize = 16;
+	const SALT_LENGTH: usize = 16;
+
+	pub fn generate() -> Self {
+		Self::generate_with_params(Argon2Params::default())
+	}
+
+	/// Generate a new salt and IV, using the given Argon2 parameters.
+	pub fn generate_with_params(params: Argon2Params) -> Self {
 		let mut rng = rand::rngs::SysRng;
 		let mut salt = [0u8; Self::SALT_LENGTH];
 		let mut iv = [0u8; Self::IV_LENGTH];
@@ -65,16 +140,36 @@ impl EntryEncryptor for Argon2idAes256 {
 		Argon2idAes256 {
 			iv: base64::engine::general_purpose::STANDARD.encode(iv),
 			salt: base64::engine::general_purpose::STANDARD.encode(salt),
+			params,
 		}
 	}
 
+	fn get_encryption_key(
+		passkey: &str,
+		salt: &str,
+		params: &Argon2Params,
+	) -> anyhow::Result<[u8; Self::KEY_SIZE_BYTES]> {
+		let salt_bytes = base64::engine::general_purpose::STANDARD.decode(salt)?;
+		params.derive_key(passkey, &salt_bytes)
+	}
+
+	fn decode_iv(&self) -> anyhow::Result<[u8; Self::IV_LENGTH]> {
+		let mut iv = [0u8; Self::IV_LENGTH];
+		base64::engine::general_purpose::STANDARD
+			.decode_slice_unchecked(&self.iv, &mut iv)
+			.context("decoding iv")?;
+		Ok(iv)
+	}
+}
+
+impl EntryEncryptor for Argon2idAes256 {
 	fn encrypt(
 		&self,
 		passkey: &str,
 		plaintext: Vec<u8>,
 	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
 		let start = std::time::Instant::now();
-		let key = Self::get_encryption_key(passkey, &self.salt)?;
+		let key = Self::get_encryption_key(passkey, &self.salt, &self.params)?;
 		debug!("key derivation took: {:?}", start.elapsed());
 
 		let start = std::time::Instant::now();
@@ -93,7 +188,7 @@ impl EntryEncryptor for Argon2idAes256 {
 		ciphertext: Vec<u8>,
 	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
 		let start = std::time::Instant::now();
-		let key = Self::get_encryption_key(passkey, &self.salt)?;
+		let key = Self::get_encryption_key(passkey, &self.salt, &self.params)?;
 		debug!("key derivation took: {:?}", start.elapsed());
 
 		let start = std::time::Instant::now();
@@ -118,9 +213,13 @@ mod tests {
 	fn test_encryption_key() {
 		assert_eq!(
 			base64::engine::general_purpose::STANDARD.encode(
-				Argon2idAes256::get_encryption_key("password", "GMhL0N2hqXg=")
-					.unwrap()
-					.as_slice()
+				Argon2idAes256::get_encryption_key(
+					"password",
+					"GMhL0N2hqXg=",
+					&Argon2Params::default()
+				)
+				.unwrap()
+				.as_slice()
 			),
 			"DTm3hc95aKyAGmyVMZdLUPfcPjcXN1i1zYObYJg2GzY="
 		);
@@ -130,9 +229,13 @@ mod tests {
 	fn test_encryption_key2() {
 		assert_eq!(
 			base64::engine::general_purpose::STANDARD.encode(
-				Argon2idAes256::get_encryption_key("password", "wTzTE9A6aN8=")
-					.unwrap()
-					.as_slice()
+				Argon2idAes256::get_encryption_key(
+					"password",
+					"wTzTE9A6aN8=",
+					&Argon2Params::default()
+				)
+				.unwrap()
+				.as_slice()
 			),
 			"zwMjXhwggpJWCvkouG/xrSPZRWn2cUUyph3PAViRONA="
 		);
@@ -158,4 +261,45 @@ mod tests {
 		}
 		Ok(())
 	}
+
+	#[test]
+	fn test_params_default_for_old_entries() -> anyhow::Result<()> {
+		let scheme: EncryptionScheme = serde_json::from_str(
+			r#"{"scheme":"Argon2idAes256","iv":"ZpAM8ZK0n3JG2Mjr4OLqHg==","salt":"GMhL0N2hqXg="}"#,
+		)?;
+		let EncryptionScheme::Argon2idAes256(scheme) = scheme else {
+			panic!("wrong scheme");
+		};
+		assert_eq!(scheme.params, Argon2Params::DEFAULT);
+		Ok(())
+	}
+
+	#[test]
+	fn test_custom_params() -> anyhow::Result<()> {
+		let params = Argon2Params {
+			m_cost: 1024,
+			t_cost: 1,
+			p_cost: 2,
+		};
+		let scheme = EncryptionScheme::Argon2idAes256(Argon2idAes256::generate_with_params(params));
+		let json = serde_json::to_string(&scheme)?;
+		assert!(json.contains(r#""m_cost":1024,"t_cost":1,"p_cost":2"#));
+		let scheme: EncryptionScheme = serde_json::from_str(&json)?;
+		let encrypted = scheme.encrypt("password", b"secret".to_vec())?;
+		assert_eq!(scheme.decrypt("password", encrypted)?, b"secret");
+		assert_ne!(
+			Argon2idAes256::get_encryption_key("password", "GMhL0N2hqXg=", &params)?,
+			Argon2idAes256::get_encryption_key("password", "GMhL0N2hqXg=", &Argon2Params::DEFAULT)?
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_params() {
+		let params = Argon2Params {
+			m_cost: 1,
+			..Argon2Params::DEFAULT
+		};
+		assert!(params.to_argon2().is_err());
+	}
 }
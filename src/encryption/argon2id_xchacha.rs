@@ -0,0 +1,229 @@
+use base64::Engine;
+use chacha20poly1305::{
+	aead::{Aead, KeyInit, Payload},
+	XChaCha20Poly1305, XNonce,
+};
+use log::*;
+use rand::TryRng;
+use sha2::{Digest, Sha256};
+
+use super::*;
+
+/// Authenticated encryption with XChaCha20-Poly1305, using a key derived from the passkey with Argon2id.
+///
+/// The encrypted file contains the salt, a key check value, a random nonce, and the ciphertext, base64 encoded. The salt and key check value are used to tell a wrong passkey apart from a corrupted file, or a file that belongs to a different entry. A new nonce is generated every time the file is written, so the salt can stay in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Argon2idXChaCha20Poly1305 {
+	pub salt: String,
+	#[serde(flatten)]
+	pub params: Argon2Params,
+}
+
+impl Argon2idXChaCha20Poly1305 {
+	const SALT_LENGTH: usize = 16;
+	const NONCE_LENGTH: usize = 24;
+	const KEY_CHECK_LENGTH: usize = 8;
+
+	pub fn generate() -> Self {
+		Self::generate_with_params(Argon2Params::default())
+	}
+
+	pub fn generate_with_params(params: Argon2Params) -> Self {
+		let mut salt = [0u8; Self::SALT_LENGTH];
+		rand::rngs::SysRng
+			.try_fill_bytes(&mut salt)
+			.expect("failed to generate random salt");
+		Self {
+			salt: base64::engine::general_purpose::STANDARD.encode(salt),
+			params,
+		}
+	}
+
+	fn decode_salt(&self) -> anyhow::Result<Vec<u8>> {
+		let salt = base64::engine::general_purpose::STANDARD.decode(&self.salt)?;
+		ensure!(salt.len() == Self::SALT_LENGTH, "invalid salt length");
+		Ok(salt)
+	}
+
+	fn key_check(key: &[u8; 32]) -> [u8; Self::KEY_CHECK_LENGTH] {
+		let digest = Sha256::new()
+			.chain_update(b"steamguard-cli key check\0")
+			.chain_update(key)
+			.finalize();
+		let mut check = [0u8; Self::KEY_CHECK_LENGTH];
+		check.copy_from_slice(&digest[..Self::KEY_CHECK_LENGTH]);
+		check
+	}
+}
+
+impl EntryEncryptor for Argon2idXChaCha20Poly1305 {
+	fn encrypt(
+		&self,
+		passkey: &str,
+		plaintext: Vec<u8>,
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		self.encrypt_with_ad(passkey, plaintext, &[])
+	}
+
+	fn decrypt(
+		&self,
+		passkey: &str,
+		ciphertext: Vec<u8>,
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		self.decrypt_with_ad(passkey, ciphertext, &[])
+	}
+
+	fn encrypt_with_ad(
+		&self,
+		passkey: &str,
+		plaintext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		let salt = self.decode_salt()?;
+		let start = std::time::Instant::now();
+		let key = self.params.derive_key(passkey, &salt)?;
+		debug!("key derivation took: {:?}", start.elapsed());
+
+		let mut nonce = [0u8; Self::NONCE_LENGTH];
+		rand::rngs::SysRng
+			.try_fill_bytes(&mut nonce)
+			.expect("failed to generate random nonce");
+		let cipher = XChaCha20Poly1305::new(&key.into());
+		let ciphertext = cipher
+			.encrypt(
+				XNonce::from_slice(&nonce),
+				Payload {
+					msg: &plaintext,
+					aad: associated_data,
+				},
+			)
+			.map_err(|_| anyhow!("encryption failed"))?;
+
+		let mut buf = Vec::with_capacity(
+			Self::SALT_LENGTH + Self::KEY_CHECK_LENGTH + nonce.len() + ciphertext.len(),
+		);
+		buf.extend_from_slice(&salt);
+		buf.extend_from_slice(&Self::key_check(&key));
+		buf.extend_from_slice(&nonce);
+		buf.extend_from_slice(&ciphertext);
+		Ok(base64::engine::general_purpose::STANDARD
+			.encode(buf)
+			.into_bytes())
+	}
+
+	fn decrypt_with_ad(
+		&self,
+		passkey: &str,
+		ciphertext: Vec<u8>,
+		associated_data: &[u8],
+	) -> anyhow::Result<Vec<u8>, EntryEncryptionError> {
+		let decoded = base64::engine::general_purpose::STANDARD
+			.decode(ciphertext)
+			.map_err(|_| EntryEncryptionError::Corrupted)?;
+		if decoded.len() < Self::SALT_LENGTH + Self::KEY_CHECK_LENGTH + Self::NONCE_LENGTH {
+			return Err(EntryEncryptionError::Corrupted);
+		}
+		let (file_salt, rest) = decoded.split_at(Self::SALT_LENGTH);
+		let (check, rest) = rest.split_at(Self::KEY_CHECK_LENGTH);
+		let (nonce, ciphertext) = rest.split_at(Self::NONCE_LENGTH);
+
+		// a different salt means this was encrypted for a different entry
+		let salt = self.decode_salt()?;
+		if file_salt != salt {
+			return Err(EntryEncryptionError::Corrupted);
+		}
+		let start = std::time::Instant::now();
+		let key = self.params.derive_key(passkey, &salt)?;
+		debug!("key derivation took: {:?}", start.elapsed());
+		if check != Self::key_check(&key) {
+			return Err(EntryEncryptionError::IncorrectPasskey);
+		}
+
+		let cipher = XChaCha20Poly1305::new(&key.into());
+		cipher
+			.decrypt(
+				XNonce::from_slice(nonce),
+				Payload {
+					msg: ciphertext,
+					aad: associated_data,
+				},
+			)
+			.map_err(|_| EntryEncryptionError::Corrupted)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn scheme() -> Argon2idXChaCha20Poly1305 {
+		Argon2idXChaCha20Poly1305::generate_with_params(Argon2Params::LOW)
+	}
+
+	#[test]
+	fn test_ensure_encryption_symmetric() -> anyhow::Result<()> {
+		let scheme = scheme();
+		for case in [
+			"",
+			"foo",
+			"shadow wizard money gang, we love casting spells",
+		] {
+			let encrypted =
+				scheme.encrypt_with_ad("password", case.as_bytes().to_vec(), b"example")?;
+			let decrypted = scheme.decrypt_with_ad("password", encrypted, b"example")?;
+			assert_eq!(decrypted, case.as_bytes());
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn test_nonce_is_not_reused() -> anyhow::Result<()> {
+		let scheme = scheme();
+		let a = scheme.encrypt("password", b"foo".to_vec())?;
+		let b = scheme.encrypt("password", b"foo".to_vec())?;
+		assert_ne!(a, b);
+		Ok(())
+	}
+
+	#[test]
+	fn test_incorrect_passkey() -> anyhow::Result<()> {
+		let scheme = scheme();
+		let encrypted = scheme.encrypt("password", b"foo".to_vec())?;
+		assert!(matches!(
+			scheme.decrypt("wrong", encrypted),
+			Err(EntryEncryptionError::IncorrectPasskey)
+		));
+		Ok(())
+	}
+
+	#[test]
+	fn test_detects_tampering() -> anyhow::Result<()> {
+		let scheme = scheme();
+		let encrypted = scheme.encrypt_with_ad("password", b"foo".to_vec(), b"example")?;
+
+		let mut decoded = base64::engine::general_purpose::STANDARD.decode(&encrypted)?;
+		*decoded.last_mut().unwrap() ^= 1;
+		let tampered = base64::engine::general_purpose::STANDARD
+			.encode(decoded)
+			.into_bytes();
+		assert!(matches!(
+			scheme.decrypt_with_ad("password", tampered, b"example"),
+			Err(EntryEncryptionError::Corrupted)
+		));
+
+		assert!(matches!(
+			scheme.decrypt_with_ad("password", encrypted.clone(), b"other"),
+			Err(EntryEncryptionError::Corrupted)
+		));
+		assert!(matches!(
+			scheme.decrypt_with_ad("password", encrypted[..20].to_vec(), b"example"),
+			Err(EntryEncryptionError::Corrupted)
+		));
+		let other_entry = Argon2idXChaCha20Poly1305::generate_with_params(Argon2Params::LOW);
+		assert!(matches!(
+			other_entry.decrypt_with_ad("password", encrypted, b"example"),
+			Err(EntryEncryptionError::Corrupted)
+		));
+		Ok(())
+	}
+}
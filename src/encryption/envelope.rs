@@ -0,0 +1,118 @@
+//! A self describing, passkey encrypted container, used for moving data out of the maFiles directory.
+//!
+//! ```json
+//! {"envelope":"bundle","version":1,"encryption":{"scheme":"Argon2idXChaCha20Poly1305","salt":"...","m_cost":12288,"t_cost":3,"p_cost":12},"payload":"..."}
+//! ```
+
+use super::*;
+
+/// What is inside an [`Envelope`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvelopeKind {
+	/// Accounts exported with `steamguard export --format bundle`.
+	Bundle,
+	/// The whole maFiles directory, created with `steamguard backup`.
+	Backup,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+	#[serde(rename = "envelope")]
+	pub kind: EnvelopeKind,
+	pub version: u32,
+	pub encryption: EncryptionScheme,
+	/// The encrypted contents, base64 encoded.
+	pub payload: String,
+}
+
+impl Envelope {
+	pub const CURRENT_VERSION: u32 = 1;
+
+	/// Encrypt `plaintext` with a freshly generated [`EncryptionScheme`].
+	pub fn seal(
+		kind: EnvelopeKind,
+		passkey: &str,
+		plaintext: Vec<u8>,
+	) -> anyhow::Result<Self, EntryEncryptionError> {
+		let encryption = EncryptionScheme::generate();
+		let payload = encryption.encrypt(passkey, plaintext)?;
+		Ok(Self {
+			kind,
+			version: Self::CURRENT_VERSION,
+			encryption,
+			payload: String::from_utf8(payload).map_err(anyhow::Error::from)?,
+		})
+	}
+
+	/// Returns `None` if `buf` is not an envelope.
+	pub fn parse(buf: &[u8]) -> Option<Self> {
+		serde_json::from_slice(buf).ok()
+	}
+
+	pub fn open(&self, kind: EnvelopeKind, passkey: &str) -> Result<Vec<u8>, EnvelopeError> {
+		if self.kind != kind {
+			return Err(EnvelopeError::UnexpectedKind {
+				expected: kind,
+				found: self.kind,
+			});
+		}
+		if self.version > Self::CURRENT_VERSION {
+			return Err(EnvelopeError::UnsupportedVersion(self.version));
+		}
+		self.encryption
+			.decrypt(passkey, self.payload.as_bytes().to_vec())
+			.map_err(|err| match err {
+				EntryEncryptionError::Corrupted => EnvelopeError::Corrupted,
+				_ => EnvelopeError::IncorrectPasskey,
+			})
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum EnvelopeError {
+	#[error("Expected a {expected:?}, but this file contains a {found:?}.")]
+	UnexpectedKind {
+		expected: EnvelopeKind,
+		found: EnvelopeKind,
+	},
+	#[error("This file was created by a newer version of steamguard-cli (version {0}). Update steamguard-cli and try again.")]
+	UnsupportedVersion(u32),
+	#[error("Incorrect passkey.")]
+	IncorrectPasskey,
+	#[error("This file is corrupted, or has been tampered with.")]
+	Corrupted,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_envelope_roundtrip() {
+		let envelope =
+			Envelope::seal(EnvelopeKind::Bundle, "password", b"{\"foo\":1}".to_vec()).unwrap();
+		let serialized = serde_json::to_vec(&envelope).unwrap();
+		let parsed = Envelope::parse(&serialized).unwrap();
+		assert_eq!(
+			parsed.open(EnvelopeKind::Bundle, "password").unwrap(),
+			b"{\"foo\":1}"
+		);
+	}
+
+	#[test]
+	fn test_envelope_rejects_other_files() {
+		assert!(Envelope::parse(b"otpauth://totp/Steam:example?secret=ASDF").is_none());
+		assert!(Envelope::parse(br#"{"account_name": "example"}"#).is_none());
+	}
+
+	#[test]
+	fn test_envelope_rejects_newer_versions() {
+		let mut envelope = Envelope::seal(EnvelopeKind::Bundle, "password", vec![1]).unwrap();
+		envelope.version = Envelope::CURRENT_VERSION + 1;
+		assert!(matches!(
+			envelope.open(EnvelopeKind::Bundle, "password"),
+			Err(EnvelopeError::UnsupportedVersion(_))
+		));
+	}
+}
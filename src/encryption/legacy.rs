@@ -22,6 +22,20 @@ impl LegacySdaCompatible {
 	const SALT_LENGTH: usize = 8;
 	const IV_LENGTH: usize = 16;
 
+	pub fn generate() -> Self {
+		let mut rng = rand::rngs::SysRng;
+		let mut salt = [0u8; Self::SALT_LENGTH];
+		let mut iv = [0u8; Self::IV_LENGTH];
+		rng.try_fill_bytes(&mut salt)
+			.expect("failed to generate random salt");
+		rng.try_fill_bytes(&mut iv)
+			.expect("failed to generate random iv");
+		LegacySdaCompatible {
+			iv: base64::engine::general_purpose::STANDARD.encode(iv),
+			salt: base64::engine::general_purpose::STANDARD.encode(salt),
+		}
+	}
+
 	fn get_encryption_key(passkey: &str, salt: &str) -> anyhow::Result<[u8; Self::KEY_SIZE_BYTES]> {
 		let password_bytes = passkey.as_bytes();
 		let salt_bytes = base64::engine::general_purpose::STANDARD.decode(salt)?;
@@ -45,20 +59,6 @@ impl LegacySdaCompatible {
 }
 
 impl EntryEncryptor for LegacySdaCompatible {
-	fn generate() -> LegacySdaCompatible {
-		let mut rng = rand::rngs::SysRng;
-		let mut salt = [0u8; Self::SALT_LENGTH];
-		let mut iv = [0u8; Self::IV_LENGTH];
-		rng.try_fill_bytes(&mut salt)
-			.expect("failed to generate random salt");
-		rng.try_fill_bytes(&mut iv)
-			.expect("failed to generate random iv");
-		LegacySdaCompatible {
-			iv: base64::engine::general_purpose::STANDARD.encode(iv),
-			salt: base64::engine::general_purpose::STANDARD.encode(salt),
-		}
-	}
-
 	fn encrypt(
 		&self,
 		passkey: &str,
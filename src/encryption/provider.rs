@@ -0,0 +1,240 @@
+//! External sources for the encryption passkey, configured in the manifest.
+
+use std::{
+	io::Read,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+};
+
+use log::*;
+use secrecy::{ExposeSecret, SecretString};
+use zeroize::Zeroize;
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PasskeyProvider {
+	/// Run a command, and read the passkey from its stdout. eg. `pass show steamguard`
+	Command { command: Vec<String> },
+	/// Read the passkey from a file descriptor inherited from the parent process. Unix only.
+	Fd { fd: u32 },
+	/// Read the passkey from a file that only its owner can access.
+	File { path: PathBuf },
+}
+
+impl PasskeyProvider {
+	/// Get the passkey from the provider. A single trailing newline is removed.
+	pub fn fetch(&self) -> Result<SecretString, PasskeyProviderError> {
+		debug!("fetching passkey from {}", self);
+		let buf = match self {
+			Self::Command { command } => run_command(command)?,
+			Self::Fd { fd } => read_fd(*fd)?,
+			Self::File { path } => read_file(path)?,
+		};
+		let passkey = match String::from_utf8(buf) {
+			Ok(s) => trim_newline(SecretString::new(s)),
+			Err(err) => {
+				err.into_bytes().zeroize();
+				return Err(PasskeyProviderError::InvalidUtf8);
+			}
+		};
+		if passkey.expose_secret().is_empty() {
+			return Err(PasskeyProviderError::Empty);
+		}
+		Ok(passkey)
+	}
+}
+
+impl std::fmt::Display for PasskeyProvider {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Command { command } => write!(f, "command `{}`", command.join(" ")),
+			Self::Fd { fd } => write!(f, "file descriptor {}", fd),
+			Self::File { path } => write!(f, "file {}", path.display()),
+		}
+	}
+}
+
+fn trim_newline(passkey: SecretString) -> SecretString {
+	let s = passkey.expose_secret();
+	let trimmed = s
+		.strip_suffix("\r\n")
+		.or_else(|| s.strip_suffix('\n'))
+		.unwrap_or(s);
+	SecretString::new(trimmed.to_owned())
+}
+
+fn run_command(command: &[String]) -> Result<Vec<u8>, PasskeyProviderError> {
+	let (program, args) = command.split_first().ok_or(PasskeyProviderError::Empty)?;
+	// stdin and stderr are inherited, so the command can prompt for things like a GPG passphrase
+	let output = Command::new(program)
+		.args(args)
+		.stdin(Stdio::inherit())
+		.stderr(Stdio::inherit())
+		.output()?;
+	if !output.status.success() {
+		let mut stdout = output.stdout;
+		stdout.zeroize();
+		return Err(PasskeyProviderError::CommandFailed(output.status));
+	}
+	Ok(output.stdout)
+}
+
+#[cfg(unix)]
+fn read_fd(fd: u32) -> Result<Vec<u8>, PasskeyProviderError> {
+	let mut buf = vec![];
+	std::fs::File::open(format!("/dev/fd/{}", fd))?.read_to_end(&mut buf)?;
+	Ok(buf)
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: u32) -> Result<Vec<u8>, PasskeyProviderError> {
+	Err(PasskeyProviderError::Unsupported)
+}
+
+#[cfg(unix)]
+fn read_file(path: &Path) -> Result<Vec<u8>, PasskeyProviderError> {
+	use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+
+	// Check the file we actually opened, so it can't be swapped out between checking and reading it.
+	// O_NOFOLLOW refuses symlinks, and O_NONBLOCK keeps a FIFO from blocking before its type is checked.
+	let mut file = std::fs::File::options()
+		.read(true)
+		.custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+		.open(path)
+		.map_err(|err| match err.raw_os_error() {
+			Some(libc::ELOOP) => PasskeyProviderError::NotAFile(path.to_owned()),
+			_ => err.into(),
+		})?;
+	let metadata = file.metadata()?;
+	if !metadata.is_file() {
+		return Err(PasskeyProviderError::NotAFile(path.to_owned()));
+	}
+	// SAFETY: geteuid has no preconditions and can't fail.
+	if metadata.uid() != unsafe { libc::geteuid() } {
+		return Err(PasskeyProviderError::WrongOwner(path.to_owned()));
+	}
+	let mode = metadata.permissions().mode();
+	if mode & 0o077 != 0 {
+		return Err(PasskeyProviderError::InsecurePermissions {
+			path: path.to_owned(),
+			mode: mode & 0o777,
+		});
+	}
+	let mut buf = vec![];
+	file.read_to_end(&mut buf)?;
+	Ok(buf)
+}
+
+#[cfg(not(unix))]
+fn read_file(path: &Path) -> Result<Vec<u8>, PasskeyProviderError> {
+	let mut file = std::fs::File::open(path)?;
+	if !file.metadata()?.is_file() {
+		return Err(PasskeyProviderError::NotAFile(path.to_owned()));
+	}
+	let mut buf = vec![];
+	file.read_to_end(&mut buf)?;
+	Ok(buf)
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyProviderError {
+	#[error("The provider returned an empty secret.")]
+	Empty,
+	#[error("The provider returned a secret that is not valid UTF-8.")]
+	InvalidUtf8,
+	#[error("The command failed: {0}")]
+	CommandFailed(std::process::ExitStatus),
+	#[error("{} is not a regular file.", .0.display())]
+	NotAFile(PathBuf),
+	#[error("{} is owned by another user.", .0.display())]
+	WrongOwner(PathBuf),
+	#[error("{} can be accessed by other users (mode {mode:o}). Run `chmod 600 {}` to fix it.", path.display(), path.display())]
+	InsecurePermissions { path: PathBuf, mode: u32 },
+	#[error("Reading from a file descriptor is not supported on this platform.")]
+	Unsupported,
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_trim_newline() {
+		for (input, expected) in [
+			("foo", "foo"),
+			("foo\n", "foo"),
+			("foo\r\n", "foo"),
+			("foo\n\n", "foo\n"),
+			(" foo ", " foo "),
+		] {
+			let trimmed = trim_newline(SecretString::new(input.into()));
+			assert_eq!(trimmed.expose_secret(), expected);
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_command() {
+		let provider = PasskeyProvider::Command {
+			command: vec!["echo".into(), "hunter2".into()],
+		};
+		assert_eq!(provider.fetch().unwrap().expose_secret(), "hunter2");
+
+		let provider = PasskeyProvider::Command {
+			command: vec!["false".into()],
+		};
+		assert!(matches!(
+			provider.fetch(),
+			Err(PasskeyProviderError::CommandFailed(_))
+		));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_file_permissions() -> anyhow::Result<()> {
+		use std::os::unix::fs::PermissionsExt;
+
+		let dir = TempDir::new()?;
+		let path = dir.path().join("passkey");
+		std::fs::write(&path, "hunter2\n")?;
+		let provider = PasskeyProvider::File { path: path.clone() };
+
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+		assert!(matches!(
+			provider.fetch(),
+			Err(PasskeyProviderError::InsecurePermissions { mode: 0o644, .. })
+		));
+
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+		assert_eq!(provider.fetch()?.expose_secret(), "hunter2");
+
+		let link = dir.path().join("link");
+		std::os::unix::fs::symlink(&path, &link)?;
+		let provider = PasskeyProvider::File { path: link };
+		assert!(matches!(
+			provider.fetch(),
+			Err(PasskeyProviderError::NotAFile(_))
+		));
+		Ok(())
+	}
+
+	#[test]
+	fn test_serialization() -> anyhow::Result<()> {
+		let provider: PasskeyProvider =
+			serde_json::from_str(r#"{"type":"command","command":["pass","show","steamguard"]}"#)?;
+		assert_eq!(
+			provider,
+			PasskeyProvider::Command {
+				command: vec!["pass".into(), "show".into(), "steamguard".into()]
+			}
+		);
+		let provider: PasskeyProvider = serde_json::from_str(r#"{"type":"fd","fd":3}"#)?;
+		assert_eq!(provider, PasskeyProvider::Fd { fd: 3 });
+		Ok(())
+	}
+}
@@ -5,3 +5,56 @@ pub(crate) enum UserError {
 	#[error("User aborted the operation.")]
 	Aborted,
 }
+
+/// Failed health checks. These exit with the exit codes that monitoring tools like Nagios expect, instead of the generic one.
+#[derive(Debug, Error)]
+pub(crate) enum HealthCheckError {
+	#[error("{0}")]
+	Warning(String),
+	#[error("{0}")]
+	Critical(String),
+}
+
+impl HealthCheckError {
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			Self::Warning(_) => 1,
+			Self::Critical(_) => 2,
+		}
+	}
+}
+
+/// Something had to be entered by the user, but `--non-interactive` was given. These all exit with 3, and each message ends with a stable `(reason: ...)` so scripts can tell why.
+#[derive(Debug, Error)]
+pub(crate) enum PromptRequiredError {
+	#[error("A username is required to log in. (reason: username_required)")]
+	Username,
+	#[error("A password is required to log in to {0}. Store one with `steamguard credentials`, or pass --password. (reason: password_required)")]
+	Password(String),
+	#[error("Failed to read the password for {0} from the password command: {1} (reason: password_command_failed)")]
+	PasswordCommandFailed(String, String),
+	#[error("The password for {0} is incorrect. (reason: incorrect_password)")]
+	IncorrectPassword(String),
+	#[error("Logging in to {0} must be confirmed by clicking the link that Steam sent by email. (reason: email_confirmation_required)")]
+	EmailConfirmation(String),
+	#[error("Logging in to {0} requires the code that Steam sent by email. (reason: email_code_required)")]
+	EmailCode(String),
+	#[error("Logging in to {0} must be confirmed on another device. (reason: device_confirmation_required)")]
+	DeviceConfirmation(String),
+	#[error(
+		"Logging in to {0} requires a 2FA code from another device. (reason: device_code_required)"
+	)]
+	DeviceCode(String),
+	#[error("The encryption passkey is required. Pass it with --passkey, or configure `steamguard passkey-provider`. (reason: passkey_required)")]
+	Passkey,
+	#[error("{0} must be confirmed. (reason: confirmation_required)")]
+	Confirmation(String),
+	#[error("There is no manifest in {0}. (reason: manifest_missing)")]
+	ManifestMissing(String),
+}
+
+impl PromptRequiredError {
+	pub fn exit_code(&self) -> i32 {
+		3
+	}
+}
@@ -1,33 +1,43 @@
 use std::io::Write;
 
+use anyhow::Context;
 use log::*;
 use secrecy::{ExposeSecret, SecretString};
 use steamguard::{
 	protobufs::steammessages_auth_steamclient::{EAuthSessionGuardType, EAuthTokenPlatformType},
 	refresher::TokenRefresher,
-	steamapi::{self, AuthenticationClient},
+	steamapi::AuthenticationClient,
 	token::Tokens,
 	transport::Transport,
 	userlogin::UpdateAuthSessionError,
-	DeviceDetails, LoginError, SteamGuardAccount, UserLogin,
+	Credentials, DeviceDetails, LoginError, SteamGuardAccount, TimeSync, UserLogin,
 };
 
-use crate::tui;
+use crate::{encryption::PasskeyProvider, errors::PromptRequiredError, tui};
+
+/// Where to get what logging in needs, and whether the user may be asked for it.
+#[derive(Debug, Clone, Default)]
+pub struct LoginOptions {
+	/// Password given on the command line. Used instead of the account's stored credentials.
+	pub password: Option<SecretString>,
+	/// Command that prints the password, used when the maFile has no stored credentials.
+	pub password_command: Option<Vec<String>>,
+	/// Fail with a [`PromptRequiredError`] instead of prompting.
+	pub non_interactive: bool,
+}
 
 /// Performs a login, prompting for credentials if necessary.
 pub fn do_login<T: Transport + Clone>(
 	transport: T,
 	account: &mut SteamGuardAccount,
-	password: Option<SecretString>,
+	login: &LoginOptions,
+	time_sync: &TimeSync,
 ) -> anyhow::Result<()> {
-	if let Some(tokens) = account.tokens.as_mut() {
+	if account.tokens.is_some() {
 		info!("Refreshing access token...");
-		let client = AuthenticationClient::new(transport.clone());
-		let mut refresher = TokenRefresher::new(client);
-		match refresher.refresh(account.steam_id, tokens) {
-			Ok(token) => {
+		match refresh_tokens(transport.clone(), account) {
+			Ok(_) => {
 				info!("Successfully refreshed access token, no need to prompt to log in.");
-				tokens.set_access_token(token);
 				return Ok(());
 			}
 			Err(err) => {
@@ -41,16 +51,14 @@ pub fn do_login<T: Transport + Clone>(
 
 	if !account.account_name.is_empty() {
 		info!("Username: {}", account.account_name);
+	} else if login.non_interactive {
+		return Err(PromptRequiredError::Username.into());
 	} else {
 		eprint!("Username: ");
 		account.account_name = tui::prompt();
 	}
 	let _ = std::io::stdout().flush();
-	let password = if let Some(p) = password {
-		p
-	} else {
-		tui::prompt_password()?
-	};
+	let password = login_password(&account.account_name, account.credentials.as_ref(), login)?;
 	if !password.expose_secret().is_empty() {
 		debug!("password is present");
 	} else {
@@ -60,7 +68,8 @@ pub fn do_login<T: Transport + Clone>(
 		transport,
 		account.account_name.clone(),
 		password,
-		Some(account),
+		login,
+		Some((account, time_sync)),
 	)?;
 	let steam_id = tokens.access_token().decode()?.steam_id();
 	account.set_tokens(tokens);
@@ -68,30 +77,107 @@ pub fn do_login<T: Transport + Clone>(
 	Ok(())
 }
 
+/// Refreshes the access token using the account's refresh token. Never prompts.
+pub fn refresh_tokens<T: Transport + Clone>(
+	transport: T,
+	account: &mut SteamGuardAccount,
+) -> anyhow::Result<()> {
+	let Some(tokens) = account.tokens.as_mut() else {
+		bail!("{} has no tokens to refresh", account.account_name);
+	};
+	let client = AuthenticationClient::new(transport);
+	let mut refresher = TokenRefresher::new(client);
+	let token = refresher.refresh(account.steam_id, tokens)?;
+	tokens.set_access_token(token);
+	Ok(())
+}
+
+/// Logs in by showing a QR code that is scanned with the Steam mobile app on another device. Never prompts for a password.
+///
+/// `show_challenge_url` is called with the URL to show as a QR code, and again whenever Steam replaces it with a new one.
+pub fn do_login_qr<T: Transport + Clone>(
+	transport: T,
+	mut show_challenge_url: impl FnMut(&str),
+) -> anyhow::Result<Tokens> {
+	debug!("starting qr login");
+	let mut login = UserLogin::new(transport, build_device_details());
+	let started = match login.begin_auth_via_qr() {
+		Ok(started) => started,
+		Err(LoginError::TooManyAttempts) => {
+			error!("Too many login attempts. Steam is rate limiting you. Please wait a while and try again later.");
+			return Err(LoginError::TooManyAttempts.into());
+		}
+		Err(err) => return Err(err.into()),
+	};
+	show_challenge_url(started.challenge_url());
+
+	info!("Waiting for the login to be approved...");
+	let tokens = login.poll_until_tokens_with(show_challenge_url)?;
+
+	info!("Logged in successfully!");
+	Ok(tokens)
+}
+
 pub fn do_login_raw<T: Transport + Clone>(
 	transport: T,
 	username: String,
-	password: Option<SecretString>,
+	login: &LoginOptions,
 ) -> anyhow::Result<Tokens> {
 	let _ = std::io::stdout().flush();
-	let password = if let Some(p) = password {
-		p
-	} else {
-		tui::prompt_password()?
-	};
+	let password = login_password(&username, None, login)?;
 	if !password.expose_secret().is_empty() {
 		debug!("password is present");
 	} else {
 		debug!("password is empty");
 	}
-	do_login_impl(transport, username, password, None)
+	do_login_impl(transport, username, password, login, None)
+}
+
+/// Gets the password from the command line, the credentials stored in the maFile, or the password command, in that order. Prompts if none of them have it.
+fn login_password(
+	account_name: &str,
+	credentials: Option<&Credentials>,
+	login: &LoginOptions,
+) -> anyhow::Result<SecretString> {
+	if let Some(password) = &login.password {
+		return Ok(password.clone());
+	}
+	if let Some(credentials) = credentials {
+		debug!("using the password stored in the maFile");
+		return Ok(credentials.password.clone());
+	}
+	if let Some(command) = &login.password_command {
+		let provider = PasskeyProvider::Command {
+			command: command.clone(),
+		};
+		info!(
+			"Reading the password for {} from {}",
+			account_name, provider
+		);
+		return match provider.fetch() {
+			Ok(password) => Ok(password),
+			Err(err) if login.non_interactive => Err(PromptRequiredError::PasswordCommandFailed(
+				account_name.to_owned(),
+				err.to_string(),
+			)
+			.into()),
+			Err(err) => {
+				Err(err).with_context(|| format!("reading the password for {}", account_name))
+			}
+		};
+	}
+	if login.non_interactive {
+		return Err(PromptRequiredError::Password(account_name.to_owned()).into());
+	}
+	tui::prompt_password()
 }
 
 fn do_login_impl<T: Transport + Clone>(
 	transport: T,
 	username: String,
 	password: SecretString,
-	account: Option<&SteamGuardAccount>,
+	login_options: &LoginOptions,
+	account: Option<(&SteamGuardAccount, &TimeSync)>,
 ) -> anyhow::Result<Tokens> {
 	debug!("starting login");
 	let mut login = UserLogin::new(transport.clone(), build_device_details());
@@ -110,6 +196,9 @@ fn do_login_impl<T: Transport + Clone>(
 			}
 			Err(LoginError::BadCredentials) => {
 				error!("Incorrect password for {username}");
+				if login_options.non_interactive {
+					return Err(PromptRequiredError::IncorrectPassword(username).into());
+				}
 				password = tui::prompt_password()?;
 				continue;
 			}
@@ -134,8 +223,34 @@ fn do_login_impl<T: Transport + Clone>(
 		method.confirmation_type
 			== EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation
 	});
+	let is_device_code_available = confirmation_methods.iter().any(|method| {
+		method.confirmation_type == EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode
+	});
 
 	for method in confirmation_methods {
+		if login_options.non_interactive {
+			match method.confirmation_type {
+				// the code can be generated without asking, so use that instead of waiting for a confirmation that may never come
+				EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation
+					if account.is_some() && is_device_code_available =>
+				{
+					continue;
+				}
+				EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation => {
+					return Err(PromptRequiredError::DeviceConfirmation(username).into());
+				}
+				EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode if account.is_none() => {
+					return Err(PromptRequiredError::DeviceCode(username).into());
+				}
+				EAuthSessionGuardType::k_EAuthSessionGuardType_EmailConfirmation => {
+					return Err(PromptRequiredError::EmailConfirmation(username).into());
+				}
+				EAuthSessionGuardType::k_EAuthSessionGuardType_EmailCode => {
+					return Err(PromptRequiredError::EmailCode(username).into());
+				}
+				_ => {}
+			}
+		}
 		match method.confirmation_type {
 			EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation => {
 				eprintln!("Please confirm this login on your other device.");
@@ -167,9 +282,9 @@ fn do_login_impl<T: Transport + Clone>(
 				};
 				let mut attempts = 0;
 				loop {
-					let code = if let Some(account) = account {
+					let code = if let Some((account, time_sync)) = account {
 						debug!("Generating 2fa code...");
-						let time = steamapi::get_server_time(transport.clone())?.server_time();
+						let time = time_sync.server_time(transport.clone())?;
 						account.generate_code(time)
 					} else {
 						tui::prompt_allow_empty(&prompt).trim().to_owned()
@@ -245,3 +360,101 @@ fn build_device_details() -> DeviceDetails {
 		gaming_device_type: 528,
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use steamguard::{
+		protobufs::steammessages_auth_steamclient::{
+			CAuthentication_AllowedConfirmation,
+			CAuthentication_BeginAuthSessionViaCredentials_Response,
+			CAuthentication_GetPasswordRSAPublicKey_Response,
+		},
+		transport::MockTransport,
+	};
+
+	fn non_interactive() -> LoginOptions {
+		LoginOptions {
+			non_interactive: true,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_login_password_order() {
+		let credentials = Credentials {
+			password: SecretString::new("stored".into()),
+		};
+		let login = LoginOptions {
+			password: Some(SecretString::new("given".into())),
+			..non_interactive()
+		};
+		let password = login_password("example", Some(&credentials), &login).unwrap();
+		assert_eq!(password.expose_secret(), "given");
+
+		let password = login_password("example", Some(&credentials), &non_interactive()).unwrap();
+		assert_eq!(password.expose_secret(), "stored");
+
+		#[cfg(unix)]
+		{
+			let login = LoginOptions {
+				password_command: Some(vec!["echo".into(), "from command".into()]),
+				..non_interactive()
+			};
+			let password = login_password("example", None, &login).unwrap();
+			assert_eq!(password.expose_secret(), "from command");
+
+			let login = LoginOptions {
+				password_command: Some(vec!["false".into()]),
+				..non_interactive()
+			};
+			let err = login_password("example", None, &login).unwrap_err();
+			assert!(matches!(
+				err.downcast_ref::<PromptRequiredError>(),
+				Some(PromptRequiredError::PasswordCommandFailed(name, _)) if name == "example"
+			));
+		}
+
+		let err = login_password("example", None, &non_interactive()).unwrap_err();
+		assert!(matches!(
+			err.downcast_ref::<PromptRequiredError>(),
+			Some(PromptRequiredError::Password(name)) if name == "example"
+		));
+	}
+
+	#[test]
+	fn test_non_interactive_email_code_fails() {
+		let transport = MockTransport::new();
+		let mut rsa = CAuthentication_GetPasswordRSAPublicKey_Response::new();
+		rsa.set_publickey_exp("010001".into());
+		rsa.set_publickey_mod("98f9088c1250b17fe19d2b2422d54a1eef0036875301731f11bd17900e215318eb6de1546727c0b7b61b86cefccdcb2f8108c813154d9a7d55631965eece810d4ab9d8a59c486bda778651b876176070598a93c2325c275cb9c17bdbcacf8edc9c18c0c5d59bc35703505ef8a09ed4c62b9f92a3fac5740ce25e490ab0e26d872140e4103d912d1e3958f844264211277ee08d2b4dd3ac58b030b25342bd5c949ae7794e46a8eab26d5a8deca683bfd381da6c305b19868b8c7cd321ce72c693310a6ebf2ecd43642518f825894602f6c239cf193cb4346ce64beac31e20ef88f934f2f776597734bb9eae1ebdf4a453973b6df9d5e90777bffe5db83dd1757b".into());
+		rsa.set_timestamp(1);
+		transport.respond_with("IAuthenticationService", "GetPasswordRSAPublicKey", rsa);
+		let mut begin = CAuthentication_BeginAuthSessionViaCredentials_Response::new();
+		begin.set_client_id(1);
+		begin.set_request_id(vec![1, 2, 3]);
+		let mut confirmation = CAuthentication_AllowedConfirmation::new();
+		confirmation
+			.set_confirmation_type(EAuthSessionGuardType::k_EAuthSessionGuardType_EmailCode);
+		begin.allowed_confirmations.push(confirmation);
+		transport.respond_with(
+			"IAuthenticationService",
+			"BeginAuthSessionViaCredentials",
+			begin,
+		);
+
+		let err = do_login_impl(
+			transport.clone(),
+			"example".into(),
+			SecretString::new("hunter2".into()),
+			&non_interactive(),
+			None,
+		)
+		.unwrap_err();
+		assert!(matches!(
+			err.downcast_ref::<PromptRequiredError>(),
+			Some(PromptRequiredError::EmailCode(name)) if name == "example"
+		));
+		assert_eq!(transport.requests().len(), 2);
+	}
+}
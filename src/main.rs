@@ -1,15 +1,18 @@
 extern crate rpassword;
+use anyhow::Context;
 use clap::Parser;
 use log::*;
 use secrecy::SecretString;
 use std::{
 	path::Path,
 	sync::{Arc, Mutex},
+	time::Duration,
 };
 use steamguard::transport::WebApiTransport;
 use steamguard::SteamGuardAccount;
 
 use crate::accountmanager::migrate::{load_and_migrate, MigrationError};
+use crate::accountmanager::ManifestLock;
 pub use crate::accountmanager::{AccountManager, ManifestAccountLoadError, ManifestLoadError};
 use crate::commands::{CommandType, Subcommands};
 pub use login::*;
@@ -27,6 +30,8 @@ mod debug;
 mod encryption;
 mod errors;
 mod login;
+mod output;
+mod rules;
 mod secret_string;
 pub(crate) mod tui;
 
@@ -50,7 +55,13 @@ fn main() {
 		Ok(_) => 0,
 		Err(e) => {
 			error!("{:?}", e);
-			255
+			if let Some(e) = e.downcast_ref::<errors::HealthCheckError>() {
+				e.exit_code()
+			} else if let Some(e) = e.downcast_ref::<errors::PromptRequiredError>() {
+				e.exit_code()
+			} else {
+				255
+			}
 		}
 	};
 
@@ -80,16 +91,25 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 	let globalargs = args.global;
 	let subcommand = args.sub.unwrap_or(Subcommands::Code(args.code));
 	let is_list_command = matches!(&subcommand, Subcommands::List(_));
+	let holds_lock = subcommand.holds_lock();
 
 	let cmd: CommandType<WebApiTransport> = match subcommand {
 		Subcommands::Approve(args) => CommandType::Account(Box::new(args)),
 		Subcommands::Debug(args) => CommandType::Const(Box::new(args)),
 		Subcommands::Completion(args) => CommandType::Const(Box::new(args)),
+		Subcommands::Benchmark(args) => CommandType::Const(Box::new(args)),
 		Subcommands::Setup(args) => CommandType::Manifest(Box::new(args)),
 		Subcommands::Import(args) => CommandType::Manifest(Box::new(args)),
+		Subcommands::Export(args) => CommandType::Account(Box::new(args)),
+		Subcommands::Backup(args) => CommandType::Manifest(Box::new(args)),
+		Subcommands::Restore(args) => CommandType::Manifest(Box::new(args)),
 		Subcommands::List(args) => CommandType::Manifest(Box::new(args)),
+		Subcommands::Tag(args) => CommandType::Manifest(Box::new(args)),
+		Subcommands::Untag(args) => CommandType::Manifest(Box::new(args)),
 		Subcommands::Encrypt(args) => CommandType::Manifest(Box::new(args)),
 		Subcommands::Decrypt(args) => CommandType::Manifest(Box::new(args)),
+		Subcommands::Rekey(args) => CommandType::Manifest(Box::new(args)),
+		Subcommands::PasskeyProvider(args) => CommandType::Manifest(Box::new(args)),
 		Subcommands::Confirm(args) => CommandType::Account(Box::new(args)),
 		Subcommands::Remove(args) => CommandType::Account(Box::new(args)),
 		Subcommands::Code(args) => CommandType::Account(Box::new(args)),
@@ -97,6 +117,12 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 		Subcommands::Qr(args) => CommandType::Account(Box::new(args)),
 		Subcommands::QrLogin(args) => CommandType::Account(Box::new(args)),
 		Subcommands::Status(args) => CommandType::Account(Box::new(args)),
+		Subcommands::Login(args) => CommandType::Account(Box::new(args)),
+		Subcommands::Sessions(args) => CommandType::Account(Box::new(args)),
+		Subcommands::Tokens(args) => CommandType::Account(Box::new(args)),
+		Subcommands::Credentials(args) => CommandType::Account(Box::new(args)),
+		#[cfg(feature = "server")]
+		Subcommands::Serve(args) => CommandType::Manifest(Box::new(args)),
 	};
 
 	if let CommandType::Const(cmd) = cmd {
@@ -110,11 +136,21 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 	};
 	info!("reading manifest from {}", mafiles_dir);
 	let path = Path::new(&mafiles_dir).join("manifest.json");
-	let mut passkey = globalargs.passkey.clone();
+	let mut passkey = match &globalargs.age_identity {
+		Some(path) => Some(
+			encryption::PasskeyProvider::File { path: path.clone() }
+				.fetch()
+				.context("reading age identity")?,
+		),
+		None => globalargs.passkey.clone(),
+	};
 
 	let mut manager: accountmanager::AccountManager;
 	if !path.exists() {
 		error!("Did not find manifest in {}", mafiles_dir);
+		if globalargs.non_interactive {
+			return Err(errors::PromptRequiredError::ManifestMissing(mafiles_dir).into());
+		}
 		if tui::prompt_char(
 			format!("Would you like to create a manifest in {} ?", mafiles_dir).as_str(),
 			"Yn",
@@ -123,13 +159,30 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 			info!("Aborting!");
 			return Err(errors::UserError::Aborted.into());
 		}
-		std::fs::create_dir_all(mafiles_dir)?;
+		std::fs::create_dir_all(&mafiles_dir)?;
+	}
+
+	// lock before reading the manifest, so we never modify a stale copy of it
+	let lock_timeout = Duration::from_secs(globalargs.lock_timeout);
+	let mut lock = if holds_lock {
+		Some(ManifestLock::acquire(
+			Path::new(&mafiles_dir),
+			lock_timeout,
+		)?)
+	} else {
+		None
+	};
 
+	if !path.exists() {
 		manager = accountmanager::AccountManager::new(path.as_path());
+		manager.set_lock(lock.take(), lock_timeout);
 		manager.save()?;
 	} else {
 		manager = match accountmanager::AccountManager::load(path.as_path()) {
-			Ok(m) => m,
+			Ok(mut m) => {
+				m.set_lock(lock.take(), lock_timeout);
+				m
+			}
 			Err(ManifestLoadError::MigrationNeeded) => {
 				if is_list_command {
 					bail!(
@@ -162,6 +215,9 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 								}
 							}
 
+							if globalargs.non_interactive {
+								return Err(errors::PromptRequiredError::Passkey.into());
+							}
 							passkey = Some(tui::prompt_passkey()?);
 						}
 						Err(e) => {
@@ -171,6 +227,7 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 					}
 				}
 				let mut manager = AccountManager::from_manifest(manifest, mafiles_dir);
+				manager.set_lock(lock.take(), lock_timeout);
 				manager.register_accounts(accounts);
 				manager.submit_passkey(passkey.clone());
 				manager.save()?;
@@ -196,6 +253,18 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 		}
 	}
 
+	if passkey.is_none() && !is_list_command && manager.iter().any(|e| e.encryption.is_some()) {
+		if let Some(provider) = manager.passkey_provider() {
+			info!("Reading encryption passkey from {}", provider);
+			match provider.fetch() {
+				Ok(k) => passkey = Some(k),
+				Err(e) => {
+					warn!("Failed to get encryption passkey from {}: {}", provider, e);
+				}
+			}
+		}
+	}
+
 	manager.submit_passkey(passkey);
 	if is_list_command {
 		let http_client = reqwest::blocking::Client::builder().build()?;
@@ -224,6 +293,9 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 				if manager.has_passkey() {
 					error!("Incorrect passkey");
 				}
+				if globalargs.non_interactive {
+					return Err(errors::PromptRequiredError::Passkey.into());
+				}
 				passkey = Some(tui::prompt_passkey()?);
 				manager.submit_passkey(passkey);
 			}
@@ -234,6 +306,10 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 		}
 	}
 
+	if manager.has_unauthenticated_encryption() {
+		warn!("Some maFiles use an encryption scheme that can't detect tampering. Run `steamguard rekey` to upgrade them, but note that SteamDesktopAuthenticator won't be able to read them afterwards.");
+	}
+
 	let mut http_client = reqwest::blocking::Client::builder();
 	if let Some(proxy) = &globalargs.http_proxy {
 		let mut proxy = reqwest::Proxy::all(proxy)?;
@@ -268,6 +344,9 @@ fn run(args: commands::Args) -> anyhow::Result<()> {
 				if manager.has_passkey() {
 					error!("Incorrect passkey");
 				}
+				if globalargs.non_interactive {
+					return Err(errors::PromptRequiredError::Passkey.into());
+				}
 				passkey = Some(tui::prompt_passkey()?);
 				manager.submit_passkey(passkey);
 			}
@@ -297,26 +376,11 @@ fn get_selected_accounts(
 	args: &commands::GlobalArgs,
 	manifest: &mut accountmanager::AccountManager,
 ) -> anyhow::Result<Vec<Arc<Mutex<SteamGuardAccount>>>, ManifestAccountLoadError> {
+	let account_names = commands::selected_account_names(args, manifest)?;
+	manifest.load_accounts_by_name(&account_names)?;
 	let mut selected_accounts: Vec<Arc<Mutex<SteamGuardAccount>>> = vec![];
-
-	if args.all {
-		manifest.load_accounts()?;
-		for entry in manifest.iter() {
-			selected_accounts.push(manifest.get_account(&entry.account_name).unwrap().clone());
-		}
-	} else {
-		let entry = if let Some(username) = &args.username {
-			manifest.get_entry(username)
-		} else {
-			manifest
-				.iter()
-				.next()
-				.ok_or(ManifestAccountLoadError::MissingManifestEntry)
-		}?;
-
-		let account_name = entry.account_name.clone();
-		let account = manifest.get_or_load_account(&account_name)?;
-		selected_accounts.push(account);
+	for account_name in account_names {
+		selected_accounts.push(manifest.get_account(&account_name)?);
 	}
 	Ok(selected_accounts)
 }
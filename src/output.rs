@@ -0,0 +1,271 @@
+//! Machine readable output, used when `--output json` is specified.
+//!
+//! Every command prints exactly one JSON document to stdout. The shape of these documents is documented in `docs/json-output.md`, so don't change them without updating the docs.
+
+use serde::Serialize;
+use steamguard::{
+	protobufs::{
+		service_twofactor::CTwoFactor_Status_Response,
+		steammessages_auth_steamclient::CAuthentication_GetAuthSessionInfo_Response,
+	},
+	token::Jwt,
+	Confirmation, SteamGuardAccount,
+};
+
+use crate::rules::SessionPlatform;
+
+/// Codes are valid for this many seconds.
+pub(crate) const CODE_PERIOD: u64 = 30;
+
+pub(crate) fn print_json<S: Serialize>(value: &S) -> anyhow::Result<()> {
+	println!("{}", serde_json::to_string(value)?);
+	Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct CodeOutput {
+	pub account_name: String,
+	pub code: String,
+	pub server_time: u64,
+	/// Seconds until the code expires.
+	pub expires_in: u64,
+}
+
+impl CodeOutput {
+	pub fn new(account: &SteamGuardAccount, server_time: u64) -> Self {
+		Self {
+			account_name: account.account_name.clone(),
+			code: account.generate_code(server_time),
+			server_time,
+			expires_in: CODE_PERIOD - server_time % CODE_PERIOD,
+		}
+	}
+}
+
+/// A code for a time step relative to the current one, used by `code --window` and `code --verify`.
+#[derive(Debug, Serialize)]
+pub(crate) struct CodeStepOutput {
+	pub account_name: String,
+	pub code: String,
+	/// Number of 30 second steps from the current one. Negative steps are in the past.
+	pub step: i64,
+	/// The time the code was generated for.
+	pub time: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ConfirmationOutput {
+	pub id: String,
+	/// Numeric confirmation type, see [`steamguard::ConfirmationType`].
+	#[serde(rename = "type")]
+	pub conf_type: u32,
+	pub type_name: String,
+	pub creator_id: String,
+	pub creation_time: u64,
+	pub headline: String,
+	pub summary: Vec<String>,
+}
+
+impl From<&Confirmation> for ConfirmationOutput {
+	fn from(conf: &Confirmation) -> Self {
+		Self {
+			id: conf.id.clone(),
+			conf_type: conf.conf_type.into(),
+			type_name: conf.type_name.clone(),
+			creator_id: conf.creator_id.clone(),
+			creation_time: conf.creation_time,
+			headline: conf.headline.clone(),
+			summary: conf.summary.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SessionOutput {
+	pub client_id: u64,
+	pub ip: String,
+	pub geoloc: String,
+	pub city: String,
+	pub state: String,
+	pub country: String,
+	pub platform: SessionPlatform,
+	pub device_friendly_name: String,
+}
+
+impl SessionOutput {
+	pub fn new(client_id: u64, session: &CAuthentication_GetAuthSessionInfo_Response) -> Self {
+		Self {
+			client_id,
+			ip: session.ip().to_owned(),
+			geoloc: session.geoloc().to_owned(),
+			city: session.city().to_owned(),
+			state: session.state().to_owned(),
+			country: session.country().to_owned(),
+			platform: session.platform_type().into(),
+			device_friendly_name: session.device_friendly_name().to_owned(),
+		}
+	}
+}
+
+/// The claims of a stored access or refresh token, used by `sessions list`.
+#[derive(Debug, Serialize)]
+pub(crate) struct TokenOutput {
+	/// Token ID
+	pub jti: String,
+	/// Audience
+	pub aud: Vec<String>,
+	/// Issued at
+	pub iat: u64,
+	/// Expires at
+	pub exp: u64,
+	/// Seconds until the token expires. Negative if it already has.
+	pub expires_in: i64,
+}
+
+impl TokenOutput {
+	pub fn new(token: &Jwt, now: u64) -> anyhow::Result<Self> {
+		let data = token.decode()?;
+		Ok(Self {
+			jti: data.jti,
+			aud: data.aud,
+			iat: data.iat,
+			exp: data.exp,
+			expires_in: data.exp as i64 - now as i64,
+		})
+	}
+
+	/// Like `expires in 3d 4h`, or `expired 5m ago`.
+	pub fn expiry_description(&self) -> String {
+		if self.expires_in > 0 {
+			format!(
+				"expires in {}",
+				format_duration(self.expires_in.unsigned_abs())
+			)
+		} else {
+			format!(
+				"expired {} ago",
+				format_duration(self.expires_in.unsigned_abs())
+			)
+		}
+	}
+}
+
+/// Format a number of seconds with the two largest units, like `3d 4h` or `5m 10s`.
+pub(crate) fn format_duration(secs: u64) -> String {
+	let units = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+	let parts: Vec<String> = units
+		.iter()
+		.scan(secs, |rest, (name, size)| {
+			let n = *rest / size;
+			*rest %= size;
+			Some((n, name))
+		})
+		.skip_while(|(n, _)| *n == 0)
+		.take(2)
+		.filter(|(n, _)| *n > 0)
+		.map(|(n, name)| format!("{}{}", n, name))
+		.collect();
+	if parts.is_empty() {
+		"0s".to_owned()
+	} else {
+		parts.join(" ")
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct StatusOutput {
+	pub steamguard_scheme: &'static str,
+	pub email_validated: bool,
+	pub has_authenticator: bool,
+	pub revocation_attempts_remaining: u32,
+	pub version: u32,
+	pub time_created: u32,
+	pub time_transferred: u32,
+	pub device_identifier: String,
+	pub authenticator_type: u32,
+}
+
+impl From<&CTwoFactor_Status_Response> for StatusOutput {
+	fn from(data: &CTwoFactor_Status_Response) -> Self {
+		Self {
+			steamguard_scheme: match data.steamguard_scheme() {
+				0 => "none",
+				1 => "email",
+				2 => "mobile_app",
+				_ => "unknown",
+			},
+			email_validated: data.email_validated(),
+			has_authenticator: data.state() == 1,
+			revocation_attempts_remaining: data.revocation_attempts_remaining(),
+			version: data.version(),
+			time_created: data.time_created(),
+			time_transferred: data.time_transferred(),
+			device_identifier: data.device_identifier().to_owned(),
+			authenticator_type: data.authenticator_type(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_code_expires_in() {
+		let account = SteamGuardAccount::new();
+		assert_eq!(CodeOutput::new(&account, 1616374841).expires_in, 19);
+		assert_eq!(CodeOutput::new(&account, 1616374830).expires_in, 30);
+		assert_eq!(CodeOutput::new(&account, 1616374859).expires_in, 1);
+	}
+
+	#[test]
+	fn test_confirmation_output_shape() {
+		let conf = Confirmation {
+			id: "1234".to_owned(),
+			nonce: "12345".to_owned(),
+			conf_type: steamguard::ConfirmationType::MarketSell,
+			creator_id: "09870987".to_owned(),
+			headline: "Sell - Mann Co. Supply Crate Key".into(),
+			type_name: "Market Listing".to_owned(),
+			creation_time: 1687457923,
+			cancel: "Cancel".to_owned(),
+			accept: "Create Listing".to_owned(),
+			icon: None,
+			multi: false,
+			summary: vec!["2,50€".to_owned()],
+		};
+		let value = serde_json::to_value(ConfirmationOutput::from(&conf)).unwrap();
+		assert_eq!(
+			value,
+			serde_json::json!({
+				"id": "1234",
+				"type": 3,
+				"type_name": "Market Listing",
+				"creator_id": "09870987",
+				"creation_time": 1687457923,
+				"headline": "Sell - Mann Co. Supply Crate Key",
+				"summary": ["2,50€"],
+			})
+		);
+	}
+
+	#[test]
+	fn test_format_duration() {
+		assert_eq!(format_duration(0), "0s");
+		assert_eq!(format_duration(59), "59s");
+		assert_eq!(format_duration(3 * 60 + 5), "3m 5s");
+		assert_eq!(format_duration(2 * 86400 + 3600 + 30), "2d 1h");
+		assert_eq!(format_duration(86400 + 59), "1d");
+	}
+
+	#[test]
+	fn test_token_output() {
+		let jwt: Jwt = "eyAidHlwIjogIkpXVCIsICJhbGciOiAiRWREU0EiIH0.eyAiaXNzIjogInN0ZWFtIiwgInN1YiI6ICI3NjU2MTE5OTE1NTcwNjg5MiIsICJhdWQiOiBbICJ3ZWIiLCAicmVuZXciLCAiZGVyaXZlIiBdLCAiZXhwIjogMTcwNTAxMTk1NSwgIm5iZiI6IDE2Nzg0NjQ4MzcsICJpYXQiOiAxNjg3MTA0ODM3LCAianRpIjogIjE4QzVfMjJCM0Y0MzFfQ0RGNkEiLCAib2F0IjogMTY4NzEwNDgzNywgInBlciI6IDEsICJpcF9zdWJqZWN0IjogIjY5LjEyMC4xMzYuMTI0IiwgImlwX2NvbmZpcm1lciI6ICI2OS4xMjAuMTM2LjEyNCIgfQ.7p5TPj9pGQbxIzWDDNCSP9OkKYSeDnWBE8E-M8hUrxOEPCW0XwrbDUrh199RzjPDw".to_owned().into();
+		let token = TokenOutput::new(&jwt, 1705011955 - 3600).unwrap();
+		assert_eq!(token.jti, "18C5_22B3F431_CDF6A");
+		assert_eq!(token.expires_in, 3600);
+		assert_eq!(token.expiry_description(), "expires in 1h");
+		let token = TokenOutput::new(&jwt, 1705011955 + 90).unwrap();
+		assert_eq!(token.expiry_description(), "expired 1m 30s ago");
+	}
+}
@@ -0,0 +1,68 @@
+//! Rules used to make decisions non-interactively.
+
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+mod confirmation;
+mod session;
+
+pub use confirmation::*;
+pub use session::*;
+
+/// Parses a human friendly duration, like `30s`, `10m`, `2h` or `1d`. A bare number is treated as seconds.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+	let s = s.trim();
+	let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+	let (num, unit) = s.split_at(split);
+	ensure!(!num.is_empty(), "Invalid duration: {}", s);
+	let num: u64 = num.parse()?;
+	let multiplier = match unit.trim() {
+		"" | "s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 60 * 60 * 24,
+		_ => bail!("Invalid duration unit in {}, expected one of s, m, h, d", s),
+	};
+	Ok(Duration::from_secs(num * multiplier))
+}
+
+fn de_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let Some(s) = Option::<String>::deserialize(deserializer)? else {
+		return Ok(None);
+	};
+	parse_duration(&s)
+		.map(Some)
+		.map_err(serde::de::Error::custom)
+}
+
+fn de_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let Some(s) = Option::<String>::deserialize(deserializer)? else {
+		return Ok(None);
+	};
+	Regex::new(&s).map(Some).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_duration() {
+		assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+		assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+		assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+		assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+		assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+		assert!(parse_duration("m").is_err());
+		assert!(parse_duration("10y").is_err());
+		assert!(parse_duration("").is_err());
+	}
+}
@@ -0,0 +1,265 @@
+use std::{path::Path, time::Duration};
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use steamguard::{Confirmation, ConfirmationType};
+
+use super::{de_duration, de_regex};
+
+/// What to do with a confirmation that matched a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+	Accept,
+	Deny,
+}
+
+/// A set of rules, loaded from a JSON file.
+///
+/// ```json
+/// {
+///     "rules": [
+///         { "action": "accept", "types": ["market-sell"], "older_than": "1m" },
+///         { "action": "deny", "types": ["api-key-creation"] },
+///         { "action": "accept", "types": ["trade"], "creator_ids": ["1234567890"] },
+///         { "action": "accept", "headline": "^Sell", "summary": "Mann Co\\. Supply Crate Key" }
+///     ]
+/// }
+/// ```
+///
+/// Rules are evaluated in order, and the first matching rule wins. Confirmations that don't match any rule are ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfirmationRules {
+	pub rules: Vec<ConfirmationRule>,
+}
+
+/// A single rule. Every condition that is specified must match for the rule to apply.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfirmationRule {
+	pub action: RuleAction,
+	/// Confirmation types, by name (eg. `market-sell`) or by number.
+	#[serde(default, deserialize_with = "de_confirmation_types")]
+	pub types: Vec<ConfirmationType>,
+	/// Trade offer IDs or market transaction IDs.
+	#[serde(default)]
+	pub creator_ids: Vec<String>,
+	/// Confirmation IDs.
+	#[serde(default)]
+	pub ids: Vec<String>,
+	#[serde(default, deserialize_with = "de_regex")]
+	pub headline: Option<Regex>,
+	/// Matches if any line of the summary matches.
+	#[serde(default, deserialize_with = "de_regex")]
+	pub summary: Option<Regex>,
+	/// Only match confirmations that are at least this old.
+	#[serde(default, deserialize_with = "de_duration")]
+	pub older_than: Option<Duration>,
+	/// Only match confirmations that are at most this old.
+	#[serde(default, deserialize_with = "de_duration")]
+	pub newer_than: Option<Duration>,
+}
+
+impl ConfirmationRules {
+	pub fn load(path: &Path) -> anyhow::Result<Self> {
+		let text = std::fs::read_to_string(path)?;
+		Self::parse(&text)
+	}
+
+	fn parse(text: &str) -> anyhow::Result<Self> {
+		let mut deser = serde_json::Deserializer::from_str(text);
+		let rules: Self = serde_path_to_error::deserialize(&mut deser)?;
+		for (i, rule) in rules.rules.iter().enumerate() {
+			ensure!(
+				rule.action != RuleAction::Accept || rule.has_conditions(),
+				"Rule {} accepts every confirmation, give it at least one of types, creator_ids, ids, headline, summary, older_than or newer_than",
+				i
+			);
+		}
+		Ok(rules)
+	}
+
+	/// Find the first rule that matches the confirmation. Returns the index of the rule and the action to take.
+	///
+	/// `now` is the current unix timestamp, used to determine the age of the confirmation.
+	pub fn decide(&self, conf: &Confirmation, now: u64) -> Option<(usize, RuleAction)> {
+		self.rules
+			.iter()
+			.enumerate()
+			.find(|(_, rule)| rule.matches(conf, now))
+			.map(|(i, rule)| (i, rule.action))
+	}
+}
+
+impl ConfirmationRule {
+	fn has_conditions(&self) -> bool {
+		!self.types.is_empty()
+			|| !self.creator_ids.is_empty()
+			|| !self.ids.is_empty()
+			|| self.headline.is_some()
+			|| self.summary.is_some()
+			|| self.older_than.is_some()
+			|| self.newer_than.is_some()
+	}
+
+	pub fn matches(&self, conf: &Confirmation, now: u64) -> bool {
+		if !self.types.is_empty() && !self.types.contains(&conf.conf_type) {
+			return false;
+		}
+		if !self.creator_ids.is_empty() && !self.creator_ids.contains(&conf.creator_id) {
+			return false;
+		}
+		if !self.ids.is_empty() && !self.ids.contains(&conf.id) {
+			return false;
+		}
+		if let Some(headline) = &self.headline {
+			if !headline.is_match(&conf.headline) {
+				return false;
+			}
+		}
+		if let Some(summary) = &self.summary {
+			if !conf.summary.iter().any(|line| summary.is_match(line)) {
+				return false;
+			}
+		}
+		let age = Duration::from_secs(now.saturating_sub(conf.creation_time));
+		if let Some(older_than) = self.older_than {
+			if age < older_than {
+				return false;
+			}
+		}
+		if let Some(newer_than) = self.newer_than {
+			if age > newer_than {
+				return false;
+			}
+		}
+		true
+	}
+}
+
+fn de_confirmation_types<'de, D>(deserializer: D) -> Result<Vec<ConfirmationType>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum NameOrNumber {
+		Name(String),
+		Number(u32),
+	}
+
+	Vec::<NameOrNumber>::deserialize(deserializer)?
+		.into_iter()
+		.map(|t| match t {
+			NameOrNumber::Name(name) => name.parse().map_err(serde::de::Error::custom),
+			NameOrNumber::Number(n) => Ok(n.into()),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn confirmation(conf_type: ConfirmationType, creation_time: u64) -> Confirmation {
+		Confirmation {
+			id: "1234".to_owned(),
+			nonce: "12345".to_owned(),
+			conf_type,
+			creator_id: "09870987".to_owned(),
+			headline: "Sell - Mann Co. Supply Crate Key".into(),
+			type_name: "Market Sell".to_owned(),
+			creation_time,
+			cancel: "Cancel".to_owned(),
+			accept: "Confirm".to_owned(),
+			icon: None,
+			multi: false,
+			summary: vec!["2,50€".to_owned(), "You receive 2,18€".to_owned()],
+		}
+	}
+
+	fn parse(json: &str) -> ConfirmationRules {
+		ConfirmationRules::parse(json).unwrap()
+	}
+
+	#[test]
+	fn test_first_matching_rule_wins() {
+		let rules = parse(
+			r#"{"rules": [
+				{"action": "deny", "types": ["trade"]},
+				{"action": "accept", "types": ["market-sell", 2]}
+			]}"#,
+		);
+		let conf = confirmation(ConfirmationType::Trade, 100);
+		assert_eq!(rules.decide(&conf, 100), Some((0, RuleAction::Deny)));
+		let conf = confirmation(ConfirmationType::MarketSell, 100);
+		assert_eq!(rules.decide(&conf, 100), Some((1, RuleAction::Accept)));
+		let conf = confirmation(ConfirmationType::ApiKeyCreation, 100);
+		assert_eq!(rules.decide(&conf, 100), None);
+	}
+
+	#[test]
+	fn test_match_text() {
+		let rules = parse(
+			r#"{"rules": [
+				{"action": "accept", "headline": "^Sell", "summary": "receive"}
+			]}"#,
+		);
+		let mut conf = confirmation(ConfirmationType::MarketSell, 100);
+		assert!(rules.decide(&conf, 100).is_some());
+		conf.summary = vec!["nope".to_owned()];
+		assert!(rules.decide(&conf, 100).is_none());
+	}
+
+	#[test]
+	fn test_match_creator_id() {
+		let rules = parse(r#"{"rules": [{"action": "accept", "creator_ids": ["09870987"]}]}"#);
+		let mut conf = confirmation(ConfirmationType::Trade, 100);
+		assert!(rules.decide(&conf, 100).is_some());
+		conf.creator_id = "1".to_owned();
+		assert!(rules.decide(&conf, 100).is_none());
+	}
+
+	#[test]
+	fn test_match_age() {
+		let rules =
+			parse(r#"{"rules": [{"action": "accept", "older_than": "1m", "newer_than": "1h"}]}"#);
+		let conf = confirmation(ConfirmationType::MarketSell, 1000);
+		assert!(rules.decide(&conf, 1030).is_none());
+		assert!(rules.decide(&conf, 1060).is_some());
+		assert!(rules.decide(&conf, 1000 + 3600).is_some());
+		assert!(rules.decide(&conf, 1000 + 3601).is_none());
+	}
+
+	#[test]
+	fn test_reject_invalid_rules() {
+		assert!(serde_json::from_str::<ConfirmationRules>(
+			r#"{"rules": [{"action": "accept", "types": ["bogus"]}]}"#
+		)
+		.is_err());
+		assert!(
+			serde_json::from_str::<ConfirmationRules>(r#"{"rules": [{"action": "maybe"}]}"#)
+				.is_err()
+		);
+		assert!(serde_json::from_str::<ConfirmationRules>(
+			r#"{"rules": [{"action": "accept", "headline": "("}]}"#
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn test_reject_unknown_fields() {
+		assert!(ConfirmationRules::parse(
+			r#"{"rules": [{"action": "accept", "type": ["trade"]}]}"#
+		)
+		.is_err());
+		assert!(ConfirmationRules::parse(r#"{"rules": [], "rule": []}"#).is_err());
+	}
+
+	#[test]
+	fn test_reject_accept_without_conditions() {
+		assert!(ConfirmationRules::parse(r#"{"rules": [{"action": "accept"}]}"#).is_err());
+		assert!(ConfirmationRules::parse(r#"{"rules": [{"action": "deny"}]}"#).is_ok());
+	}
+}
@@ -0,0 +1,409 @@
+use std::{net::IpAddr, path::Path, str::FromStr};
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use steamguard::protobufs::steammessages_auth_steamclient::{
+	CAuthentication_GetAuthSessionInfo_Response, EAuthTokenPlatformType,
+};
+
+use super::de_regex;
+
+/// What to do with a login session that matched a rule. Defaults to `Deny`, so a rule that forgets to set it never approves anything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionRuleAction {
+	Approve,
+	#[default]
+	Deny,
+}
+
+/// A set of rules for pending login sessions, loaded from a JSON file.
+///
+/// ```json
+/// {
+///     "rules": [
+///         { "action": "deny", "countries": ["KP"] },
+///         { "action": "approve", "ips": ["10.0.0.0/8", "2001:db8::/32"], "platforms": ["web_browser"] },
+///         { "action": "approve", "countries": ["US", "CA"], "device_name": "^ci-runner-" }
+///     ]
+/// }
+/// ```
+///
+/// Rules are evaluated in order, and the first matching rule wins. Sessions that don't match any rule are skipped.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionRules {
+	pub rules: Vec<SessionRule>,
+}
+
+/// A single rule. Every condition that is specified must match for the rule to apply.
+///
+/// If Steam doesn't report a usable IP address or country for a session, deny rules that check them still match, so missing information never gets a session past a deny rule.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionRule {
+	pub action: SessionRuleAction,
+	/// IP addresses or CIDR ranges, IPv4 or IPv6.
+	#[serde(default, deserialize_with = "de_parse_list")]
+	pub ips: Vec<IpRange>,
+	/// Country codes as reported by Steam, eg. `US`. Case-insensitive.
+	#[serde(default)]
+	pub countries: Vec<String>,
+	#[serde(default, deserialize_with = "de_parse_list")]
+	pub platforms: Vec<SessionPlatform>,
+	/// Matched against the device friendly name.
+	#[serde(default, deserialize_with = "de_regex")]
+	pub device_name: Option<Regex>,
+}
+
+impl SessionRules {
+	pub fn load(path: &Path) -> anyhow::Result<Self> {
+		let text = std::fs::read_to_string(path)?;
+		Self::parse(&text)
+	}
+
+	fn parse(text: &str) -> anyhow::Result<Self> {
+		let mut deser = serde_json::Deserializer::from_str(text);
+		let rules: Self = serde_path_to_error::deserialize(&mut deser)?;
+		for (i, rule) in rules.rules.iter().enumerate() {
+			ensure!(
+				rule.action != SessionRuleAction::Approve || rule.has_conditions(),
+				"Rule {} approves every session, give it at least one of ips, countries, platforms or device_name",
+				i
+			);
+		}
+		Ok(rules)
+	}
+
+	/// Find the first rule that matches the session. Returns the index of the rule and the action to take.
+	pub fn decide(
+		&self,
+		session: &CAuthentication_GetAuthSessionInfo_Response,
+	) -> Option<(usize, SessionRuleAction)> {
+		self.rules
+			.iter()
+			.enumerate()
+			.find(|(_, rule)| rule.matches(session))
+			.map(|(i, rule)| (i, rule.action))
+	}
+}
+
+impl SessionRule {
+	/// Whether any condition is specified. A rule without conditions matches every session.
+	pub fn has_conditions(&self) -> bool {
+		!self.ips.is_empty()
+			|| !self.countries.is_empty()
+			|| !self.platforms.is_empty()
+			|| self.device_name.is_some()
+	}
+
+	pub fn matches(&self, session: &CAuthentication_GetAuthSessionInfo_Response) -> bool {
+		let deny = self.action == SessionRuleAction::Deny;
+		if !self.ips.is_empty() {
+			match session.ip().parse::<IpAddr>() {
+				Ok(ip) => {
+					if !self.ips.iter().any(|range| range.contains(ip)) {
+						return false;
+					}
+				}
+				Err(_) if deny => {}
+				Err(_) => return false,
+			}
+		}
+		if !self.countries.is_empty() {
+			let country = session.country();
+			if country.is_empty() {
+				if !deny {
+					return false;
+				}
+			} else if !self
+				.countries
+				.iter()
+				.any(|c| c.eq_ignore_ascii_case(country))
+			{
+				return false;
+			}
+		}
+		if !self.platforms.is_empty()
+			&& !self
+				.platforms
+				.contains(&SessionPlatform::from(session.platform_type()))
+		{
+			return false;
+		}
+		if let Some(device_name) = &self.device_name {
+			if !device_name.is_match(session.device_friendly_name()) {
+				return false;
+			}
+		}
+		true
+	}
+}
+
+/// The platform that a login session was started from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPlatform {
+	Unknown,
+	SteamClient,
+	WebBrowser,
+	MobileApp,
+}
+
+impl From<EAuthTokenPlatformType> for SessionPlatform {
+	fn from(platform: EAuthTokenPlatformType) -> Self {
+		match platform {
+			EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown => Self::Unknown,
+			EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient => Self::SteamClient,
+			EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser => Self::WebBrowser,
+			EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => Self::MobileApp,
+		}
+	}
+}
+
+impl FromStr for SessionPlatform {
+	type Err = anyhow::Error;
+
+	/// Parses a platform from its name, eg. `web_browser` or `web-browser`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.to_lowercase().replace('-', "_").as_str() {
+			"unknown" => Self::Unknown,
+			"steam_client" => Self::SteamClient,
+			"web_browser" => Self::WebBrowser,
+			"mobile_app" => Self::MobileApp,
+			_ => bail!(
+				"Unknown platform: {}, expected one of steam_client, web_browser, mobile_app, unknown",
+				s
+			),
+		})
+	}
+}
+
+impl std::fmt::Display for SessionPlatform {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Unknown => "Unknown",
+			Self::SteamClient => "Steam Client",
+			Self::WebBrowser => "Web Browser",
+			Self::MobileApp => "Mobile App",
+		})
+	}
+}
+
+/// An IP address, or a range of them in CIDR notation like `10.0.0.0/8` or `2001:db8::/32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+	addr: IpAddr,
+	prefix_len: u8,
+}
+
+impl IpRange {
+	pub fn contains(&self, ip: IpAddr) -> bool {
+		// Steam may report IPv4 addresses mapped to IPv6, eg. `::ffff:10.0.0.1`
+		match (self.addr, ip.to_canonical()) {
+			(IpAddr::V4(net), IpAddr::V4(ip)) => {
+				let mask = u32::MAX
+					.checked_shl(32 - self.prefix_len as u32)
+					.unwrap_or(0);
+				u32::from(net) & mask == u32::from(ip) & mask
+			}
+			(IpAddr::V6(net), IpAddr::V6(ip)) => {
+				let mask = u128::MAX
+					.checked_shl(128 - self.prefix_len as u32)
+					.unwrap_or(0);
+				u128::from(net) & mask == u128::from(ip) & mask
+			}
+			_ => false,
+		}
+	}
+}
+
+impl FromStr for IpRange {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (addr, prefix_len) = match s.split_once('/') {
+			Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+			None => (s, None),
+		};
+		let addr: IpAddr = addr
+			.parse()
+			.map_err(|_| anyhow!("Invalid IP address: {}", s))?;
+		let max_len = if addr.is_ipv4() { 32 } else { 128 };
+		let prefix_len = match prefix_len {
+			Some(len) => len
+				.parse::<u8>()
+				.ok()
+				.filter(|len| *len <= max_len)
+				.ok_or_else(|| anyhow!("Invalid prefix length in {}", s))?,
+			None => max_len,
+		};
+		Ok(Self { addr, prefix_len })
+	}
+}
+
+impl std::fmt::Display for IpRange {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}/{}", self.addr, self.prefix_len)
+	}
+}
+
+fn de_parse_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+	D: Deserializer<'de>,
+	T: FromStr<Err = anyhow::Error>,
+{
+	Vec::<String>::deserialize(deserializer)?
+		.iter()
+		.map(|s| s.parse().map_err(serde::de::Error::custom))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn session(
+		ip: &str,
+		country: &str,
+		platform: EAuthTokenPlatformType,
+		device_name: &str,
+	) -> CAuthentication_GetAuthSessionInfo_Response {
+		let mut session = CAuthentication_GetAuthSessionInfo_Response::new();
+		session.set_ip(ip.to_owned());
+		session.set_country(country.to_owned());
+		session.set_platform_type(platform);
+		session.set_device_friendly_name(device_name.to_owned());
+		session
+	}
+
+	fn parse(json: &str) -> SessionRules {
+		SessionRules::parse(json).unwrap()
+	}
+
+	#[test]
+	fn test_ip_range() {
+		let range: IpRange = "10.1.0.0/16".parse().unwrap();
+		assert!(range.contains("10.1.2.3".parse().unwrap()));
+		assert!(range.contains("::ffff:10.1.2.3".parse().unwrap()));
+		assert!(!range.contains("10.2.0.1".parse().unwrap()));
+		assert!(!range.contains("2001:db8::1".parse().unwrap()));
+
+		let range: IpRange = "2001:db8::/32".parse().unwrap();
+		assert!(range.contains("2001:db8:1::1".parse().unwrap()));
+		assert!(!range.contains("2001:db9::1".parse().unwrap()));
+
+		let range: IpRange = "192.168.1.5".parse().unwrap();
+		assert!(range.contains("192.168.1.5".parse().unwrap()));
+		assert!(!range.contains("192.168.1.6".parse().unwrap()));
+
+		let range: IpRange = "0.0.0.0/0".parse().unwrap();
+		assert!(range.contains("8.8.8.8".parse().unwrap()));
+
+		assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+		assert!("::/129".parse::<IpRange>().is_err());
+		assert!("example.com".parse::<IpRange>().is_err());
+	}
+
+	#[test]
+	fn test_first_matching_rule_wins() {
+		let rules = parse(
+			r#"{"rules": [
+				{"action": "deny", "countries": ["kp"]},
+				{"action": "approve", "ips": ["10.0.0.0/8", "2001:db8::/32"], "platforms": ["web_browser"]}
+			]}"#,
+		);
+		let browser = EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser;
+		assert_eq!(
+			rules.decide(&session("10.0.0.1", "KP", browser, "")),
+			Some((0, SessionRuleAction::Deny))
+		);
+		assert_eq!(
+			rules.decide(&session("2001:db8::1", "US", browser, "")),
+			Some((1, SessionRuleAction::Approve))
+		);
+		assert_eq!(rules.decide(&session("11.0.0.1", "US", browser, "")), None);
+		let client = EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient;
+		assert_eq!(rules.decide(&session("10.0.0.1", "US", client, "")), None);
+	}
+
+	#[test]
+	fn test_match_device_name() {
+		let rules =
+			parse(r#"{"rules": [{"action": "approve", "device_name": "^ci-runner-\\d+$"}]}"#);
+		let platform = EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient;
+		assert!(rules
+			.decide(&session("10.0.0.1", "US", platform, "ci-runner-12"))
+			.is_some());
+		assert!(rules
+			.decide(&session("10.0.0.1", "US", platform, "my laptop"))
+			.is_none());
+	}
+
+	#[test]
+	fn test_reject_invalid_rules() {
+		assert!(serde_json::from_str::<SessionRules>(
+			r#"{"rules": [{"action": "approve", "ips": ["10.0.0.0/40"]}]}"#
+		)
+		.is_err());
+		assert!(serde_json::from_str::<SessionRules>(
+			r#"{"rules": [{"action": "approve", "platforms": ["toaster"]}]}"#
+		)
+		.is_err());
+		assert!(
+			serde_json::from_str::<SessionRules>(r#"{"rules": [{"action": "accept"}]}"#).is_err()
+		);
+	}
+
+	#[test]
+	fn test_reject_unknown_fields() {
+		assert!(
+			SessionRules::parse(r#"{"rules": [{"action": "approve", "ip": ["10.0.0.0/8"]}]}"#)
+				.is_err()
+		);
+		assert!(SessionRules::parse(r#"{"rules": [], "rule": []}"#).is_err());
+	}
+
+	#[test]
+	fn test_reject_approve_without_conditions() {
+		assert!(SessionRules::parse(r#"{"rules": [{"action": "approve"}]}"#).is_err());
+		assert!(SessionRules::parse(r#"{"rules": [{"action": "deny"}]}"#).is_ok());
+	}
+
+	#[test]
+	fn test_default_rule_denies() {
+		let rules = SessionRules {
+			rules: vec![SessionRule::default()],
+		};
+		let browser = EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser;
+		assert_eq!(
+			rules.decide(&session("10.0.0.1", "US", browser, "")),
+			Some((0, SessionRuleAction::Deny))
+		);
+	}
+
+	#[test]
+	fn test_deny_rules_match_missing_info() {
+		let rules = parse(
+			r#"{"rules": [
+				{"action": "deny", "countries": ["KP"]},
+				{"action": "deny", "ips": ["203.0.113.0/24"]},
+				{"action": "approve", "ips": ["0.0.0.0/0"]},
+				{"action": "approve", "countries": ["US"]}
+			]}"#,
+		);
+		let browser = EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser;
+		assert_eq!(
+			rules.decide(&session("10.0.0.1", "", browser, "")),
+			Some((0, SessionRuleAction::Deny))
+		);
+		assert_eq!(
+			rules.decide(&session("", "US", browser, "")),
+			Some((1, SessionRuleAction::Deny))
+		);
+		assert_eq!(
+			rules.decide(&session("10.0.0.1", "US", browser, "")),
+			Some((2, SessionRuleAction::Approve))
+		);
+	}
+}
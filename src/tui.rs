@@ -9,9 +9,13 @@ use crossterm::{
 };
 use log::debug;
 use secrecy::SecretString;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{stderr, stdout, Write};
-use steamguard::Confirmation;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use steamguard::{Confirmation, ConfirmationDetails, SteamGuardAccount};
+
+use crate::output::CODE_PERIOD;
 
 /// Prompt the user for text input.
 pub(crate) fn prompt() -> String {
@@ -30,13 +34,11 @@ pub(crate) fn prompt() -> String {
 				eprint!("{}", c);
 				let _ = stderr().flush();
 			}
-			KeyCode::Backspace => {
-				if !line.is_empty() {
-					line.pop();
+			KeyCode::Backspace if !line.is_empty() => {
+				line.pop();
 
-					eprint!("\x08 \x08");
-					let _ = stderr().flush();
-				}
+				eprint!("\x08 \x08");
+				let _ = stderr().flush();
 			}
 			_ => {}
 		}
@@ -107,13 +109,29 @@ fn prompt_char_impl(input: impl Into<String>, chars: &str) -> anyhow::Result<cha
 }
 
 /// Returns a tuple of (accepted, denied). Ignored confirmations are not included.
+///
+/// `fetch_details` is called on a background thread the first time the details of a confirmation are shown, so the menu stays responsive while they load.
 pub(crate) fn prompt_confirmation_menu(
 	confirmations: Vec<Confirmation>,
+	fetch_details: impl Fn(&Confirmation) -> anyhow::Result<ConfirmationDetails> + Sync,
 ) -> anyhow::Result<(Vec<Confirmation>, Vec<Confirmation>)> {
 	if confirmations.is_empty() {
 		return Ok((vec![], vec![]));
 	}
 
+	std::thread::scope(|scope| confirmation_menu(scope, &confirmations, &fetch_details))
+}
+
+type DetailsResult = Result<ConfirmationDetails, String>;
+
+fn confirmation_menu<'scope, 'env, F>(
+	scope: &'scope std::thread::Scope<'scope, 'env>,
+	confirmations: &'env [Confirmation],
+	fetch_details: &'env F,
+) -> anyhow::Result<(Vec<Confirmation>, Vec<Confirmation>)>
+where
+	F: Fn(&Confirmation) -> anyhow::Result<ConfirmationDetails> + Sync,
+{
 	let mut to_accept_idx: HashSet<usize> = HashSet::new();
 	let mut to_deny_idx: HashSet<usize> = HashSet::new();
 
@@ -121,6 +139,10 @@ pub(crate) fn prompt_confirmation_menu(
 	crossterm::terminal::enable_raw_mode()?;
 
 	let mut selected_idx = 0;
+	let mut show_details = false;
+	// `None` while the details are still loading
+	let mut details: HashMap<usize, Option<DetailsResult>> = HashMap::new();
+	let (details_tx, details_rx) = std::sync::mpsc::channel::<(usize, DetailsResult)>();
 
 	loop {
 		execute!(
@@ -128,7 +150,7 @@ pub(crate) fn prompt_confirmation_menu(
 			Clear(ClearType::All),
 			cursor::MoveTo(1, 1),
 			PrintStyledContent(
-				"arrow keys to select, [a]ccept, [d]eny, [i]gnore, [enter] confirm choices\n\n"
+				"arrow keys to select, [a]ccept, [d]eny, [i]gnore, [v]iew details, [enter] confirm choices\n\n"
 					.white()
 			),
 		)?;
@@ -160,8 +182,47 @@ pub(crate) fn prompt_confirmation_menu(
 			stdout().queue(Print(format!(" {}\n", conf.description())))?;
 		}
 
+		if show_details {
+			let selected = details.entry(selected_idx).or_insert_with(|| {
+				let idx = selected_idx;
+				let conf = &confirmations[idx];
+				let tx = details_tx.clone();
+				scope.spawn(move || {
+					let result = fetch_details(conf).map_err(|err| err.to_string());
+					// the menu may already be closed
+					let _ = tx.send((idx, result));
+				});
+				None
+			});
+			let lines = match selected {
+				None => vec![(Color::DarkGrey, "Loading details...".to_owned())],
+				Some(Ok(details)) => details_lines(details),
+				Some(Err(err)) => vec![(Color::Red, format!("Failed to get details: {}", err))],
+			};
+			stdout().queue(SetForegroundColor(Color::White))?;
+			stdout().queue(Print("\r\n"))?;
+			for (color, line) in lines {
+				stdout().queue(SetForegroundColor(color))?;
+				stdout().queue(Print(format!("\r   {}\n", line)))?;
+			}
+		}
+
 		stdout().flush()?;
 
+		// redraw when details finish loading, without waiting for a key press
+		let loaded = loop {
+			if crossterm::event::poll(Duration::from_millis(100))? {
+				break false;
+			}
+			if let Ok((idx, result)) = details_rx.try_recv() {
+				details.insert(idx, Some(result));
+				break true;
+			}
+		};
+		if loaded {
+			continue;
+		}
+
 		match crossterm::event::read()? {
 			Event::Resize(_, _) => continue,
 			Event::Key(KeyEvent {
@@ -185,6 +246,12 @@ pub(crate) fn prompt_confirmation_menu(
 				to_accept_idx.remove(&selected_idx);
 				to_deny_idx.remove(&selected_idx);
 			}
+			Event::Key(KeyEvent {
+				code: KeyCode::Char('v'),
+				..
+			}) => {
+				show_details = !show_details;
+			}
 			Event::Key(KeyEvent {
 				code: KeyCode::Char('A'),
 				..
@@ -257,6 +324,190 @@ pub(crate) fn prompt_confirmation_menu(
 	))
 }
 
+/// Lines to show in the details pane of the confirmation menu.
+fn details_lines(details: &ConfirmationDetails) -> Vec<(Color, String)> {
+	/// Details pages can contain a lot of boilerplate, so only show the start.
+	const MAX_TEXT_LINES: usize = 20;
+
+	let mut lines = vec![];
+	if let Some(trade) = &details.trade {
+		let mut partner = trade
+			.partner
+			.name
+			.clone()
+			.unwrap_or_else(|| "unknown partner".into());
+		if let Some(level) = trade.partner.level {
+			partner += &format!(" (level {})", level);
+		}
+		if let Some(steam_id) = trade.partner.steam_id {
+			partner += &format!(" [{}]", steam_id);
+		}
+		lines.push((Color::White, format!("Trade with {}", partner)));
+		for (header, items) in [
+			("You give:", &trade.items_given),
+			("You receive:", &trade.items_received),
+		] {
+			lines.push((Color::White, header.to_owned()));
+			if items.is_empty() {
+				lines.push((Color::DarkGrey, "  nothing".to_owned()));
+			}
+			for item in items {
+				lines.push((Color::Cyan, format!("  - {}", item)));
+			}
+		}
+	}
+	lines.extend(
+		details
+			.text
+			.iter()
+			.take(MAX_TEXT_LINES)
+			.map(|line| (Color::White, line.clone())),
+	);
+	if details.text.len() > MAX_TEXT_LINES {
+		lines.push((
+			Color::DarkGrey,
+			format!("... {} more lines", details.text.len() - MAX_TEXT_LINES),
+		));
+	}
+	for warning in &details.warnings {
+		lines.push((Color::Red, format!("! {}", warning)));
+	}
+	if lines.is_empty() {
+		lines.push((Color::DarkGrey, "No details available".to_owned()));
+	}
+	lines
+}
+
+/// Show a live view of the current code for each account, with a countdown to the next code, until the user quits.
+///
+/// `offset` is the number of seconds to add to the local clock to get Steam's time.
+pub(crate) fn watch_codes(
+	accounts: &[Arc<Mutex<SteamGuardAccount>>],
+	offset: i64,
+) -> anyhow::Result<()> {
+	if accounts.is_empty() {
+		return Ok(());
+	}
+
+	execute!(stdout(), EnterAlternateScreen, cursor::Hide)?;
+	crossterm::terminal::enable_raw_mode()?;
+	let result = watch_codes_loop(accounts, offset);
+	crossterm::terminal::disable_raw_mode()?;
+	execute!(stdout(), cursor::Show, LeaveAlternateScreen)?;
+	result
+}
+
+fn watch_codes_loop(accounts: &[Arc<Mutex<SteamGuardAccount>>], offset: i64) -> anyhow::Result<()> {
+	let names: Vec<String> = accounts
+		.iter()
+		.map(|a| a.lock().unwrap().account_name.clone())
+		.collect();
+	let name_width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+	let period_ms = CODE_PERIOD * 1000;
+
+	let mut selected_idx = 0;
+	let mut status = String::new();
+	loop {
+		let now_ms =
+			SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64 + offset * 1000;
+		let time = now_ms as u64 / 1000;
+		let remaining_ms = period_ms - now_ms as u64 % period_ms;
+		let codes: Vec<String> = accounts
+			.iter()
+			.map(|a| a.lock().unwrap().generate_code(time))
+			.collect();
+
+		execute!(
+			stdout(),
+			cursor::MoveTo(1, 1),
+			Clear(ClearType::FromCursorDown),
+			PrintStyledContent(
+				"arrow keys to select, [c]opy code to clipboard, [q]uit\n\n".white()
+			),
+		)?;
+		for (i, (name, code)) in names.iter().zip(&codes).enumerate() {
+			stdout().queue(Print("\r"))?;
+			if selected_idx == i {
+				stdout().queue(SetForegroundColor(Color::Yellow))?;
+				stdout().queue(Print(" >"))?;
+			} else {
+				stdout().queue(SetForegroundColor(Color::White))?;
+				stdout().queue(Print("  "))?;
+			}
+			stdout().queue(Print(format!(" {:<name_width$}  ", name)))?;
+			stdout().queue(SetForegroundColor(Color::Green))?;
+			stdout().queue(Print(format!("{}\n", code)))?;
+		}
+		let bar_color = if remaining_ms <= 5000 {
+			Color::Red
+		} else {
+			Color::Cyan
+		};
+		stdout().queue(Print("\r\n "))?;
+		stdout().queue(SetForegroundColor(bar_color))?;
+		stdout().queue(Print(countdown_bar(remaining_ms, period_ms, 30)))?;
+		stdout().queue(SetForegroundColor(Color::White))?;
+		stdout().queue(Print(format!(
+			" {:>2}s\r\n\n {}",
+			remaining_ms.div_ceil(1000),
+			status
+		)))?;
+		stdout().flush()?;
+
+		if !crossterm::event::poll(Duration::from_millis(100))? {
+			continue;
+		}
+		match crossterm::event::read()? {
+			Event::Key(KeyEvent {
+				code: KeyCode::Up, ..
+			}) => {
+				selected_idx = selected_idx.saturating_sub(1);
+			}
+			Event::Key(KeyEvent {
+				code: KeyCode::Down,
+				..
+			}) => {
+				selected_idx = (selected_idx + 1).min(accounts.len() - 1);
+			}
+			Event::Key(KeyEvent {
+				code: KeyCode::Char('c') | KeyCode::Enter,
+				modifiers: KeyModifiers::NONE,
+			}) => {
+				copy_to_clipboard(&codes[selected_idx])?;
+				status = format!("Copied code for {}", names[selected_idx]);
+			}
+			Event::Key(KeyEvent {
+				code: KeyCode::Char('q') | KeyCode::Esc,
+				..
+			})
+			| Event::Key(KeyEvent {
+				code: KeyCode::Char('c'),
+				modifiers: KeyModifiers::CONTROL,
+			}) => {
+				return Ok(());
+			}
+			_ => {}
+		}
+	}
+}
+
+/// A bar that empties as the current code gets closer to expiring.
+fn countdown_bar(remaining_ms: u64, period_ms: u64, width: usize) -> String {
+	let filled = (remaining_ms as usize * width)
+		.div_ceil(period_ms as usize)
+		.min(width);
+	format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+/// Copy text to the clipboard with an OSC 52 escape sequence, so it works over SSH without any clipboard tools installed. Not every terminal supports this.
+fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+	use base64::Engine;
+	let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+	write!(stdout(), "\x1b]52;c;{}\x07", encoded)?;
+	stdout().flush()?;
+	Ok(())
+}
+
 pub(crate) fn pause() {
 	let _ = write!(stderr(), "Press enter to continue...");
 	let _ = stderr().flush();
@@ -282,6 +533,25 @@ pub(crate) fn prompt_passkey() -> anyhow::Result<SecretString> {
 	}
 }
 
+/// Prompt for a new passkey, asking for it twice to catch typos. `what` describes what the passkey is for, eg. "bundle".
+pub(crate) fn prompt_new_passkey(what: &str) -> anyhow::Result<SecretString> {
+	debug!("prompting for new {} passkey", what);
+	loop {
+		let passkey = rpassword::prompt_password(format!("Enter {} passkey: ", what))
+			.context("prompting for passkey")?;
+		if passkey.is_empty() {
+			log::error!("Passkey cannot be empty, try again.");
+			continue;
+		}
+		let confirm = rpassword::prompt_password(format!("Confirm {} passkey: ", what))
+			.context("prompting for passkey")?;
+		if passkey == confirm {
+			return Ok(SecretString::new(passkey));
+		}
+		log::error!("Passkeys do not match, try again.");
+	}
+}
+
 pub(crate) fn prompt_password() -> anyhow::Result<SecretString> {
 	debug!("prompting for password");
 	loop {
@@ -292,6 +562,67 @@ pub(crate) fn prompt_password() -> anyhow::Result<SecretString> {
 	}
 }
 
+#[cfg(test)]
+mod details_lines_tests {
+	use super::*;
+
+	#[test]
+	fn test_details_lines() {
+		let lines = |text: Vec<String>| -> Vec<String> {
+			details_lines(&ConfirmationDetails {
+				text,
+				..Default::default()
+			})
+			.into_iter()
+			.map(|(_, line)| line)
+			.collect()
+		};
+		assert_eq!(lines(vec![]), vec!["No details available"]);
+		assert_eq!(
+			lines(vec!["You will give up".into(), "AK-47".into()]),
+			vec!["You will give up", "AK-47"]
+		);
+		let long = lines((0..25).map(|i| i.to_string()).collect());
+		assert_eq!(long.len(), 21);
+		assert_eq!(long[20], "... 5 more lines");
+
+		let trade = details_lines(&ConfirmationDetails::parse(
+			r#"<div class="mobileconf_trade_warning">Trade hold</div>
+			<div class="tradeoffer" id="tradeofferid_1">
+				<div class="tradeoffer_items primary">
+					<div class="tradeoffer_items_header">You offered:</div>
+					<div class="trade_item" data-economy-item="classinfo/440/1/2"></div>
+				</div>
+			</div>"#,
+		));
+		let trade: Vec<_> = trade.into_iter().map(|(_, line)| line).collect();
+		assert_eq!(
+			trade,
+			vec![
+				"Trade with unknown partner",
+				"You give:",
+				"  - app 440 class 1",
+				"You receive:",
+				"  nothing",
+				"! Trade hold",
+			]
+		);
+	}
+}
+
+#[cfg(test)]
+mod countdown_bar_tests {
+	use super::*;
+
+	#[test]
+	fn test_countdown_bar() {
+		assert_eq!(countdown_bar(30000, 30000, 10), "██████████");
+		assert_eq!(countdown_bar(15000, 30000, 10), "█████░░░░░");
+		assert_eq!(countdown_bar(100, 30000, 10), "█░░░░░░░░░");
+		assert_eq!(countdown_bar(0, 30000, 10), "░░░░░░░░░░");
+	}
+}
+
 #[cfg(test)]
 mod prompt_char_tests {
 	use super::*;
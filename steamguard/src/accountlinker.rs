@@ -1,6 +1,8 @@
 use crate::protobufs::service_twofactor::{
-	CTwoFactor_AddAuthenticator_Request, CTwoFactor_FinalizeAddAuthenticator_Request,
+	CTwoFactor_AddAuthenticator_Request, CTwoFactor_AddAuthenticator_Response,
+	CTwoFactor_FinalizeAddAuthenticator_Request,
 	CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Request,
+	CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Response,
 	CTwoFactor_RemoveAuthenticatorViaChallengeStart_Request,
 	CTwoFactor_RemoveAuthenticator_Request, CTwoFactor_Status_Request, CTwoFactor_Status_Response,
 };
@@ -49,15 +51,7 @@ where
 			.context("decoding access token")?
 			.steam_id();
 
-		let mut req = CTwoFactor_AddAuthenticator_Request::new();
-		req.set_authenticator_type(1);
-		req.set_steamid(steam_id);
-		req.set_sms_phone_id("1".to_owned());
-		req.set_device_identifier(self.device_id.clone());
-		// Currently, the version value determines what `EAuthSessionGuardType` values are allowed during the login process.
-		// Version 2 allows `EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation`, where version 1 does not.
-		// However, the device confirmation auth guard does not emit a typical 2fa confirmation, so it doesn't show up when running `steamguard confirm`.
-		req.set_version(2);
+		let req = build_add_authenticator_request(steam_id, &self.device_id);
 
 		let resp = self
 			.client
@@ -68,32 +62,12 @@ where
 			return Err(resp.result.into());
 		}
 
-		let mut resp = resp.into_response_data();
-
-		let account = SteamGuardAccount {
-			account_name: resp.take_account_name(),
+		Ok(AccountLinkSuccess::new(
+			resp.into_response_data(),
 			steam_id,
-			serial_number: resp.serial_number().to_string(),
-			revocation_code: resp.take_revocation_code().into(),
-			uri: resp.take_uri().into(),
-			shared_secret: TwoFactorSecret::from_bytes(resp.take_shared_secret()),
-			token_gid: resp.take_token_gid(),
-			identity_secret: base64::engine::general_purpose::STANDARD
-				.encode(resp.take_identity_secret())
-				.into(),
-			device_id: self.device_id.clone(),
-			secret_1: base64::engine::general_purpose::STANDARD
-				.encode(resp.take_secret_1())
-				.into(),
-			tokens: Some(self.tokens.clone()),
-		};
-		let success = AccountLinkSuccess {
-			account,
-			server_time: resp.server_time(),
-			phone_number_hint: resp.take_phone_number_hint(),
-			confirm_type: resp.confirm_type().into(),
-		};
-		Ok(success)
+			&self.device_id,
+			&self.tokens,
+		))
 	}
 
 	/// You may have to call this multiple times. If you have to call it a bunch of times, then you can assume that you are unable to generate correct 2fa codes.
@@ -103,19 +77,10 @@ where
 		account: &mut SteamGuardAccount,
 		confirm_code: String,
 	) -> Result<(), FinalizeLinkError> {
-		let code = account.generate_code(time);
-
-		let token = self.tokens.access_token();
-		let steam_id = account.steam_id;
-
-		let mut req = CTwoFactor_FinalizeAddAuthenticator_Request::new();
-		req.set_steamid(steam_id);
-		req.set_authenticator_code(code);
-		req.set_authenticator_time(time);
-		req.set_activation_code(confirm_code);
-		req.set_validate_sms_code(true);
-
-		let resp = self.client.finalize_authenticator(req, token)?;
+		let req = build_finalize_request(time, account, confirm_code);
+		let resp = self
+			.client
+			.finalize_authenticator(req, self.tokens.access_token())?;
 
 		if resp.result != EResult::OK {
 			return Err(resp.result.into());
@@ -204,18 +169,104 @@ where
 			.decode()
 			.context("decoding access token")?
 			.steam_id();
-		let mut req = CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Request::new();
-		req.set_sms_code(sms_code.as_ref().to_owned());
-		req.set_generate_new_token(true);
-		req.set_version(2); // Version has the same meaning as it does in AddAuthenticator Request, see `link()` above.
+		let req = build_transfer_continue_request(sms_code.as_ref());
 		let resp = self
 			.client
 			.remove_authenticator_via_challenge_continue(req, access_token)?;
 		if resp.result != EResult::OK {
 			return Err(resp.result.into());
 		}
-		let resp = resp.into_response_data();
-		let mut resp = resp.replacement_token.clone().unwrap();
+		Ok(account_from_replacement_token(
+			resp.into_response_data(),
+			steam_id,
+			&self.device_id,
+			&self.tokens,
+		))
+	}
+}
+
+pub(crate) fn build_add_authenticator_request(
+	steam_id: u64,
+	device_id: &str,
+) -> CTwoFactor_AddAuthenticator_Request {
+	let mut req = CTwoFactor_AddAuthenticator_Request::new();
+	req.set_authenticator_type(1);
+	req.set_steamid(steam_id);
+	req.set_sms_phone_id("1".to_owned());
+	req.set_device_identifier(device_id.to_owned());
+	// Currently, the version value determines what `EAuthSessionGuardType` values are allowed during the login process.
+	// Version 2 allows `EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation`, where version 1 does not.
+	// However, the device confirmation auth guard does not emit a typical 2fa confirmation, so it doesn't show up when running `steamguard confirm`.
+	req.set_version(2);
+	req
+}
+
+pub(crate) fn build_finalize_request(
+	time: u64,
+	account: &SteamGuardAccount,
+	confirm_code: String,
+) -> CTwoFactor_FinalizeAddAuthenticator_Request {
+	let mut req = CTwoFactor_FinalizeAddAuthenticator_Request::new();
+	req.set_steamid(account.steam_id);
+	req.set_authenticator_code(account.generate_code(time));
+	req.set_authenticator_time(time);
+	req.set_activation_code(confirm_code);
+	req.set_validate_sms_code(true);
+	req
+}
+
+pub(crate) fn build_transfer_continue_request(
+	sms_code: &str,
+) -> CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Request {
+	let mut req = CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Request::new();
+	req.set_sms_code(sms_code.to_owned());
+	req.set_generate_new_token(true);
+	req.set_version(2); // Version has the same meaning as it does in AddAuthenticator Request, see `build_add_authenticator_request()` above.
+	req
+}
+
+pub(crate) fn account_from_replacement_token(
+	resp: CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Response,
+	steam_id: u64,
+	device_id: &str,
+	tokens: &Tokens,
+) -> SteamGuardAccount {
+	let mut resp = resp.replacement_token.clone().unwrap();
+	SteamGuardAccount {
+		account_name: resp.take_account_name(),
+		steam_id,
+		serial_number: resp.serial_number().to_string(),
+		revocation_code: resp.take_revocation_code().into(),
+		uri: resp.take_uri().into(),
+		shared_secret: TwoFactorSecret::from_bytes(resp.take_shared_secret()),
+		token_gid: resp.take_token_gid(),
+		identity_secret: base64::engine::general_purpose::STANDARD
+			.encode(resp.take_identity_secret())
+			.into(),
+		device_id: device_id.to_owned(),
+		secret_1: base64::engine::general_purpose::STANDARD
+			.encode(resp.take_secret_1())
+			.into(),
+		tokens: Some(tokens.clone()),
+		credentials: None,
+	}
+}
+
+#[derive(Debug)]
+pub struct AccountLinkSuccess {
+	account: SteamGuardAccount,
+	server_time: u64,
+	phone_number_hint: String,
+	confirm_type: AccountLinkConfirmType,
+}
+
+impl AccountLinkSuccess {
+	pub(crate) fn new(
+		mut resp: CTwoFactor_AddAuthenticator_Response,
+		steam_id: u64,
+		device_id: &str,
+		tokens: &Tokens,
+	) -> Self {
 		let account = SteamGuardAccount {
 			account_name: resp.take_account_name(),
 			steam_id,
@@ -227,25 +278,21 @@ where
 			identity_secret: base64::engine::general_purpose::STANDARD
 				.encode(resp.take_identity_secret())
 				.into(),
-			device_id: self.device_id.clone(),
+			device_id: device_id.to_owned(),
 			secret_1: base64::engine::general_purpose::STANDARD
 				.encode(resp.take_secret_1())
 				.into(),
-			tokens: Some(self.tokens.clone()),
+			tokens: Some(tokens.clone()),
+			credentials: None,
 		};
-		Ok(account)
+		Self {
+			account,
+			server_time: resp.server_time(),
+			phone_number_hint: resp.take_phone_number_hint(),
+			confirm_type: resp.confirm_type().into(),
+		}
 	}
-}
-
-#[derive(Debug)]
-pub struct AccountLinkSuccess {
-	account: SteamGuardAccount,
-	server_time: u64,
-	phone_number_hint: String,
-	confirm_type: AccountLinkConfirmType,
-}
 
-impl AccountLinkSuccess {
 	pub fn account(&self) -> &SteamGuardAccount {
 		&self.account
 	}
@@ -285,7 +332,7 @@ impl From<i32> for AccountLinkConfirmType {
 	}
 }
 
-fn generate_device_id() -> String {
+pub(crate) fn generate_device_id() -> String {
 	format!("android:{}", uuid::Uuid::new_v4())
 }
 
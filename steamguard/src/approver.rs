@@ -80,17 +80,9 @@ where
 		challenge: Challenge,
 		persistence: ESessionPersistence,
 	) -> Result<(), ApproverError> {
-		debug!("building signature");
-		let signature = build_signature(&account.shared_secret, account.steam_id, &challenge);
+		let req = build_mobile_confirmation_request(account, &challenge, true, persistence);
 
 		debug!("approving login");
-		let mut req = CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request::new();
-		req.set_steamid(account.steam_id);
-		req.set_version(challenge.version.into());
-		req.set_client_id(challenge.client_id);
-		req.set_signature(signature.to_vec());
-		req.set_confirm(true);
-		req.set_persistence(persistence);
 
 		let resp = self
 			.client
@@ -118,19 +110,14 @@ where
 		account: &SteamGuardAccount,
 		challenge: Challenge,
 	) -> Result<(), ApproverError> {
-		debug!("building signature");
-		let signature = build_signature(&account.shared_secret, account.steam_id, &challenge);
+		let req = build_mobile_confirmation_request(
+			account,
+			&challenge,
+			false,
+			ESessionPersistence::k_ESessionPersistence_Persistent,
+		);
 
 		debug!("denying login");
-		let mut req = CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request::new();
-		req.set_steamid(account.steam_id);
-		req.set_version(challenge.version.into());
-		req.set_client_id(challenge.client_id);
-		req.set_signature(signature.to_vec());
-		req.set_confirm(false);
-		req.set_persistence(
-			crate::protobufs::enums::ESessionPersistence::k_ESessionPersistence_Persistent,
-		);
 
 		let resp = self
 			.client
@@ -144,6 +131,25 @@ where
 	}
 }
 
+pub(crate) fn build_mobile_confirmation_request(
+	account: &SteamGuardAccount,
+	challenge: &Challenge,
+	confirm: bool,
+	persistence: ESessionPersistence,
+) -> CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request {
+	debug!("building signature");
+	let signature = build_signature(&account.shared_secret, account.steam_id, challenge);
+
+	let mut req = CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request::new();
+	req.set_steamid(account.steam_id);
+	req.set_version(challenge.version.into());
+	req.set_client_id(challenge.client_id);
+	req.set_signature(signature.to_vec());
+	req.set_confirm(confirm);
+	req.set_persistence(persistence);
+	req
+}
+
 fn build_signature(
 	shared_secret: &TwoFactorSecret,
 	steam_id: u64,
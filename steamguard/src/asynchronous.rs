@@ -0,0 +1,20 @@
+//! Async versions of the Steam API clients. Requires the `async` feature.
+//!
+//! Everything in here mirrors its blocking counterpart in the crate root, and shares the same protobuf request and response types, as well as the same error types. Requests are sent with an [`AsyncTransport`], and [`AsyncWebApiTransport`] is the async equivalent of [`crate::transport::WebApiTransport`].
+//!
+//! [`crate::transport::MockTransport`] also implements [`AsyncTransport`], so it can be used to test code built on these types.
+
+mod accountlinker;
+mod approver;
+mod confirmation;
+mod refresher;
+pub mod steamapi;
+pub mod transport;
+mod userlogin;
+
+pub use accountlinker::AccountLinker;
+pub use approver::LoginApprover;
+pub use confirmation::Confirmer;
+pub use refresher::TokenRefresher;
+pub use transport::{AsyncTransport, AsyncWebApiTransport};
+pub use userlogin::UserLogin;
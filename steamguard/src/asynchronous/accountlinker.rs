@@ -0,0 +1,186 @@
+use anyhow::Context;
+
+use super::steamapi::TwoFactorClient;
+use super::AsyncTransport;
+use crate::accountlinker::{
+	account_from_replacement_token, build_add_authenticator_request, build_finalize_request,
+	build_transfer_continue_request, generate_device_id, AccountLinkSuccess,
+	RemoveAuthenticatorError, TransferError,
+};
+use crate::protobufs::service_twofactor::{
+	CTwoFactor_RemoveAuthenticatorViaChallengeStart_Request,
+	CTwoFactor_RemoveAuthenticator_Request, CTwoFactor_Status_Request, CTwoFactor_Status_Response,
+};
+use crate::transport::TransportError;
+use crate::{steamapi::EResult, token::Tokens, SteamGuardAccount};
+use crate::{AccountLinkError, FinalizeLinkError};
+
+/// Async version of [`crate::AccountLinker`].
+#[derive(Debug)]
+pub struct AccountLinker<T>
+where
+	T: AsyncTransport,
+{
+	device_id: String,
+	pub account: Option<SteamGuardAccount>,
+	pub finalized: bool,
+	tokens: Tokens,
+	client: TwoFactorClient<T>,
+}
+
+impl<T> AccountLinker<T>
+where
+	T: AsyncTransport,
+{
+	pub fn new(transport: T, tokens: Tokens) -> Self {
+		Self {
+			device_id: generate_device_id(),
+			account: None,
+			finalized: false,
+			tokens,
+			client: TwoFactorClient::new(transport),
+		}
+	}
+
+	pub fn tokens(&self) -> &Tokens {
+		&self.tokens
+	}
+
+	pub async fn link(&mut self) -> Result<AccountLinkSuccess, AccountLinkError> {
+		let access_token = self.tokens.access_token();
+		let steam_id = access_token
+			.decode()
+			.context("decoding access token")?
+			.steam_id();
+
+		let req = build_add_authenticator_request(steam_id, &self.device_id);
+		let resp = self
+			.client
+			.add_authenticator(req, access_token)
+			.await
+			.context("add authenticator request")?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		Ok(AccountLinkSuccess::new(
+			resp.into_response_data(),
+			steam_id,
+			&self.device_id,
+			&self.tokens,
+		))
+	}
+
+	/// You may have to call this multiple times, see [`crate::AccountLinker::finalize`].
+	pub async fn finalize(
+		&mut self,
+		time: u64,
+		account: &mut SteamGuardAccount,
+		confirm_code: String,
+	) -> Result<(), FinalizeLinkError> {
+		let req = build_finalize_request(time, account, confirm_code);
+		let resp = self
+			.client
+			.finalize_authenticator(req, self.tokens.access_token())
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		let resp = resp.into_response_data();
+
+		if resp.want_more() {
+			return Err(FinalizeLinkError::WantMore {
+				server_time: resp.server_time(),
+			});
+		}
+
+		self.finalized = true;
+		Ok(())
+	}
+
+	pub async fn query_status(
+		&self,
+		account: &SteamGuardAccount,
+	) -> Result<CTwoFactor_Status_Response, TransportError> {
+		let mut req = CTwoFactor_Status_Request::new();
+		req.set_steamid(account.steam_id);
+
+		let resp = self
+			.client
+			.query_status(req, self.tokens.access_token())
+			.await?;
+
+		Ok(resp.into_response_data())
+	}
+
+	pub async fn remove_authenticator(
+		&self,
+		revocation_code: Option<&String>,
+	) -> Result<(), RemoveAuthenticatorError> {
+		let Some(revocation_code) = revocation_code.filter(|code| !code.is_empty()) else {
+			return Err(RemoveAuthenticatorError::MissingRevocationCode);
+		};
+		let mut req = CTwoFactor_RemoveAuthenticator_Request::new();
+		req.set_revocation_code(revocation_code.clone());
+		let resp = self
+			.client
+			.remove_authenticator(req, self.tokens.access_token())
+			.await?;
+
+		// returns EResult::TwoFactorCodeMismatch if the revocation code is incorrect
+		if resp.result != EResult::OK && resp.result != EResult::TwoFactorCodeMismatch {
+			return Err(resp.result.into());
+		}
+		let resp = resp.into_response_data();
+		if !resp.success() {
+			return Err(RemoveAuthenticatorError::IncorrectRevocationCode {
+				attempts_remaining: resp.revocation_attempts_remaining(),
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Begin the process of "transfering" a mobile authenticator from a different device to this device, see [`crate::AccountLinker::transfer_start`].
+	pub async fn transfer_start(&mut self) -> Result<(), TransferError> {
+		let req = CTwoFactor_RemoveAuthenticatorViaChallengeStart_Request::new();
+
+		let resp = self
+			.client
+			.remove_authenticator_via_challenge_start(req, self.tokens().access_token())
+			.await?;
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+		Ok(())
+	}
+
+	/// Completes the process of "transfering" a mobile authenticator from a different device to this device.
+	pub async fn transfer_finish(
+		&mut self,
+		sms_code: impl AsRef<str>,
+	) -> Result<SteamGuardAccount, TransferError> {
+		let access_token = self.tokens.access_token();
+		let steam_id = access_token
+			.decode()
+			.context("decoding access token")?
+			.steam_id();
+		let req = build_transfer_continue_request(sms_code.as_ref());
+		let resp = self
+			.client
+			.remove_authenticator_via_challenge_continue(req, access_token)
+			.await?;
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+		Ok(account_from_replacement_token(
+			resp.into_response_data(),
+			steam_id,
+			&self.device_id,
+			&self.tokens,
+		))
+	}
+}
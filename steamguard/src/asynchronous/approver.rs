@@ -0,0 +1,167 @@
+use log::debug;
+use reqwest::IntoUrl;
+
+use super::steamapi::AuthenticationClient;
+use super::AsyncTransport;
+use crate::{
+	approver::{build_mobile_confirmation_request, parse_challenge_url, Challenge},
+	protobufs::{
+		enums::ESessionPersistence,
+		steammessages_auth_steamclient::{
+			CAuthentication_GetAuthSessionInfo_Request,
+			CAuthentication_GetAuthSessionInfo_Response,
+			CAuthentication_GetAuthSessionsForAccount_Request,
+		},
+	},
+	steamapi::EResult,
+	token::Tokens,
+	ApproverError, SteamGuardAccount,
+};
+
+/// Async version of [`crate::LoginApprover`].
+pub struct LoginApprover<'a, T>
+where
+	T: AsyncTransport,
+{
+	tokens: &'a Tokens,
+	client: AuthenticationClient<T>,
+}
+
+impl<'a, T> LoginApprover<'a, T>
+where
+	T: AsyncTransport,
+{
+	pub fn new(transport: T, tokens: &'a Tokens) -> Self {
+		let client = AuthenticationClient::new(transport);
+		Self { tokens, client }
+	}
+
+	/// List all active auth sessions. Returns a list of client IDs.
+	pub async fn list_auth_sessions(&self) -> Result<Vec<u64>, ApproverError> {
+		let req = CAuthentication_GetAuthSessionsForAccount_Request::new();
+		let resp = self
+			.client
+			.get_auth_sessions_for_account(req, self.tokens.access_token())
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		Ok(resp.into_response_data().client_ids.clone())
+	}
+
+	pub async fn get_auth_session_info(
+		&self,
+		client_id: u64,
+	) -> Result<CAuthentication_GetAuthSessionInfo_Response, ApproverError> {
+		let mut req = CAuthentication_GetAuthSessionInfo_Request::new();
+		req.set_client_id(client_id);
+		let resp = self
+			.client
+			.get_auth_session_info(req, self.tokens.access_token())
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		Ok(resp.into_response_data())
+	}
+
+	/// Approve a login request from a challenge URL
+	pub async fn approve(
+		&mut self,
+		account: &SteamGuardAccount,
+		challenge: Challenge,
+		persistence: ESessionPersistence,
+	) -> Result<(), ApproverError> {
+		let req = build_mobile_confirmation_request(account, &challenge, true, persistence);
+
+		debug!("approving login");
+		let resp = self
+			.client
+			.update_session_with_mobile_confirmation(req, self.tokens.access_token())
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		Ok(())
+	}
+
+	pub async fn approve_from_challenge_url(
+		&mut self,
+		account: &SteamGuardAccount,
+		challenge_url: impl IntoUrl,
+		persistence: ESessionPersistence,
+	) -> Result<(), ApproverError> {
+		let challenge = parse_challenge_url(challenge_url)?;
+		self.approve(account, challenge, persistence).await
+	}
+
+	pub async fn deny(
+		&mut self,
+		account: &SteamGuardAccount,
+		challenge: Challenge,
+	) -> Result<(), ApproverError> {
+		let req = build_mobile_confirmation_request(
+			account,
+			&challenge,
+			false,
+			ESessionPersistence::k_ESessionPersistence_Persistent,
+		);
+
+		debug!("denying login");
+		let resp = self
+			.client
+			.update_session_with_mobile_confirmation(req, self.tokens.access_token())
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::protobufs::steammessages_auth_steamclient::CAuthentication_GetAuthSessionsForAccount_Response;
+	use crate::transport::MockTransport;
+
+	#[tokio::test]
+	async fn test_list_auth_sessions() {
+		let tokens = Tokens::new("access".to_owned(), "refresh".to_owned());
+		let transport = MockTransport::new();
+		let mut sessions = CAuthentication_GetAuthSessionsForAccount_Response::new();
+		sessions.client_ids = vec![1234, 5678];
+		transport.respond_with(
+			"IAuthenticationService",
+			"GetAuthSessionsForAccount",
+			sessions,
+		);
+
+		let approver = LoginApprover::new(transport, &tokens);
+		assert_eq!(
+			approver.list_auth_sessions().await.unwrap(),
+			vec![1234, 5678]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_unauthorized() {
+		let tokens = Tokens::new("access".to_owned(), "refresh".to_owned());
+		let transport = MockTransport::new();
+		transport.respond_unauthorized("IAuthenticationService", "GetAuthSessionsForAccount");
+
+		let approver = LoginApprover::new(transport, &tokens);
+		assert!(matches!(
+			approver.list_auth_sessions().await,
+			Err(ApproverError::Unauthorized)
+		));
+	}
+}
@@ -0,0 +1,250 @@
+use log::*;
+
+use super::AsyncTransport;
+use crate::confirmation::{
+	build_confirmation_ajax_request, build_mobileconf_request,
+	build_multi_confirmation_ajax_request, confirmation_query_params, parse_confirmation_details,
+	parse_confirmation_list, parse_send_confirmation_response,
+};
+use crate::{
+	Confirmation, ConfirmationAction, ConfirmationId, ConfirmerError, SteamGuardAccount, TimeSync,
+};
+
+/// Async version of [`crate::Confirmer`].
+///
+/// Requests are sent with [`AsyncTransport::send_mobileconf_request`].
+pub struct Confirmer<'a, T> {
+	account: &'a SteamGuardAccount,
+	transport: T,
+	time_sync: TimeSync,
+}
+
+impl<'a, T> Confirmer<'a, T>
+where
+	T: AsyncTransport + Clone,
+{
+	pub fn new(transport: T, account: &'a SteamGuardAccount) -> Self {
+		Self {
+			account,
+			transport,
+			time_sync: TimeSync::new(),
+		}
+	}
+
+	/// Use a shared [`TimeSync`], instead of asking Steam for the time the first time it's needed.
+	pub fn with_time_sync(mut self, time_sync: TimeSync) -> Self {
+		self.time_sync = time_sync;
+		self
+	}
+
+	async fn server_time(&self) -> Result<u64, ConfirmerError> {
+		Ok(self
+			.time_sync
+			.server_time_async(self.transport.clone())
+			.await?)
+	}
+
+	pub async fn get_confirmations(&self) -> Result<Vec<Confirmation>, ConfirmerError> {
+		let time = self.server_time().await?;
+		let req = build_mobileconf_request(
+			self.account,
+			reqwest::Method::GET,
+			"getlist",
+			confirmation_query_params(self.account, "conf", time),
+		);
+		let text = self.transport.send_mobileconf_request(req).await?;
+		parse_confirmation_list(&text)
+	}
+
+	async fn send_confirmation_ajax<'id>(
+		&self,
+		conf: impl Into<ConfirmationId<'id>>,
+		action: ConfirmationAction,
+	) -> Result<(), ConfirmerError> {
+		debug!("responding to a single confirmation: send_confirmation_ajax()");
+		let time = self.server_time().await?;
+		let req = build_confirmation_ajax_request(self.account, conf.into(), action, time);
+		let raw = self.transport.send_mobileconf_request(req).await?;
+		debug!("send_confirmation_ajax() response body: {:?}", &raw);
+		parse_send_confirmation_response(&raw)
+	}
+
+	pub async fn accept_confirmation<'id>(
+		&self,
+		conf: impl Into<ConfirmationId<'id>>,
+	) -> Result<(), ConfirmerError> {
+		self.send_confirmation_ajax(conf, ConfirmationAction::Accept)
+			.await
+	}
+
+	pub async fn deny_confirmation<'id>(
+		&self,
+		conf: impl Into<ConfirmationId<'id>>,
+	) -> Result<(), ConfirmerError> {
+		self.send_confirmation_ajax(conf, ConfirmationAction::Deny)
+			.await
+	}
+
+	async fn send_multi_confirmation_ajax<TId>(
+		&self,
+		confs: &[TId],
+		action: ConfirmationAction,
+	) -> Result<(), ConfirmerError>
+	where
+		for<'id> &'id TId: Into<ConfirmationId<'id>>,
+	{
+		debug!("responding to bulk confirmations: send_multi_confirmation_ajax()");
+		if confs.is_empty() {
+			debug!("confs is empty, nothing to do.");
+			return Ok(());
+		}
+
+		let time = self.server_time().await?;
+		let confs = confs.iter().map(|conf| conf.into()).collect::<Vec<_>>();
+		let req = build_multi_confirmation_ajax_request(self.account, &confs, action, time);
+		let raw = self.transport.send_mobileconf_request(req).await?;
+		debug!("send_multi_confirmation_ajax() response body: {:?}", &raw);
+		parse_send_confirmation_response(&raw)
+	}
+
+	/// Bulk accept confirmations.
+	///
+	/// Sends one request per confirmation.
+	pub async fn accept_confirmations<TId>(&self, confs: &[TId]) -> Result<(), ConfirmerError>
+	where
+		for<'id> &'id TId: Into<ConfirmationId<'id>>,
+	{
+		for conf in confs {
+			self.accept_confirmation(conf).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Bulk deny confirmations.
+	///
+	/// Sends one request per confirmation.
+	pub async fn deny_confirmations<TId>(&self, confs: &[TId]) -> Result<(), ConfirmerError>
+	where
+		for<'id> &'id TId: Into<ConfirmationId<'id>>,
+	{
+		for conf in confs {
+			self.deny_confirmation(conf).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Bulk accept confirmations in one request.
+	pub async fn accept_confirmations_bulk<TId>(&self, confs: &[TId]) -> Result<(), ConfirmerError>
+	where
+		for<'id> &'id TId: Into<ConfirmationId<'id>>,
+	{
+		self.send_multi_confirmation_ajax(confs, ConfirmationAction::Accept)
+			.await
+	}
+
+	/// Bulk deny confirmations in one request.
+	pub async fn deny_confirmations_bulk<TId>(&self, confs: &[TId]) -> Result<(), ConfirmerError>
+	where
+		for<'id> &'id TId: Into<ConfirmationId<'id>>,
+	{
+		self.send_multi_confirmation_ajax(confs, ConfirmationAction::Deny)
+			.await
+	}
+
+	/// Steam Endpoint: `GET /mobileconf/details/:id`
+	pub async fn get_confirmation_details<'id>(
+		&self,
+		conf: impl Into<ConfirmationId<'id>>,
+	) -> anyhow::Result<crate::ConfirmationDetails> {
+		let id = conf.into().id;
+		let time = self.server_time().await?;
+		let req = build_mobileconf_request(
+			self.account,
+			reqwest::Method::GET,
+			format!("details/{}", id),
+			confirmation_query_params(self.account, "details", time),
+		);
+		let text = self.transport.send_mobileconf_request(req).await?;
+		parse_confirmation_details(&text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::token::Tokens;
+	use crate::transport::{mock::MockRequest, MockTransport};
+	use crate::ConfirmationType;
+
+	fn account() -> SteamGuardAccount {
+		let mut account = SteamGuardAccount::new();
+		account.steam_id = 76561199000000000;
+		account.identity_secret = String::from("GQP46b73Ws7gr8GmZFR0sDuau5c=").into();
+		account.tokens = Some(Tokens::new("access".to_owned(), "refresh".to_owned()));
+		account
+	}
+
+	fn transport() -> MockTransport {
+		let transport = MockTransport::new().with_server_time(1616374841);
+		transport.respond_mobileconf(
+			"getlist",
+			include_str!("../fixtures/confirmations/phone-number-change.json"),
+		);
+		transport.respond_mobileconf("multiajaxop", r#"{"success": true}"#);
+		transport
+	}
+
+	#[tokio::test]
+	async fn test_confirmations() {
+		let account = account();
+		let transport = transport();
+		let confirmer = Confirmer::new(transport.clone(), &account);
+		let confirmations = confirmer.get_confirmations().await.unwrap();
+		assert_eq!(confirmations.len(), 1);
+		assert_eq!(
+			confirmations[0].conf_type,
+			ConfirmationType::PhoneNumberChange
+		);
+		confirmer
+			.accept_confirmations_bulk(&confirmations)
+			.await
+			.unwrap();
+		// the time is only queried once
+		assert_eq!(transport.requests().len(), 3);
+	}
+
+	#[tokio::test]
+	async fn test_same_requests_as_blocking() {
+		let account = account();
+		// always ask the mock transport for the time, so both confirmers use exactly the same time
+		let time_sync = || TimeSync::new().with_ttl(std::time::Duration::ZERO);
+
+		let blocking = transport();
+		let confirmer =
+			crate::Confirmer::new(blocking.clone(), &account).with_time_sync(time_sync());
+		let confirmations = confirmer.get_confirmations().unwrap();
+		confirmer.deny_confirmations_bulk(&confirmations).unwrap();
+
+		let nonblocking = transport();
+		let confirmer = Confirmer::new(nonblocking.clone(), &account).with_time_sync(time_sync());
+		let confirmations = confirmer.get_confirmations().await.unwrap();
+		confirmer
+			.deny_confirmations_bulk(&confirmations)
+			.await
+			.unwrap();
+
+		let mobileconf = |transport: &MockTransport| {
+			transport
+				.requests()
+				.into_iter()
+				.filter_map(|req| match req {
+					MockRequest::MobileConf(req) => Some((req.path, req.query, req.body)),
+					_ => None,
+				})
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(mobileconf(&blocking), mobileconf(&nonblocking));
+	}
+}
@@ -0,0 +1,46 @@
+use super::steamapi::AuthenticationClient;
+use super::AsyncTransport;
+use crate::{
+	protobufs::steammessages_auth_steamclient::CAuthentication_AccessToken_GenerateForApp_Request,
+	steamapi::EResult,
+	token::{Jwt, Tokens},
+};
+
+/// Async version of [`crate::refresher::TokenRefresher`].
+pub struct TokenRefresher<T>
+where
+	T: AsyncTransport,
+{
+	client: AuthenticationClient<T>,
+}
+
+impl<T> TokenRefresher<T>
+where
+	T: AsyncTransport,
+{
+	pub fn new(client: AuthenticationClient<T>) -> Self {
+		Self { client }
+	}
+
+	pub async fn refresh(&mut self, steam_id: u64, tokens: &Tokens) -> Result<Jwt, anyhow::Error> {
+		let mut req = CAuthentication_AccessToken_GenerateForApp_Request::new();
+		req.set_steamid(steam_id);
+		req.set_refresh_token(tokens.refresh_token().expose_secret().to_owned());
+
+		let resp = self
+			.client
+			.generate_access_token(req, tokens.access_token())
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(anyhow::anyhow!(
+				"Failed to refresh access token: {:?}",
+				resp.result
+			));
+		}
+
+		let mut resp = resp.into_response_data();
+
+		Ok(resp.take_access_token().into())
+	}
+}
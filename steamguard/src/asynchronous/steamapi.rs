@@ -0,0 +1,270 @@
+//! Async versions of the clients in [`crate::steamapi`].
+
+use super::AsyncTransport;
+use crate::protobufs::custom::CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData;
+use crate::protobufs::service_twofactor::*;
+use crate::protobufs::steammessages_auth_steamclient::*;
+use crate::steamapi::{ApiRequest, ApiResponse, EResult};
+use crate::token::Jwt;
+use crate::transport::TransportError;
+
+const AUTHENTICATION_SERVICE: &str = "IAuthenticationService";
+const TWO_FACTOR_SERVICE: &str = "ITwoFactorService";
+
+/// Queries Steam for the current time. A convenience function around [`TwoFactorClient`].
+///
+/// Endpoint: `/ITwoFactorService/QueryTime/v0001`
+pub async fn get_server_time<T: AsyncTransport>(
+	client: T,
+) -> anyhow::Result<CTwoFactor_Time_Response> {
+	let client = TwoFactorClient::new(client);
+	let resp = client.query_time().await?;
+	if resp.result != EResult::OK {
+		return Err(anyhow::anyhow!("QueryTime failed: {:?}", resp));
+	}
+
+	Ok(resp.into_response_data())
+}
+
+/// Async version of [`crate::steamapi::AuthenticationClient`].
+#[derive(Debug, Clone)]
+pub struct AuthenticationClient<T>
+where
+	T: AsyncTransport,
+{
+	transport: T,
+}
+
+impl<T> AuthenticationClient<T>
+where
+	T: AsyncTransport,
+{
+	#[must_use]
+	pub fn new(transport: T) -> Self {
+		Self { transport }
+	}
+
+	pub async fn begin_auth_session_via_credentials(
+		&self,
+		req: CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData,
+	) -> Result<ApiResponse<CAuthentication_BeginAuthSessionViaCredentials_Response>, TransportError>
+	{
+		let req = ApiRequest::new(
+			AUTHENTICATION_SERVICE,
+			"BeginAuthSessionViaCredentials",
+			1u32,
+			req,
+		);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn begin_auth_session_via_qr(
+		&self,
+		req: CAuthentication_BeginAuthSessionViaQR_Request,
+	) -> Result<ApiResponse<CAuthentication_BeginAuthSessionViaQR_Response>, TransportError> {
+		let req = ApiRequest::new(AUTHENTICATION_SERVICE, "BeginAuthSessionViaQR", 1u32, req);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn generate_access_token(
+		&self,
+		req: CAuthentication_AccessToken_GenerateForApp_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CAuthentication_AccessToken_GenerateForApp_Response>, TransportError> {
+		let req = ApiRequest::new(
+			AUTHENTICATION_SERVICE,
+			"GenerateAccessTokenForApp",
+			1u32,
+			req,
+		)
+		.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn fetch_rsa_key(
+		&self,
+		account_name: String,
+	) -> Result<ApiResponse<CAuthentication_GetPasswordRSAPublicKey_Response>, TransportError> {
+		let mut inner = CAuthentication_GetPasswordRSAPublicKey_Request::new();
+		inner.set_account_name(account_name);
+		let req = ApiRequest::new(
+			AUTHENTICATION_SERVICE,
+			"GetPasswordRSAPublicKey",
+			1u32,
+			inner,
+		);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn get_auth_sessions_for_account(
+		&self,
+		req: CAuthentication_GetAuthSessionsForAccount_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CAuthentication_GetAuthSessionsForAccount_Response>, TransportError> {
+		let req = ApiRequest::new(
+			AUTHENTICATION_SERVICE,
+			"GetAuthSessionsForAccount",
+			1u32,
+			req,
+		)
+		.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn get_auth_session_info(
+		&self,
+		req: CAuthentication_GetAuthSessionInfo_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CAuthentication_GetAuthSessionInfo_Response>, TransportError> {
+		let req = ApiRequest::new(AUTHENTICATION_SERVICE, "GetAuthSessionInfo", 1u32, req)
+			.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn poll_auth_session(
+		&self,
+		req: CAuthentication_PollAuthSessionStatus_Request,
+	) -> Result<ApiResponse<CAuthentication_PollAuthSessionStatus_Response>, TransportError> {
+		let req = ApiRequest::new(AUTHENTICATION_SERVICE, "PollAuthSessionStatus", 1u32, req);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn update_session_with_mobile_confirmation(
+		&self,
+		req: CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request,
+		access_token: &Jwt,
+	) -> Result<
+		ApiResponse<CAuthentication_UpdateAuthSessionWithMobileConfirmation_Response>,
+		TransportError,
+	> {
+		let req = ApiRequest::new(
+			AUTHENTICATION_SERVICE,
+			"UpdateAuthSessionWithMobileConfirmation",
+			1u32,
+			req,
+		)
+		.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn update_session_with_steam_guard_code(
+		&self,
+		req: CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request,
+	) -> Result<
+		ApiResponse<CAuthentication_UpdateAuthSessionWithSteamGuardCode_Response>,
+		TransportError,
+	> {
+		let req = ApiRequest::new(
+			AUTHENTICATION_SERVICE,
+			"UpdateAuthSessionWithSteamGuardCode",
+			1u32,
+			req,
+		);
+		self.transport.send_request(req).await
+	}
+}
+
+/// Async version of [`crate::steamapi::TwoFactorClient`].
+#[derive(Debug, Clone)]
+pub struct TwoFactorClient<T>
+where
+	T: AsyncTransport,
+{
+	transport: T,
+}
+
+impl<T> TwoFactorClient<T>
+where
+	T: AsyncTransport,
+{
+	#[must_use]
+	pub fn new(transport: T) -> Self {
+		Self { transport }
+	}
+
+	pub async fn add_authenticator(
+		&self,
+		req: CTwoFactor_AddAuthenticator_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CTwoFactor_AddAuthenticator_Response>, TransportError> {
+		let req = ApiRequest::new(TWO_FACTOR_SERVICE, "AddAuthenticator", 1, req)
+			.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn finalize_authenticator(
+		&self,
+		req: CTwoFactor_FinalizeAddAuthenticator_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CTwoFactor_FinalizeAddAuthenticator_Response>, TransportError> {
+		let req = ApiRequest::new(TWO_FACTOR_SERVICE, "FinalizeAddAuthenticator", 1, req)
+			.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn remove_authenticator(
+		&self,
+		req: CTwoFactor_RemoveAuthenticator_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CTwoFactor_RemoveAuthenticator_Response>, TransportError> {
+		let req = ApiRequest::new(TWO_FACTOR_SERVICE, "RemoveAuthenticator", 1, req)
+			.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn remove_authenticator_via_challenge_start(
+		&self,
+		req: CTwoFactor_RemoveAuthenticatorViaChallengeStart_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CTwoFactor_RemoveAuthenticatorViaChallengeStart_Response>, TransportError>
+	{
+		let req = ApiRequest::new(
+			TWO_FACTOR_SERVICE,
+			"RemoveAuthenticatorViaChallengeStart",
+			1,
+			req,
+		)
+		.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn remove_authenticator_via_challenge_continue(
+		&self,
+		req: CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Request,
+		access_token: &Jwt,
+	) -> Result<
+		ApiResponse<CTwoFactor_RemoveAuthenticatorViaChallengeContinue_Response>,
+		TransportError,
+	> {
+		let req = ApiRequest::new(
+			TWO_FACTOR_SERVICE,
+			"RemoveAuthenticatorViaChallengeContinue",
+			1,
+			req,
+		)
+		.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn query_status(
+		&self,
+		req: CTwoFactor_Status_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CTwoFactor_Status_Response>, TransportError> {
+		let req = ApiRequest::new(TWO_FACTOR_SERVICE, "QueryStatus", 1, req)
+			.with_access_token(access_token);
+		self.transport.send_request(req).await
+	}
+
+	pub async fn query_time(
+		&self,
+	) -> Result<ApiResponse<CTwoFactor_Time_Response>, TransportError> {
+		let req = ApiRequest::new(
+			TWO_FACTOR_SERVICE,
+			"QueryTime",
+			1,
+			CTwoFactor_Time_Request::new(),
+		);
+		self.transport.send_request(req).await
+	}
+}
@@ -0,0 +1,145 @@
+use std::future::Future;
+
+use log::*;
+use protobuf::MessageFull;
+use reqwest::header::{CONTENT_TYPE, COOKIE, USER_AGENT};
+use reqwest::multipart::Form;
+
+use crate::steamapi::{ApiRequest, ApiResponse, BuildableRequest};
+use crate::transport::webapi::{encode_msg, parse_response};
+use crate::transport::{MobileConfRequest, MockTransport, Transport, TransportError};
+
+/// The async equivalent of [`Transport`].
+pub trait AsyncTransport {
+	fn send_request<Req: BuildableRequest + MessageFull, Res: MessageFull>(
+		&self,
+		req: ApiRequest<'_, Req>,
+	) -> impl Future<Output = Result<ApiResponse<Res>, TransportError>> + Send;
+
+	/// Send a request to the mobile confirmation endpoints on steamcommunity.com, and return the response body.
+	fn send_mobileconf_request(
+		&self,
+		req: MobileConfRequest,
+	) -> impl Future<Output = Result<String, TransportError>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub struct AsyncWebApiTransport {
+	client: reqwest::Client,
+}
+
+impl AsyncWebApiTransport {
+	pub fn new(client: reqwest::Client) -> Self {
+		Self { client }
+	}
+}
+
+impl AsyncTransport for AsyncWebApiTransport {
+	async fn send_request<Req: BuildableRequest + MessageFull, Res: MessageFull>(
+		&self,
+		apireq: ApiRequest<'_, Req>,
+	) -> Result<ApiResponse<Res>, TransportError> {
+		// See `WebApiTransport::send_request` for details on how requests are encoded.
+		if Req::requires_access_token() && apireq.access_token().is_none() {
+			return Err(TransportError::Unauthorized);
+		}
+
+		let url = apireq.build_url();
+		debug!("HTTP Request: {} {}", Req::method(), url);
+		trace!("Request body: {:#?}", apireq.request_data());
+		let mut req = self.client.request(Req::method(), &url);
+
+		req = if Req::method() == reqwest::Method::GET {
+			let encoded = encode_msg(
+				apireq.request_data(),
+				base64::engine::general_purpose::URL_SAFE,
+			)?;
+			let mut params = vec![("input_protobuf_encoded", encoded.as_str())];
+			if let Some(access_token) = apireq.access_token() {
+				params.push(("access_token", access_token.expose_secret()));
+			}
+			req.query(&params)
+		} else {
+			if let Some(access_token) = apireq.access_token() {
+				req = req.query(&[("access_token", access_token)]);
+			}
+			let encoded = encode_msg(
+				apireq.request_data(),
+				base64::engine::general_purpose::STANDARD,
+			)?;
+			let form = Form::new().text("input_protobuf_encoded", encoded);
+			req.multipart(form)
+		};
+
+		let resp = req.send().await?;
+		let status = resp.status();
+		let headers = resp.headers().clone();
+		let bytes = resp.bytes().await?;
+		parse_response(status, &headers, bytes.as_ref())
+	}
+
+	async fn send_mobileconf_request(
+		&self,
+		req: MobileConfRequest,
+	) -> Result<String, TransportError> {
+		let url = format!("https://steamcommunity.com/mobileconf/{}", req.path);
+		debug!("HTTP Request: {} {}", req.method, url);
+		let mut builder = self
+			.client
+			.request(req.method, url)
+			.header(USER_AGENT, "steamguard-cli")
+			.header(COOKIE, req.cookies)
+			.header("Origin", "https://steamcommunity.com")
+			.query(&req.query);
+		if let Some(body) = req.body {
+			builder = builder
+				.header(
+					CONTENT_TYPE,
+					"application/x-www-form-urlencoded; charset=UTF-8",
+				)
+				.body(body);
+		}
+		let resp = builder.send().await?;
+		debug!("Response HTTP status: {}", resp.status());
+		Ok(resp.text().await?)
+	}
+}
+
+/// Responses are served immediately, exactly like the blocking implementation.
+impl AsyncTransport for MockTransport {
+	fn send_request<Req: BuildableRequest + MessageFull, Res: MessageFull>(
+		&self,
+		req: ApiRequest<'_, Req>,
+	) -> impl Future<Output = Result<ApiResponse<Res>, TransportError>> + Send {
+		std::future::ready(Transport::send_request(self, req))
+	}
+
+	fn send_mobileconf_request(
+		&self,
+		req: MobileConfRequest,
+	) -> impl Future<Output = Result<String, TransportError>> + Send {
+		std::future::ready(Transport::send_mobileconf_request(self, req))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asynchronous::{steamapi, Confirmer, LoginApprover};
+	use crate::{token::Tokens, SteamGuardAccount};
+
+	fn assert_send<T: Send>(_: T) {}
+
+	#[test]
+	fn test_futures_are_send() {
+		let transport = AsyncWebApiTransport::new(reqwest::Client::new());
+		let mut account = SteamGuardAccount::new();
+		account.tokens = Some(Tokens::new("access".to_owned(), "refresh".to_owned()));
+
+		let confirmer = Confirmer::new(transport.clone(), &account);
+		assert_send(confirmer.get_confirmations());
+		let approver = LoginApprover::new(transport.clone(), account.tokens.as_ref().unwrap());
+		assert_send(approver.list_auth_sessions());
+		assert_send(steamapi::get_server_time(transport));
+	}
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use log::*;
+
+use super::steamapi::AuthenticationClient;
+use super::{AsyncTransport, TokenRefresher};
+use crate::api_responses::AllowedConfirmation;
+use crate::protobufs::steammessages_auth_steamclient::{
+	CAuthentication_PollAuthSessionStatus_Response,
+	CAuthentication_UpdateAuthSessionWithSteamGuardCode_Response, EAuthSessionGuardType,
+};
+use crate::steamapi::EResult;
+use crate::token::Tokens;
+use crate::userlogin::{
+	build_credentials_request, build_qr_request, has_poll_data, BeginQrLoginResponse,
+	DeviceDetails, LoginError, StartAuth, UpdateAuthSessionError,
+};
+
+/// Async version of [`crate::UserLogin`].
+#[derive(Debug)]
+pub struct UserLogin<T>
+where
+	T: AsyncTransport + Clone,
+{
+	client: AuthenticationClient<T>,
+	device_details: DeviceDetails,
+
+	started_auth: Option<StartAuth>,
+}
+
+impl<T> UserLogin<T>
+where
+	T: AsyncTransport + Clone,
+{
+	pub fn new(transport: T, device_details: DeviceDetails) -> Self {
+		Self {
+			client: AuthenticationClient::new(transport),
+			device_details,
+			started_auth: None,
+		}
+	}
+
+	pub async fn begin_auth_via_credentials(
+		&mut self,
+		account_name: &str,
+		password: &str,
+	) -> Result<Vec<AllowedConfirmation>, LoginError> {
+		if self.started_auth.is_some() {
+			return Err(LoginError::AuthAlreadyStarted);
+		}
+		trace!("UserLogin::begin_auth_via_credentials");
+
+		let rsa = self.client.fetch_rsa_key(account_name.to_owned()).await?;
+		let req = build_credentials_request(
+			&self.device_details,
+			account_name,
+			rsa.into_response_data(),
+			password,
+		);
+
+		let resp = self.client.begin_auth_session_via_credentials(req).await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		debug!("auth session started");
+		let started_auth = StartAuth::from(resp.into_response_data());
+		let allowed_confirmations = started_auth
+			.allowed_confirmations()
+			.iter()
+			.map(|c| c.clone().into())
+			.collect();
+		self.started_auth = Some(started_auth);
+
+		Ok(allowed_confirmations)
+	}
+
+	pub async fn begin_auth_via_qr(&mut self) -> Result<BeginQrLoginResponse, LoginError> {
+		if self.started_auth.is_some() {
+			return Err(LoginError::AuthAlreadyStarted);
+		}
+
+		let resp = self
+			.client
+			.begin_auth_session_via_qr(build_qr_request(&self.device_details))
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		let return_resp = BeginQrLoginResponse::from(resp.response_data());
+
+		debug!("auth session started");
+		self.started_auth = Some(resp.into_response_data().into());
+
+		Ok(return_resp)
+	}
+
+	async fn poll_until_info(
+		&mut self,
+	) -> anyhow::Result<CAuthentication_PollAuthSessionStatus_Response> {
+		let Some(started_auth) = self.started_auth.as_ref() else {
+			return Err(anyhow::anyhow!("no auth session started"));
+		};
+
+		loop {
+			let resp = self
+				.client
+				.poll_auth_session(started_auth.build_poll_request())
+				.await?;
+			if resp.result != EResult::OK {
+				// EResult::FileNotFound is returned when the server couldn't find the auth session
+				return Err(anyhow::anyhow!("poll failed: {:?}", resp.result));
+			}
+
+			if has_poll_data(resp.response_data()) {
+				return Ok(resp.into_response_data());
+			}
+
+			tokio::time::sleep(Duration::from_secs_f32(started_auth.interval())).await;
+		}
+	}
+
+	pub async fn poll_until_tokens(&mut self) -> anyhow::Result<Tokens> {
+		self.poll_until_tokens_with(|_| {}).await
+	}
+
+	/// Async version of [`crate::UserLogin::poll_until_tokens_with`].
+	pub async fn poll_until_tokens_with(
+		&mut self,
+		mut on_new_challenge_url: impl FnMut(&str),
+	) -> anyhow::Result<Tokens> {
+		loop {
+			let mut next_poll = self.poll_until_info().await?;
+
+			if next_poll.has_new_client_id() {
+				debug!("auth session has a new client id");
+				if let Some(started_auth) = self.started_auth.as_mut() {
+					started_auth.set_client_id(next_poll.new_client_id());
+				}
+			}
+			if next_poll.has_new_challenge_url() {
+				debug!("got new challenge url");
+				on_new_challenge_url(next_poll.new_challenge_url());
+			}
+
+			if next_poll.has_access_token() || next_poll.has_refresh_token() {
+				// Steam doesn't always issue access tokens alongside refresh tokens, see `crate::UserLogin::poll_until_tokens`.
+				let access_token = next_poll.take_access_token();
+				if !access_token.is_empty() {
+					return Ok(Tokens::new(access_token, next_poll.take_refresh_token()));
+				}
+
+				let mut refresher = TokenRefresher::new(self.client.clone());
+				let mut tokens = Tokens::new(access_token, next_poll.take_refresh_token());
+				let steamid = tokens
+					.refresh_token()
+					.decode()
+					.context("decoding refresh token for steam id")?
+					.steam_id();
+				let access_token = refresher.refresh(steamid, &tokens).await?;
+				tokens.set_access_token(access_token);
+				return Ok(tokens);
+			}
+
+			if let Some(started_auth) = self.started_auth.as_ref() {
+				tokio::time::sleep(Duration::from_secs_f32(started_auth.interval())).await;
+			}
+		}
+	}
+
+	/// Submit a 2fa code generated from a device, or received in an email.
+	pub async fn submit_steam_guard_code(
+		&mut self,
+		guard_type: EAuthSessionGuardType,
+		code: String,
+	) -> Result<CAuthentication_UpdateAuthSessionWithSteamGuardCode_Response, UpdateAuthSessionError>
+	{
+		let Some(started_auth) = self.started_auth.as_ref() else {
+			return Err(UpdateAuthSessionError::SessionNotStarted);
+		};
+		let req = started_auth.build_steam_guard_code_request(guard_type, code)?;
+
+		let resp = self
+			.client
+			.update_session_with_steam_guard_code(req)
+			.await?;
+
+		if resp.result != EResult::OK {
+			return Err(resp.result.into());
+		}
+
+		Ok(resp.into_response_data())
+	}
+}
@@ -3,31 +3,26 @@ use std::borrow::Cow;
 use base64::Engine;
 use hmac::{Hmac, Mac};
 use log::*;
-use reqwest::{
-	cookie::CookieStore,
-	header::{CONTENT_TYPE, COOKIE, USER_AGENT},
-	Url,
-};
 use secrecy::ExposeSecret;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sha1::Sha1;
 
 use crate::{
-	steamapi::{self},
-	transport::Transport,
-	SteamGuardAccount,
+	transport::{MobileConfRequest, Transport, TransportError},
+	SteamGuardAccount, TimeSync,
 };
 
-lazy_static! {
-	static ref STEAM_COOKIE_URL: Url = "https://steamcommunity.com".parse::<Url>().unwrap();
-}
+mod details;
+
+pub use details::*;
 
 /// Provides an interface that wraps the Steam mobile confirmation API.
 ///
-/// Only compatible with WebApiTransport.
+/// Requests are sent with [`Transport::send_mobileconf_request`].
 pub struct Confirmer<'a, T> {
 	account: &'a SteamGuardAccount,
 	transport: T,
+	time_sync: TimeSync,
 }
 
 impl<'a, T> Confirmer<'a, T>
@@ -35,7 +30,17 @@ where
 	T: Transport + Clone,
 {
 	pub fn new(transport: T, account: &'a SteamGuardAccount) -> Self {
-		Self { account, transport }
+		Self {
+			account,
+			transport,
+			time_sync: TimeSync::new(),
+		}
+	}
+
+	/// Use a shared [`TimeSync`], instead of asking Steam for the time the first time it's needed.
+	pub fn with_time_sync(mut self, time_sync: TimeSync) -> Self {
+		self.time_sync = time_sync;
+		self
 	}
 
 	fn get_confirmation_query_params<'q>(
@@ -43,79 +48,27 @@ where
 		tag: &'q str,
 		time: u64,
 	) -> Vec<(&'static str, Cow<'q, str>)> {
-		[
-			("p", self.account.device_id.as_str().into()),
-			("a", self.account.steam_id.to_string().into()),
-			(
-				"k",
-				generate_confirmation_hash_for_time(
-					time,
-					tag,
-					self.account.identity_secret.expose_secret(),
-				)
-				.into(),
-			),
-			("t", time.to_string().into()),
-			("m", "react".into()),
-			("tag", tag.into()),
-		]
-		.into()
+		confirmation_query_params(self.account, tag, time)
 	}
 
-	fn build_cookie_jar(&self) -> reqwest::cookie::Jar {
-		let cookies = reqwest::cookie::Jar::default();
-		let tokens = self.account.tokens.as_ref().unwrap();
-		cookies.add_cookie_str("dob=", &STEAM_COOKIE_URL);
-		cookies.add_cookie_str(
-			format!("steamid={}", self.account.steam_id).as_str(),
-			&STEAM_COOKIE_URL,
-		);
-		cookies.add_cookie_str(
-			format!(
-				"steamLoginSecure={}||{}",
-				self.account.steam_id,
-				tokens.access_token().expose_secret()
-			)
-			.as_str(),
-			&STEAM_COOKIE_URL,
-		);
-		cookies
+	fn build_request(
+		&self,
+		method: reqwest::Method,
+		path: impl Into<String>,
+		query: Vec<(&str, Cow<str>)>,
+	) -> MobileConfRequest {
+		build_mobileconf_request(self.account, method, path, query)
 	}
 
 	pub fn get_confirmations(&self) -> Result<Vec<Confirmation>, ConfirmerError> {
-		let cookies = self.build_cookie_jar();
-		let client = self.transport.innner_http_client()?;
-
-		let time = steamapi::get_server_time(self.transport.clone())?.server_time();
-		let resp = client
-			.get(
-				"https://steamcommunity.com/mobileconf/getlist"
-					.parse::<Url>()
-					.unwrap(),
-			)
-			.header(USER_AGENT, "steamguard-cli")
-			.header(COOKIE, cookies.cookies(&STEAM_COOKIE_URL).unwrap())
-			.query(&self.get_confirmation_query_params("conf", time))
-			.send()?;
-
-		trace!("{:?}", resp);
-		let text = resp.text().unwrap();
-		debug!("Confirmations response: {}", text);
-
-		let mut deser = serde_json::Deserializer::from_str(text.as_str());
-		let body: ConfirmationListResponse = serde_path_to_error::deserialize(&mut deser)?;
-
-		if body.needauth.unwrap_or(false) {
-			return Err(ConfirmerError::InvalidTokens);
-		}
-		if !body.success {
-			if let Some(msg) = body.message {
-				return Err(ConfirmerError::RemoteFailureWithMessage(msg));
-			} else {
-				return Err(ConfirmerError::RemoteFailure);
-			}
-		}
-		Ok(body.conf)
+		let time = self.time_sync.server_time(self.transport.clone())?;
+		let req = self.build_request(
+			reqwest::Method::GET,
+			"getlist",
+			self.get_confirmation_query_params("conf", time),
+		);
+		let text = self.transport.send_mobileconf_request(req)?;
+		parse_confirmation_list(&text)
 	}
 
 	/// Respond to a confirmation.
@@ -128,54 +81,11 @@ where
 		action: ConfirmationAction,
 	) -> Result<(), ConfirmerError> {
 		debug!("responding to a single confirmation: send_confirmation_ajax()");
-		let conf = conf.into();
-		let operation = action.to_operation();
-
-		let cookies = self.build_cookie_jar();
-		let client = self.transport.innner_http_client()?;
-
-		let time = steamapi::get_server_time(self.transport.clone())?.server_time();
-		let mut query_params = self.get_confirmation_query_params("conf", time);
-		query_params.push(("op", operation.into()));
-		query_params.push(("cid", Cow::Borrowed(conf.id)));
-		query_params.push(("ck", Cow::Borrowed(conf.nonce)));
-
-		let resp = client
-			.get(
-				"https://steamcommunity.com/mobileconf/ajaxop"
-					.parse::<Url>()
-					.unwrap(),
-			)
-			.header(USER_AGENT, "steamguard-cli")
-			.header(COOKIE, cookies.cookies(&STEAM_COOKIE_URL).unwrap())
-			.header("Origin", "https://steamcommunity.com")
-			.query(&query_params)
-			.send()?;
-
-		trace!("send_confirmation_ajax() response: {:?}", &resp);
-		debug!(
-			"send_confirmation_ajax() response status code: {}",
-			&resp.status()
-		);
-
-		let raw = resp.text()?;
+		let time = self.time_sync.server_time(self.transport.clone())?;
+		let req = build_confirmation_ajax_request(self.account, conf.into(), action, time);
+		let raw = self.transport.send_mobileconf_request(req)?;
 		debug!("send_confirmation_ajax() response body: {:?}", &raw);
-
-		let mut deser = serde_json::Deserializer::from_str(raw.as_str());
-		let body: SendConfirmationResponse = serde_path_to_error::deserialize(&mut deser)?;
-
-		if body.needsauth.unwrap_or(false) {
-			return Err(ConfirmerError::InvalidTokens);
-		}
-		if !body.success {
-			if let Some(msg) = body.message {
-				return Err(ConfirmerError::RemoteFailureWithMessage(msg));
-			} else {
-				return Err(ConfirmerError::RemoteFailure);
-			}
-		}
-
-		Ok(())
+		parse_send_confirmation_response(&raw)
 	}
 
 	pub fn accept_confirmation<'id>(
@@ -209,63 +119,12 @@ where
 			debug!("confs is empty, nothing to do.");
 			return Ok(());
 		}
-		let operation = action.to_operation();
-
-		let cookies = self.build_cookie_jar();
-		let client = self.transport.innner_http_client()?;
-
-		let time = steamapi::get_server_time(self.transport.clone())?.server_time();
-		let mut query_params = self.get_confirmation_query_params("conf", time);
-		query_params.push(("op", operation.into()));
-		for conf in confs.iter() {
-			let conf = conf.into();
-			query_params.push(("cid[]", Cow::Borrowed(conf.id)));
-			query_params.push(("ck[]", Cow::Borrowed(conf.nonce)));
-		}
-		let query_params = self.build_multi_conf_query_string(&query_params);
-		// despite being called query parameters, they will actually go in the body
-		debug!("query_params: {}", &query_params);
-
-		let resp = client
-			.post(
-				"https://steamcommunity.com/mobileconf/multiajaxop"
-					.parse::<Url>()
-					.unwrap(),
-			)
-			.header(USER_AGENT, "steamguard-cli")
-			.header(COOKIE, cookies.cookies(&STEAM_COOKIE_URL).unwrap())
-			.header(
-				CONTENT_TYPE,
-				"application/x-www-form-urlencoded; charset=UTF-8",
-			)
-			.header("Origin", "https://steamcommunity.com")
-			.body(query_params)
-			.send()?;
-
-		trace!("send_multi_confirmation_ajax() response: {:?}", &resp);
-		debug!(
-			"send_multi_confirmation_ajax() response status code: {}",
-			&resp.status()
-		);
-
-		let raw = resp.text()?;
+		let time = self.time_sync.server_time(self.transport.clone())?;
+		let confs = confs.iter().map(|conf| conf.into()).collect::<Vec<_>>();
+		let req = build_multi_confirmation_ajax_request(self.account, &confs, action, time);
+		let raw = self.transport.send_mobileconf_request(req)?;
 		debug!("send_multi_confirmation_ajax() response body: {:?}", &raw);
-
-		let mut deser = serde_json::Deserializer::from_str(raw.as_str());
-		let body: SendConfirmationResponse = serde_path_to_error::deserialize(&mut deser)?;
-
-		if body.needsauth.unwrap_or(false) {
-			return Err(ConfirmerError::InvalidTokens);
-		}
-		if !body.success {
-			if let Some(msg) = body.message {
-				return Err(ConfirmerError::RemoteFailureWithMessage(msg));
-			} else {
-				return Err(ConfirmerError::RemoteFailure);
-			}
-		}
-
-		Ok(())
+		parse_send_confirmation_response(&raw)
 	}
 
 	/// Bulk accept confirmations.
@@ -316,52 +175,159 @@ where
 		self.send_multi_confirmation_ajax(confs, ConfirmationAction::Deny)
 	}
 
-	fn build_multi_conf_query_string(&self, params: &[(&str, Cow<str>)]) -> String {
-		params
-			.iter()
-			.map(|(k, v)| format!("{}={}", k, v))
-			.collect::<Vec<_>>()
-			.join("&")
-	}
-
+	/// Get the details page for a confirmation, like the items in a trade offer.
+	///
 	/// Steam Endpoint: `GET /mobileconf/details/:id`
 	pub fn get_confirmation_details<'id>(
 		&self,
 		conf: impl Into<ConfirmationId<'id>>,
-	) -> anyhow::Result<String> {
-		#[derive(Debug, Clone, Deserialize)]
-		struct ConfirmationDetailsResponse {
-			pub success: bool,
-			pub html: String,
+	) -> anyhow::Result<ConfirmationDetails> {
+		let time = self.time_sync.server_time(self.transport.clone())?;
+		let query_params = self.get_confirmation_query_params("details", time);
+
+		let req = self.build_request(
+			reqwest::Method::GET,
+			format!("details/{}", conf.into().id),
+			query_params,
+		);
+		let text = self.transport.send_mobileconf_request(req)?;
+		parse_confirmation_details(&text)
+	}
+}
+
+pub(crate) fn confirmation_query_params<'q>(
+	account: &'q SteamGuardAccount,
+	tag: &'q str,
+	time: u64,
+) -> Vec<(&'static str, Cow<'q, str>)> {
+	[
+		("p", account.device_id.as_str().into()),
+		("a", account.steam_id.to_string().into()),
+		(
+			"k",
+			generate_confirmation_hash_for_time(time, tag, account.identity_secret.expose_secret())
+				.into(),
+		),
+		("t", time.to_string().into()),
+		("m", "react".into()),
+		("tag", tag.into()),
+	]
+	.into()
+}
+
+/// Builds a request to the mobileconf endpoints, authenticated as `account`.
+pub(crate) fn build_mobileconf_request(
+	account: &SteamGuardAccount,
+	method: reqwest::Method,
+	path: impl Into<String>,
+	query: Vec<(&str, Cow<str>)>,
+) -> MobileConfRequest {
+	let tokens = account.tokens.as_ref().unwrap();
+	MobileConfRequest {
+		method,
+		path: path.into(),
+		query: query
+			.into_iter()
+			.map(|(k, v)| (k.to_owned(), v.into_owned()))
+			.collect(),
+		body: None,
+		cookies: format!(
+			"dob=; steamid={steam_id}; steamLoginSecure={steam_id}||{}",
+			tokens.access_token().expose_secret(),
+			steam_id = account.steam_id,
+		),
+	}
+}
+
+/// Steam Endpoint: `GET /mobileconf/ajaxop`
+pub(crate) fn build_confirmation_ajax_request(
+	account: &SteamGuardAccount,
+	conf: ConfirmationId,
+	action: ConfirmationAction,
+	time: u64,
+) -> MobileConfRequest {
+	let mut query_params = confirmation_query_params(account, "conf", time);
+	query_params.push(("op", action.to_operation().into()));
+	query_params.push(("cid", Cow::Borrowed(conf.id)));
+	query_params.push(("ck", Cow::Borrowed(conf.nonce)));
+	build_mobileconf_request(account, reqwest::Method::GET, "ajaxop", query_params)
+}
+
+/// Steam Endpoint: `POST /mobileconf/multiajaxop`
+pub(crate) fn build_multi_confirmation_ajax_request(
+	account: &SteamGuardAccount,
+	confs: &[ConfirmationId],
+	action: ConfirmationAction,
+	time: u64,
+) -> MobileConfRequest {
+	let mut query_params = confirmation_query_params(account, "conf", time);
+	query_params.push(("op", action.to_operation().into()));
+	for conf in confs {
+		query_params.push(("cid[]", Cow::Borrowed(conf.id)));
+		query_params.push(("ck[]", Cow::Borrowed(conf.nonce)));
+	}
+	// despite being called query parameters, they will actually go in the body
+	let body = query_params
+		.iter()
+		.map(|(k, v)| format!("{}={}", k, v))
+		.collect::<Vec<_>>()
+		.join("&");
+	debug!("query_params: {}", &body);
+
+	let mut req = build_mobileconf_request(account, reqwest::Method::POST, "multiajaxop", vec![]);
+	req.body = Some(body);
+	req
+}
+
+pub(crate) fn parse_confirmation_list(text: &str) -> Result<Vec<Confirmation>, ConfirmerError> {
+	debug!("Confirmations response: {}", text);
+
+	let mut deser = serde_json::Deserializer::from_str(text);
+	let body: ConfirmationListResponse = serde_path_to_error::deserialize(&mut deser)?;
+
+	if body.needauth.unwrap_or(false) {
+		return Err(ConfirmerError::InvalidTokens);
+	}
+	if !body.success {
+		if let Some(msg) = body.message {
+			return Err(ConfirmerError::RemoteFailureWithMessage(msg));
+		} else {
+			return Err(ConfirmerError::RemoteFailure);
 		}
+	}
+	Ok(body.conf)
+}
 
-		let cookies = self.build_cookie_jar();
-		let client = self.transport.innner_http_client()?;
+pub(crate) fn parse_send_confirmation_response(text: &str) -> Result<(), ConfirmerError> {
+	let mut deser = serde_json::Deserializer::from_str(text);
+	let body: SendConfirmationResponse = serde_path_to_error::deserialize(&mut deser)?;
 
-		let time = steamapi::get_server_time(self.transport.clone())?.server_time();
-		let query_params = self.get_confirmation_query_params("details", time);
+	if body.needsauth.unwrap_or(false) {
+		return Err(ConfirmerError::InvalidTokens);
+	}
+	if !body.success {
+		if let Some(msg) = body.message {
+			return Err(ConfirmerError::RemoteFailureWithMessage(msg));
+		} else {
+			return Err(ConfirmerError::RemoteFailure);
+		}
+	}
 
-		let resp = client
-			.get(
-				format!(
-					"https://steamcommunity.com/mobileconf/details/{}",
-					conf.into().id
-				)
-				.parse::<Url>()
-				.unwrap(),
-			)
-			.header(USER_AGENT, "steamguard-cli")
-			.header(COOKIE, cookies.cookies(&STEAM_COOKIE_URL).unwrap())
-			.query(&query_params)
-			.send()?;
-
-		let text = resp.text()?;
-		let mut deser = serde_json::Deserializer::from_str(text.as_str());
-		let body: ConfirmationDetailsResponse = serde_path_to_error::deserialize(&mut deser)?;
-
-		ensure!(body.success);
-		Ok(body.html)
+	Ok(())
+}
+
+pub(crate) fn parse_confirmation_details(text: &str) -> anyhow::Result<ConfirmationDetails> {
+	#[derive(Debug, Clone, Deserialize)]
+	struct ConfirmationDetailsResponse {
+		pub success: bool,
+		pub html: String,
 	}
+
+	let mut deser = serde_json::Deserializer::from_str(text);
+	let body: ConfirmationDetailsResponse = serde_path_to_error::deserialize(&mut deser)?;
+
+	ensure!(body.success, "failed to get confirmation details");
+	Ok(ConfirmationDetails::parse(&body.html))
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -371,7 +337,7 @@ pub enum ConfirmationAction {
 }
 
 impl ConfirmationAction {
-	fn to_operation(self) -> &'static str {
+	pub(crate) fn to_operation(self) -> &'static str {
 		match self {
 			ConfirmationAction::Accept => "allow",
 			ConfirmationAction::Deny => "cancel",
@@ -395,8 +361,18 @@ pub enum ConfirmerError {
 	Unknown(#[from] anyhow::Error),
 }
 
+impl From<TransportError> for ConfirmerError {
+	fn from(err: TransportError) -> Self {
+		match err {
+			TransportError::Unauthorized => Self::InvalidTokens,
+			TransportError::NetworkFailure(err) => Self::NetworkFailure(err),
+			err => Self::Unknown(err.into()),
+		}
+	}
+}
+
 /// A mobile confirmation. There are multiple things that can be confirmed, like trade offers.
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Confirmation {
 	#[serde(rename = "type")]
 	pub conf_type: ConfirmationType,
@@ -444,9 +420,19 @@ impl<'a> From<&'a Confirmation> for ConfirmationId<'a> {
 	}
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, num_enum::FromPrimitive)]
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	Eq,
+	Serialize,
+	Deserialize,
+	num_enum::FromPrimitive,
+	num_enum::IntoPrimitive,
+)]
 #[repr(u32)]
-#[serde(from = "u32")]
+#[serde(from = "u32", into = "u32")]
 /// Source: <https://github.com/SteamDatabase/SteamTracking/blob/6e7797e69b714c59f4b5784780b24753c17732ba/Structs/enums.steamd#L1607-L1616>
 /// There are also some additional undocumented types.
 pub enum ConfirmationType {
@@ -470,6 +456,29 @@ pub enum ConfirmationType {
 	Unknown(u32),
 }
 
+impl std::str::FromStr for ConfirmationType {
+	type Err = anyhow::Error;
+
+	/// Parses a confirmation type from its kebab-case name (eg. `market-sell`) or its numeric value.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Ok(n) = s.parse::<u32>() {
+			return Ok(n.into());
+		}
+		let conf_type = match s.to_lowercase().replace('_', "-").as_str() {
+			"test" => ConfirmationType::Test,
+			"trade" => ConfirmationType::Trade,
+			"market-sell" => ConfirmationType::MarketSell,
+			"feature-opt-out" => ConfirmationType::FeatureOptOut,
+			"phone-number-change" => ConfirmationType::PhoneNumberChange,
+			"account-recovery" => ConfirmationType::AccountRecovery,
+			"api-key-creation" => ConfirmationType::ApiKeyCreation,
+			"join-steam-family" => ConfirmationType::JoinSteamFamily,
+			_ => bail!("Unknown confirmation type: {}", s),
+		};
+		Ok(conf_type)
+	}
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ConfirmationListResponse {
 	pub success: bool,
@@ -561,6 +570,27 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_parse_confirmation_type() {
+		assert_eq!(
+			"market-sell".parse::<ConfirmationType>().unwrap(),
+			ConfirmationType::MarketSell
+		);
+		assert_eq!(
+			"api_key_creation".parse::<ConfirmationType>().unwrap(),
+			ConfirmationType::ApiKeyCreation
+		);
+		assert_eq!(
+			"2".parse::<ConfirmationType>().unwrap(),
+			ConfirmationType::Trade
+		);
+		assert_eq!(
+			"42".parse::<ConfirmationType>().unwrap(),
+			ConfirmationType::Unknown(42)
+		);
+		assert!("not-a-type".parse::<ConfirmationType>().is_err());
+	}
+
 	#[test]
 	fn test_generate_confirmation_hash_for_time() {
 		assert_eq!(
@@ -0,0 +1,296 @@
+//! Parsing for the HTML page returned by `GET /mobileconf/details/:id`.
+
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+
+/// Steam IDs of individual accounts are their account ID plus this.
+const STEAM_ID_BASE: u64 = 76561197960265728;
+
+lazy_static! {
+	static ref TRADE_OFFER: Selector = Selector::parse(".tradeoffer").unwrap();
+	static ref PARTNER_AVATAR: Selector = Selector::parse(
+		".mobileconf_trade_partner [data-miniprofile], .tradeoffer_header [data-miniprofile]"
+	)
+	.unwrap();
+	static ref PARTNER_NAME: Selector =
+		Selector::parse(".mobileconf_trade_partner_name, .tradeoffer_header a").unwrap();
+	static ref PARTNER_LEVEL: Selector = Selector::parse(".friendPlayerLevelNum").unwrap();
+	static ref ITEMS: Selector = Selector::parse(".tradeoffer_items").unwrap();
+	static ref ITEMS_HEADER: Selector = Selector::parse(".tradeoffer_items_header").unwrap();
+	static ref ITEM: Selector = Selector::parse(".trade_item[data-economy-item]").unwrap();
+	static ref ITEM_IMAGE: Selector = Selector::parse("img[alt]").unwrap();
+	static ref ITEM_AMOUNT: Selector = Selector::parse(".item_currency_amount").unwrap();
+	static ref WARNING: Selector =
+		Selector::parse(".mobileconf_trade_warning, .tradeoffer_escrow_warning").unwrap();
+}
+
+/// What a confirmation is about, parsed from the details page that the mobile app shows before accepting it.
+///
+/// Parsing is best effort. Anything that can't be found is left empty, and the original HTML is kept in `html`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfirmationDetails {
+	/// Only present for trade offers.
+	pub trade: Option<TradeOfferDetails>,
+	/// Warnings shown with the confirmation, like trade holds.
+	pub warnings: Vec<String>,
+	/// The visible text of the page, one entry per line, for confirmations that aren't trade offers.
+	pub text: Vec<String>,
+	#[serde(skip)]
+	pub html: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeOfferDetails {
+	pub trade_offer_id: Option<u64>,
+	pub partner: TradePartner,
+	/// Items that leave this account.
+	pub items_given: Vec<TradeItem>,
+	/// Items that this account receives.
+	pub items_received: Vec<TradeItem>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradePartner {
+	pub name: Option<String>,
+	pub steam_id: Option<u64>,
+	/// Steam level
+	pub level: Option<u32>,
+}
+
+/// An item in a trade offer. Steam only includes the name of the item if it's shown in the page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeItem {
+	pub app_id: u32,
+	pub class_id: u64,
+	pub instance_id: u64,
+	pub name: Option<String>,
+	pub amount: u64,
+}
+
+impl std::fmt::Display for TradeItem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.amount != 1 {
+			write!(f, "{}x ", self.amount)?;
+		}
+		match &self.name {
+			Some(name) => write!(f, "{}", name),
+			None => write!(f, "app {} class {}", self.app_id, self.class_id),
+		}
+	}
+}
+
+impl ConfirmationDetails {
+	pub fn parse(html: &str) -> Self {
+		let document = Html::parse_fragment(html);
+		let trade = document
+			.select(&TRADE_OFFER)
+			.next()
+			.map(|offer| parse_trade_offer(&document, offer));
+		let warnings = document.select(&WARNING).map(element_text).collect();
+		let text = match trade {
+			Some(_) => vec![],
+			None => visible_text(&document),
+		};
+		Self {
+			trade,
+			warnings,
+			text,
+			html: html.to_owned(),
+		}
+	}
+}
+
+fn parse_trade_offer(document: &Html, offer: ElementRef) -> TradeOfferDetails {
+	let partner = TradePartner {
+		name: document
+			.select(&PARTNER_NAME)
+			.map(element_text)
+			.find(|name| !name.is_empty()),
+		steam_id: document
+			.select(&PARTNER_AVATAR)
+			.find_map(|e| e.attr("data-miniprofile")?.parse::<u64>().ok())
+			.map(|account_id| account_id + STEAM_ID_BASE),
+		level: document
+			.select(&PARTNER_LEVEL)
+			.find_map(|e| element_text(e).parse().ok()),
+	};
+
+	// The first list belongs to whoever made the offer. Its header starts with "You" if that's us.
+	let lists: Vec<_> = offer.select(&ITEMS).collect();
+	let first_is_ours = lists.first().is_some_and(|list| {
+		list.select(&ITEMS_HEADER)
+			.next()
+			.is_some_and(|header| element_text(header).to_lowercase().starts_with("you"))
+	});
+	let mut items = lists
+		.iter()
+		.map(|list| list.select(&ITEM).filter_map(parse_item).collect());
+	let first = items.next().unwrap_or_default();
+	let second = items.next().unwrap_or_default();
+	let (items_given, items_received) = if first_is_ours {
+		(first, second)
+	} else {
+		(second, first)
+	};
+
+	TradeOfferDetails {
+		// `id="tradeofferid_6549812364"`
+		trade_offer_id: offer
+			.attr("id")
+			.and_then(|id| id.strip_prefix("tradeofferid_"))
+			.or_else(|| offer.attr("data-tradeofferid"))
+			.and_then(|id| id.parse().ok()),
+		partner,
+		items_given,
+		items_received,
+	}
+}
+
+/// Items are identified like `classinfo/730/310776560/302028390`.
+fn parse_item(item: ElementRef) -> Option<TradeItem> {
+	let mut parts = item
+		.attr("data-economy-item")?
+		.strip_prefix("classinfo/")?
+		.split('/');
+	let app_id = parts.next()?.parse().ok()?;
+	let class_id = parts.next()?.parse().ok()?;
+	let instance_id = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+	Some(TradeItem {
+		app_id,
+		class_id,
+		instance_id,
+		name: item
+			.select(&ITEM_IMAGE)
+			.next()
+			.and_then(|img| img.attr("alt"))
+			.map(str::trim)
+			.filter(|alt| !alt.is_empty())
+			.map(str::to_owned),
+		amount: item
+			.select(&ITEM_AMOUNT)
+			.next()
+			.and_then(|e| element_text(e).parse().ok())
+			.unwrap_or(1),
+	})
+}
+
+/// The text of an element, with whitespace collapsed.
+fn element_text(element: ElementRef) -> String {
+	collapse_whitespace(&element.text().collect::<String>())
+}
+
+fn collapse_whitespace(s: &str) -> String {
+	s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Every piece of text that would be shown on the page, skipping scripts and styles.
+fn visible_text(document: &Html) -> Vec<String> {
+	document
+		.tree
+		.nodes()
+		.filter_map(|node| {
+			let Node::Text(text) = node.value() else {
+				return None;
+			};
+			let hidden = node.ancestors().any(|a| {
+				a.value()
+					.as_element()
+					.is_some_and(|e| matches!(e.name(), "script" | "style" | "head" | "title"))
+			});
+			if hidden {
+				return None;
+			}
+			Some(collapse_whitespace(text))
+		})
+		.filter(|line| !line.is_empty())
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_trade_offer() {
+		let details = ConfirmationDetails::parse(include_str!(
+			"../fixtures/confirmations/trade-offer-details.html"
+		));
+		let trade = details.trade.expect("should find the trade offer");
+		assert_eq!(trade.trade_offer_id, Some(6549812364));
+		assert_eq!(
+			trade.partner,
+			TradePartner {
+				name: Some("Trade Partner".into()),
+				steam_id: Some(76561199000007892),
+				level: Some(23),
+			}
+		);
+		assert_eq!(
+			trade.items_given,
+			vec![
+				TradeItem {
+					app_id: 730,
+					class_id: 310776560,
+					instance_id: 302028390,
+					name: Some("AK-47 | Redline (Field-Tested)".into()),
+					amount: 1,
+				},
+				TradeItem {
+					app_id: 753,
+					class_id: 667924416,
+					instance_id: 0,
+					name: Some("Summer 2021 - Horror".into()),
+					amount: 3,
+				},
+			]
+		);
+		assert_eq!(trade.items_received.len(), 1);
+		assert_eq!(
+			trade.items_received[0].to_string(),
+			"Mann Co. Supply Crate Key"
+		);
+		assert_eq!(trade.items_given[1].to_string(), "3x Summer 2021 - Horror");
+		assert_eq!(details.warnings.len(), 1);
+		assert!(details.warnings[0].contains("held by Steam for 15 days"));
+		assert!(details.text.is_empty());
+	}
+
+	#[test]
+	fn test_parse_offer_from_partner() {
+		let html = r#"<div class="tradeoffer" data-tradeofferid="1">
+			<div class="tradeoffer_items primary">
+				<div class="tradeoffer_items_header">Partner offered:</div>
+				<div class="trade_item" data-economy-item="classinfo/440/1/2"></div>
+			</div>
+			<div class="tradeoffer_items secondary">
+				<div class="tradeoffer_items_header">For your:</div>
+			</div>
+		</div>"#;
+		let trade = ConfirmationDetails::parse(html).trade.unwrap();
+		assert!(trade.items_given.is_empty());
+		assert_eq!(trade.items_received.len(), 1);
+		assert_eq!(trade.items_received[0].to_string(), "app 440 class 1");
+	}
+
+	#[test]
+	fn test_parse_other_pages() {
+		for html in [
+			include_str!("../fixtures/confirmations/email-change.html"),
+			include_str!("../fixtures/confirmations/phone-number-change.html"),
+			include_str!("../fixtures/confirmations/multiple-confirmations.html"),
+		] {
+			let details = ConfirmationDetails::parse(html);
+			assert_eq!(details.trade, None);
+			assert!(details.warnings.is_empty());
+			assert!(!details.text.is_empty());
+			assert!(details.text.iter().all(|line| !line.contains("function")));
+		}
+
+		let details = ConfirmationDetails::parse(include_str!(
+			"../fixtures/confirmations/multiple-confirmations.html"
+		));
+		assert!(details
+			.text
+			.contains(&"Sell - Summer 2021 - Horror".to_owned()));
+	}
+}
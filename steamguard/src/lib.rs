@@ -6,6 +6,7 @@ pub use confirmation::*;
 pub use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use std::io::Read;
+pub use timesync::TimeSync;
 use token::Tokens;
 use transport::{Transport, TransportError};
 pub use userlogin::{DeviceDetails, LoginError, UserLogin};
@@ -19,12 +20,15 @@ extern crate maplit;
 pub mod accountlinker;
 mod api_responses;
 pub mod approver;
+#[cfg(feature = "async")]
+pub mod asynchronous;
 mod confirmation;
 pub mod phonelinker;
 pub mod protobufs;
 pub mod refresher;
 mod secret_string;
 pub mod steamapi;
+pub mod timesync;
 pub mod token;
 pub mod transport;
 pub mod userlogin;
@@ -49,6 +53,15 @@ pub struct SteamGuardAccount {
 	#[serde(with = "secret_string")]
 	pub secret_1: SecretString,
 	pub tokens: Option<Tokens>,
+	/// Credentials stored so the account can log in again without prompting. Only present if the user chose to store them.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub credentials: Option<Credentials>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+	#[serde(with = "secret_string")]
+	pub password: SecretString,
 }
 
 impl Default for SteamGuardAccount {
@@ -65,6 +78,7 @@ impl Default for SteamGuardAccount {
 			device_id: String::from(""),
 			secret_1: String::from("").into(),
 			tokens: None,
+			credentials: None,
 		}
 	}
 }
@@ -67,6 +67,18 @@ impl<'a, T: BuildableRequest> ApiRequest<'a, T> {
 		self.access_token
 	}
 
+	pub(crate) fn interface(&self) -> &str {
+		&self.api_interface
+	}
+
+	pub(crate) fn method(&self) -> &str {
+		&self.api_method
+	}
+
+	pub(crate) fn version(&self) -> u32 {
+		self.api_version
+	}
+
 	pub(crate) fn build_url(&self) -> String {
 		format!(
 			"{}/{}/{}/v{}",
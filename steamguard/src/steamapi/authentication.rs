@@ -153,14 +153,17 @@ where
 		Ok(resp)
 	}
 
+	/// Revoke a token, like the session's own refresh token. Any access tokens generated from a revoked refresh token stop working too.
 	pub fn revoke_access_token(
 		&mut self,
-		req: CAuthenticationSupport_RevokeToken_Request,
-	) -> Result<ApiResponse<CAuthenticationSupport_RevokeToken_Response>, TransportError> {
-		let req = ApiRequest::new(SERVICE_NAME, "RevokeToken", 1u32, req);
+		req: CAuthentication_Token_Revoke_Request,
+		access_token: &Jwt,
+	) -> Result<ApiResponse<CAuthentication_Token_Revoke_Response>, TransportError> {
+		let req =
+			ApiRequest::new(SERVICE_NAME, "RevokeToken", 1u32, req).with_access_token(access_token);
 		let resp = self
 			.transport
-			.send_request::<CAuthenticationSupport_RevokeToken_Request, CAuthenticationSupport_RevokeToken_Response>(
+			.send_request::<CAuthentication_Token_Revoke_Request, CAuthentication_Token_Revoke_Response>(
 				req,
 			)?;
 		Ok(resp)
@@ -261,6 +264,7 @@ impl_buildable_req!(CAuthentication_MigrateMobileSession_Request, false);
 impl_buildable_req!(CAuthentication_PollAuthSessionStatus_Request, false);
 impl_buildable_req!(CAuthentication_RefreshToken_Revoke_Request, true);
 impl_buildable_req!(CAuthenticationSupport_RevokeToken_Request, true);
+impl_buildable_req!(CAuthentication_Token_Revoke_Request, true);
 impl_buildable_req!(
 	CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request,
 	false
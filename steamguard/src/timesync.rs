@@ -0,0 +1,258 @@
+//! Keeps track of the difference between the local clock and Steam's clock.
+
+use std::{
+	io::Write,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{steamapi, transport::Transport};
+
+/// How long a measured offset is trusted before Steam is asked for the time again.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Tracks the offset between the local clock and Steam's clock, so Steam only needs to be asked for the time once in a while, instead of before every request that needs it.
+///
+/// Clones share the same offset, so one `TimeSync` can be handed to everything that needs the time. If it was created with [`TimeSync::with_file`], the offset is loaded from that file, and saved to it every time it's measured.
+#[derive(Debug, Clone)]
+pub struct TimeSync {
+	offset: Arc<Mutex<Option<TimeOffset>>>,
+	path: Option<PathBuf>,
+	ttl: Duration,
+}
+
+/// An offset between the local clock and Steam's clock, measured with `ITwoFactorService/QueryTime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeOffset {
+	/// Seconds to add to the local clock to get Steam's time.
+	pub offset: i64,
+	/// Local time when the offset was measured, in seconds since the unix epoch.
+	pub synced_at: u64,
+}
+
+impl TimeOffset {
+	pub fn new(server_time: u64, local_time: u64) -> Self {
+		Self {
+			offset: server_time as i64 - local_time as i64,
+			synced_at: local_time,
+		}
+	}
+
+	/// Steam's time, given the local time.
+	pub fn apply(&self, local_time: u64) -> u64 {
+		local_time.saturating_add_signed(self.offset)
+	}
+
+	/// Whether the offset is younger than `ttl`. If the local clock went backwards since it was measured, it is not.
+	pub fn is_fresh(&self, local_time: u64, ttl: Duration) -> bool {
+		local_time
+			.checked_sub(self.synced_at)
+			.is_some_and(|age| age < ttl.as_secs())
+	}
+}
+
+impl Default for TimeSync {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl TimeSync {
+	/// A `TimeSync` that only keeps the offset in memory.
+	pub fn new() -> Self {
+		Self {
+			offset: Arc::new(Mutex::new(None)),
+			path: None,
+			ttl: DEFAULT_TTL,
+		}
+	}
+
+	/// A `TimeSync` that persists the offset to `path`. A missing or unreadable file is treated as if the offset was never measured.
+	pub fn with_file(path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+		let offset = match load_offset(&path) {
+			Ok(offset) => offset,
+			Err(err) => {
+				warn!("Ignoring time offset in {}: {}", path.display(), err);
+				None
+			}
+		};
+		Self {
+			offset: Arc::new(Mutex::new(offset)),
+			path: Some(path),
+			ttl: DEFAULT_TTL,
+		}
+	}
+
+	pub fn with_ttl(mut self, ttl: Duration) -> Self {
+		self.ttl = ttl;
+		self
+	}
+
+	/// The last measured offset, if any.
+	pub fn offset(&self) -> Option<TimeOffset> {
+		*self.offset.lock().unwrap()
+	}
+
+	/// Steam's current time. Only asks Steam if the offset is missing or older than the TTL.
+	pub fn server_time<T: Transport>(&self, transport: T) -> anyhow::Result<u64> {
+		let now = local_time();
+		if let Some(offset) = self.offset().filter(|o| o.is_fresh(now, self.ttl)) {
+			return Ok(offset.apply(now));
+		}
+		self.sync(transport)
+	}
+
+	/// Ask Steam for the time, and remember the offset. Returns Steam's current time.
+	pub fn sync<T: Transport>(&self, transport: T) -> anyhow::Result<u64> {
+		debug!("syncing time with steam");
+		let server_time = steamapi::get_server_time(transport)?.server_time();
+		self.update(TimeOffset::new(server_time, local_time()));
+		Ok(server_time)
+	}
+
+	/// Async version of [`TimeSync::server_time`].
+	#[cfg(feature = "async")]
+	pub async fn server_time_async<T: crate::asynchronous::AsyncTransport>(
+		&self,
+		transport: T,
+	) -> anyhow::Result<u64> {
+		let now = local_time();
+		if let Some(offset) = self.offset().filter(|o| o.is_fresh(now, self.ttl)) {
+			return Ok(offset.apply(now));
+		}
+		debug!("syncing time with steam");
+		let server_time = crate::asynchronous::steamapi::get_server_time(transport)
+			.await?
+			.server_time();
+		self.update(TimeOffset::new(server_time, local_time()));
+		Ok(server_time)
+	}
+
+	/// Steam's current time without asking Steam, using the last known offset no matter how old it is. Falls back to the local clock if the offset was never measured.
+	pub fn offline_time(&self) -> u64 {
+		let now = local_time();
+		match self.offset() {
+			Some(offset) => offset.apply(now),
+			None => {
+				debug!("no known time offset, using the local clock");
+				now
+			}
+		}
+	}
+
+	fn update(&self, offset: TimeOffset) {
+		debug!("time offset: {}s", offset.offset);
+		*self.offset.lock().unwrap() = Some(offset);
+		if let Some(path) = &self.path {
+			if let Err(err) = save_offset(path, &offset) {
+				warn!("Failed to save time offset to {}: {}", path.display(), err);
+			}
+		}
+	}
+}
+
+fn local_time() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+fn load_offset(path: &Path) -> anyhow::Result<Option<TimeOffset>> {
+	match std::fs::read(path) {
+		Ok(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+		Err(err) => Err(err.into()),
+	}
+}
+
+fn save_offset(path: &Path, offset: &TimeOffset) -> anyhow::Result<()> {
+	// write to a uniquely named temporary file first, so concurrent readers never see a partial file, and concurrent writers don't clobber each other's temporary files
+	let dir = match path.parent() {
+		Some(parent) if !parent.as_os_str().is_empty() => parent,
+		_ => Path::new("."),
+	};
+	let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+	temp.write_all(&serde_json::to_vec(offset)?)?;
+	temp.persist(path)?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::transport::MockTransport;
+
+	#[test]
+	fn test_offset() {
+		let offset = TimeOffset::new(1000, 990);
+		assert_eq!(offset.offset, 10);
+		assert_eq!(offset.apply(1990), 2000);
+		assert_eq!(TimeOffset::new(990, 1000).apply(2000), 1990);
+
+		let ttl = Duration::from_secs(60);
+		assert!(offset.is_fresh(990, ttl));
+		assert!(offset.is_fresh(1049, ttl));
+		assert!(!offset.is_fresh(1050, ttl));
+		assert!(!offset.is_fresh(989, ttl));
+	}
+
+	#[test]
+	fn test_queries_steam_once() -> anyhow::Result<()> {
+		let transport = MockTransport::new().with_server_time(local_time() + 100);
+		let time_sync = TimeSync::new();
+		let shared = time_sync.clone();
+		let first = time_sync.server_time(transport.clone())?;
+		let second = shared.server_time(transport.clone())?;
+		assert!(second >= first && second - first <= 1);
+		assert_eq!(transport.requests().len(), 1);
+		assert!((99..=100).contains(&shared.offset().unwrap().offset));
+
+		let expired = time_sync.with_ttl(Duration::ZERO);
+		expired.server_time(transport.clone())?;
+		assert_eq!(transport.requests().len(), 2);
+		Ok(())
+	}
+
+	#[test]
+	fn test_persists_offset() -> anyhow::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("timesync.json");
+		let transport = MockTransport::new().with_server_time(local_time() - 50);
+
+		assert!(TimeSync::with_file(&path).offset().is_none());
+		TimeSync::with_file(&path).sync(transport)?;
+		let reloaded = TimeSync::with_file(&path);
+		assert!((-51..=-50).contains(&reloaded.offset().unwrap().offset));
+		assert!(reloaded.offline_time() < local_time());
+
+		std::fs::write(&path, "garbage")?;
+		assert!(TimeSync::with_file(&path).offset().is_none());
+		Ok(())
+	}
+
+	#[test]
+	fn test_concurrent_saves() -> anyhow::Result<()> {
+		let dir = tempfile::TempDir::new()?;
+		let path = dir.path().join("timesync.json");
+		std::thread::scope(|scope| {
+			for i in 0..8 {
+				let path = &path;
+				scope.spawn(move || {
+					for _ in 0..20 {
+						save_offset(path, &TimeOffset::new(1000 + i, 1000)).unwrap();
+					}
+				});
+			}
+		});
+		assert!(load_offset(&path)?.is_some());
+		// no temporary files are left behind
+		assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+		Ok(())
+	}
+}
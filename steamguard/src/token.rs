@@ -177,6 +177,12 @@ pub struct SteamJwtData {
 	/// Subject (steam id)
 	pub sub: String,
 	pub jti: String,
+	/// Not before
+	pub nbf: Option<u64>,
+	/// When the refresh token that this token was generated from expires. Only present in access tokens.
+	pub rt_exp: Option<u64>,
+	/// Session persistence, see [`ESessionPersistence`](crate::protobufs::enums::ESessionPersistence).
+	pub per: Option<i32>,
 }
 
 impl SteamJwtData {
@@ -260,6 +266,9 @@ mod tests {
 		assert_eq!(data.aud, vec!["web", "renew", "derive"]);
 		assert_eq!(data.sub, "76561199155706892");
 		assert_eq!(data.jti, "18C5_22B3F431_CDF6A");
+		assert_eq!(data.nbf, Some(1678464837));
+		assert_eq!(data.rt_exp, None);
+		assert_eq!(data.per, Some(1));
 	}
 
 	#[test]
@@ -269,5 +278,8 @@ mod tests {
 
 		assert_eq!(data.aud, vec!["web", "mobile"]);
 		assert_eq!(data.sub, "76561199441992970");
+		assert_eq!(data.nbf, Some(1683031051));
+		assert_eq!(data.rt_exp, Some(1710011689));
+		assert_eq!(data.per, Some(0));
 	}
 }
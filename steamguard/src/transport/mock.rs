@@ -0,0 +1,401 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{Arc, Mutex},
+};
+
+use log::debug;
+use protobuf::MessageFull;
+
+use super::{MobileConfRequest, Transport, TransportError};
+use crate::{
+	protobufs::service_twofactor::CTwoFactor_Time_Response,
+	steamapi::{ApiRequest, ApiResponse, BuildableRequest, EResult},
+};
+
+/// A transport that answers requests with canned responses, without touching the network. Useful for tests and dry runs.
+///
+/// Responses are registered per endpoint, and are returned in the order they were registered. The last response for an endpoint is repeated for any further requests. Requests to endpoints without any responses fail with [`TransportError::Unknown`].
+///
+/// Clones share the same responses and request log, so the transport can be handed to anything that needs a `Transport + Clone`.
+///
+/// ```
+/// # use steamguard::transport::MockTransport;
+/// let transport = MockTransport::new().with_server_time(1616374841);
+/// transport.respond_mobileconf("getlist", r#"{"success": true, "conf": []}"#);
+/// let time = steamguard::steamapi::get_server_time(transport.clone()).unwrap();
+/// assert_eq!(time.server_time(), 1616374841);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+	state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+	responses: HashMap<(String, String), VecDeque<MockResponse>>,
+	mobileconf: HashMap<String, VecDeque<String>>,
+	requests: Vec<MockRequest>,
+}
+
+#[derive(Debug, Clone)]
+enum MockResponse {
+	Ok {
+		result: EResult,
+		error_message: Option<String>,
+		body: Vec<u8>,
+	},
+	Unauthorized,
+}
+
+/// A request that was received by [`MockTransport`].
+#[derive(Debug, Clone)]
+pub enum MockRequest {
+	Api {
+		interface: String,
+		method: String,
+		version: u32,
+		has_access_token: bool,
+		/// The request message, encoded as protobuf. Use [`MockRequest::decode`] to read it.
+		body: Vec<u8>,
+	},
+	MobileConf(MobileConfRequest),
+}
+
+impl MockRequest {
+	/// Decode the protobuf request message. Fails for mobileconf requests.
+	pub fn decode<T: MessageFull>(&self) -> anyhow::Result<T> {
+		match self {
+			Self::Api { body, .. } => Ok(T::parse_from_bytes(body)?),
+			Self::MobileConf(_) => bail!("mobileconf requests are not protobuf encoded"),
+		}
+	}
+
+	/// `interface/method` for API requests, or `mobileconf/path` for mobileconf requests.
+	pub fn endpoint(&self) -> String {
+		match self {
+			Self::Api {
+				interface, method, ..
+			} => format!("{}/{}", interface, method),
+			Self::MobileConf(req) => format!("mobileconf/{}", req.path),
+		}
+	}
+}
+
+impl MockTransport {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Respond to `ITwoFactorService/QueryTime` with the given time.
+	pub fn with_server_time(self, server_time: u64) -> Self {
+		let mut resp = CTwoFactor_Time_Response::new();
+		resp.set_server_time(server_time);
+		self.respond_with("ITwoFactorService", "QueryTime", resp);
+		self
+	}
+
+	/// Queue a successful response for an API endpoint, eg. `("IAuthenticationService", "PollAuthSessionStatus")`.
+	pub fn respond_with<Res: MessageFull>(
+		&self,
+		interface: impl Into<String>,
+		method: impl Into<String>,
+		response: Res,
+	) -> &Self {
+		self.respond_with_result(interface, method, EResult::OK, response)
+	}
+
+	/// Queue a response with a specific `EResult` for an API endpoint.
+	pub fn respond_with_result<Res: MessageFull>(
+		&self,
+		interface: impl Into<String>,
+		method: impl Into<String>,
+		result: EResult,
+		response: Res,
+	) -> &Self {
+		let body = response
+			.write_to_bytes()
+			.expect("failed to encode mock response");
+		self.push_response(
+			interface,
+			method,
+			MockResponse::Ok {
+				result,
+				error_message: None,
+				body,
+			},
+		)
+	}
+
+	/// Queue a response for an API endpoint from its JSON representation, like the ones shown by `steamguard -v trace`.
+	pub fn respond_with_json<Res: MessageFull>(
+		&self,
+		interface: impl Into<String>,
+		method: impl Into<String>,
+		json: &str,
+	) -> anyhow::Result<&Self> {
+		let response: Res = protobuf_json_mapping::parse_from_str(json)?;
+		Ok(self.respond_with(interface, method, response))
+	}
+
+	/// Queue an HTTP 401 response for an API endpoint.
+	pub fn respond_unauthorized(
+		&self,
+		interface: impl Into<String>,
+		method: impl Into<String>,
+	) -> &Self {
+		self.push_response(interface, method, MockResponse::Unauthorized)
+	}
+
+	/// Queue a response body for a mobileconf endpoint, eg. `getlist`, `multiajaxop` or `details/1234`.
+	pub fn respond_mobileconf(&self, path: impl Into<String>, body: impl Into<String>) -> &Self {
+		let mut state = self.state.lock().unwrap();
+		state
+			.mobileconf
+			.entry(path.into())
+			.or_default()
+			.push_back(body.into());
+		drop(state);
+		self
+	}
+
+	/// All requests received so far, in order.
+	pub fn requests(&self) -> Vec<MockRequest> {
+		self.state.lock().unwrap().requests.clone()
+	}
+
+	fn push_response(
+		&self,
+		interface: impl Into<String>,
+		method: impl Into<String>,
+		response: MockResponse,
+	) -> &Self {
+		let mut state = self.state.lock().unwrap();
+		state
+			.responses
+			.entry((interface.into(), method.into()))
+			.or_default()
+			.push_back(response);
+		drop(state);
+		self
+	}
+}
+
+/// Take the next response from the queue, keeping the last one around so it can be repeated.
+fn next_response<T: Clone>(queue: Option<&mut VecDeque<T>>) -> Option<T> {
+	let queue = queue?;
+	if queue.len() > 1 {
+		queue.pop_front()
+	} else {
+		queue.front().cloned()
+	}
+}
+
+impl Transport for MockTransport {
+	fn send_request<Req: BuildableRequest + MessageFull, Res: MessageFull>(
+		&self,
+		req: ApiRequest<Req>,
+	) -> Result<ApiResponse<Res>, TransportError> {
+		debug!("Mock Request: {}/{}", req.interface(), req.method());
+		let mut state = self.state.lock().unwrap();
+		state.requests.push(MockRequest::Api {
+			interface: req.interface().to_owned(),
+			method: req.method().to_owned(),
+			version: req.version(),
+			has_access_token: req.access_token().is_some(),
+			body: req.request_data().write_to_bytes()?,
+		});
+
+		if Req::requires_access_token() && req.access_token().is_none() {
+			return Err(TransportError::Unauthorized);
+		}
+
+		let key = (req.interface().to_owned(), req.method().to_owned());
+		let Some(response) = next_response(state.responses.get_mut(&key)) else {
+			return Err(anyhow!(
+				"MockTransport has no response for {}/{}",
+				req.interface(),
+				req.method()
+			)
+			.into());
+		};
+		match response {
+			MockResponse::Ok {
+				result,
+				error_message,
+				body,
+			} => Ok(ApiResponse {
+				result,
+				error_message,
+				response_data: Res::parse_from_bytes(&body)?,
+			}),
+			MockResponse::Unauthorized => Err(TransportError::Unauthorized),
+		}
+	}
+
+	fn close(&mut self) {}
+
+	fn send_mobileconf_request(&self, req: MobileConfRequest) -> Result<String, TransportError> {
+		debug!("Mock Request: mobileconf/{}", req.path);
+		let mut state = self.state.lock().unwrap();
+		let path = req.path.clone();
+		state.requests.push(MockRequest::MobileConf(req));
+		next_response(state.mobileconf.get_mut(&path))
+			.ok_or_else(|| anyhow!("MockTransport has no response for mobileconf/{}", path).into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		protobufs::steammessages_auth_steamclient::{
+			CAuthentication_GetAuthSessionInfo_Response,
+			CAuthentication_GetAuthSessionsForAccount_Response,
+		},
+		token::Tokens,
+		ConfirmationType, Confirmer, ConfirmerError, LoginApprover, SteamGuardAccount,
+	};
+
+	fn account() -> SteamGuardAccount {
+		let mut account = SteamGuardAccount::new();
+		account.account_name = "example".into();
+		account.steam_id = 76561199000000000;
+		account.identity_secret = String::from("GQP46b73Ws7gr8GmZFR0sDuau5c=").into();
+		account.tokens = Some(Tokens::new("access".to_owned(), "refresh".to_owned()));
+		account
+	}
+
+	#[test]
+	fn test_server_time() {
+		let transport = MockTransport::new().with_server_time(1616374841);
+		let time = crate::steamapi::get_server_time(transport.clone()).unwrap();
+		assert_eq!(time.server_time(), 1616374841);
+		let time = crate::steamapi::get_server_time(transport.clone()).unwrap();
+		assert_eq!(time.server_time(), 1616374841);
+		assert_eq!(transport.requests().len(), 2);
+	}
+
+	#[test]
+	fn test_missing_response() {
+		let transport = MockTransport::new();
+		assert!(crate::steamapi::get_server_time(transport).is_err());
+	}
+
+	#[test]
+	fn test_responses_in_order() {
+		let transport = MockTransport::new();
+		transport.respond_mobileconf("getlist", "first");
+		transport.respond_mobileconf("getlist", "second");
+		let req = MobileConfRequest {
+			method: reqwest::Method::GET,
+			path: "getlist".into(),
+			query: vec![],
+			body: None,
+			cookies: String::new(),
+		};
+		assert_eq!(
+			transport.send_mobileconf_request(req.clone()).unwrap(),
+			"first"
+		);
+		assert_eq!(
+			transport.send_mobileconf_request(req.clone()).unwrap(),
+			"second"
+		);
+		assert_eq!(transport.send_mobileconf_request(req).unwrap(), "second");
+	}
+
+	#[test]
+	fn test_confirmations() {
+		let account = account();
+		let transport = MockTransport::new().with_server_time(1616374841);
+		transport.respond_mobileconf(
+			"getlist",
+			include_str!("../fixtures/confirmations/phone-number-change.json"),
+		);
+		transport.respond_mobileconf("multiajaxop", r#"{"success": true}"#);
+
+		let confirmer = Confirmer::new(transport.clone(), &account);
+		let confirmations = confirmer.get_confirmations().unwrap();
+		assert_eq!(confirmations.len(), 1);
+		assert_eq!(
+			confirmations[0].conf_type,
+			ConfirmationType::PhoneNumberChange
+		);
+		confirmer.accept_confirmations_bulk(&confirmations).unwrap();
+
+		let requests = transport.requests();
+		let MockRequest::MobileConf(submit) = requests.last().unwrap() else {
+			panic!("expected a mobileconf request");
+		};
+		assert_eq!(submit.path, "multiajaxop");
+		let body = submit.body.as_ref().unwrap();
+		assert!(body.contains("op=allow"));
+		assert!(body.contains(&format!("cid[]={}", confirmations[0].id)));
+		assert!(submit
+			.cookies
+			.contains("steamLoginSecure=76561199000000000||access"));
+	}
+
+	#[test]
+	fn test_confirmations_need_auth() {
+		let account = account();
+		let transport = MockTransport::new().with_server_time(1616374841);
+		transport.respond_mobileconf(
+			"getlist",
+			include_str!("../fixtures/confirmations/need-auth.json"),
+		);
+
+		let confirmer = Confirmer::new(transport, &account);
+		assert!(matches!(
+			confirmer.get_confirmations(),
+			Err(ConfirmerError::InvalidTokens)
+		));
+	}
+
+	#[test]
+	fn test_approver() {
+		let account = account();
+		let transport = MockTransport::new();
+		let mut sessions = CAuthentication_GetAuthSessionsForAccount_Response::new();
+		sessions.client_ids = vec![1234];
+		transport.respond_with(
+			"IAuthenticationService",
+			"GetAuthSessionsForAccount",
+			sessions,
+		);
+		transport
+			.respond_with_json::<CAuthentication_GetAuthSessionInfo_Response>(
+				"IAuthenticationService",
+				"GetAuthSessionInfo",
+				r#"{"ip": "203.0.113.7", "country": "US"}"#,
+			)
+			.unwrap();
+
+		let approver = LoginApprover::new(transport.clone(), account.tokens.as_ref().unwrap());
+		assert_eq!(approver.list_auth_sessions().unwrap(), vec![1234]);
+		let info = approver.get_auth_session_info(1234).unwrap();
+		assert_eq!(info.ip(), "203.0.113.7");
+
+		let requests = transport.requests();
+		assert_eq!(
+			requests[1].endpoint(),
+			"IAuthenticationService/GetAuthSessionInfo"
+		);
+		let req = requests[1]
+			.decode::<crate::protobufs::steammessages_auth_steamclient::CAuthentication_GetAuthSessionInfo_Request>()
+			.unwrap();
+		assert_eq!(req.client_id(), 1234);
+	}
+
+	#[test]
+	fn test_unauthorized() {
+		let account = account();
+		let transport = MockTransport::new();
+		transport.respond_unauthorized("IAuthenticationService", "GetAuthSessionsForAccount");
+		let approver = LoginApprover::new(transport, account.tokens.as_ref().unwrap());
+		assert!(matches!(
+			approver.list_auth_sessions(),
+			Err(crate::ApproverError::Unauthorized)
+		));
+	}
+}
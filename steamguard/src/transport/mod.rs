@@ -1,6 +1,10 @@
+pub mod mock;
 pub mod webapi;
 
+use log::*;
+pub use mock::MockTransport;
 use protobuf::MessageFull;
+use reqwest::header::{CONTENT_TYPE, COOKIE, USER_AGENT};
 pub use webapi::WebApiTransport;
 
 use crate::steamapi::{ApiRequest, ApiResponse, BuildableRequest};
@@ -16,6 +20,45 @@ pub trait Transport {
 	fn innner_http_client(&self) -> anyhow::Result<reqwest::blocking::Client> {
 		bail!("Transport does not support extracting HTTP client")
 	}
+
+	/// Send a request to the mobile confirmation endpoints on steamcommunity.com, and return the response body.
+	///
+	/// By default, this uses the HTTP client from [`Transport::innner_http_client`].
+	fn send_mobileconf_request(&self, req: MobileConfRequest) -> Result<String, TransportError> {
+		let client = self.innner_http_client()?;
+		let url = format!("https://steamcommunity.com/mobileconf/{}", req.path);
+		debug!("HTTP Request: {} {}", req.method, url);
+		let mut builder = client
+			.request(req.method, url)
+			.header(USER_AGENT, "steamguard-cli")
+			.header(COOKIE, req.cookies)
+			.header("Origin", "https://steamcommunity.com")
+			.query(&req.query);
+		if let Some(body) = req.body {
+			builder = builder
+				.header(
+					CONTENT_TYPE,
+					"application/x-www-form-urlencoded; charset=UTF-8",
+				)
+				.body(body);
+		}
+		let resp = builder.send()?;
+		debug!("Response HTTP status: {}", resp.status());
+		Ok(resp.text()?)
+	}
+}
+
+/// A request to `https://steamcommunity.com/mobileconf/`.
+#[derive(Debug, Clone)]
+pub struct MobileConfRequest {
+	pub method: reqwest::Method,
+	/// Path relative to `/mobileconf/`, eg. `getlist` or `details/1234`.
+	pub path: String,
+	pub query: Vec<(String, String)>,
+	/// Form encoded request body.
+	pub body: Option<String>,
+	/// Value of the `Cookie` header.
+	pub cookies: String,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -1,6 +1,7 @@
 use log::{debug, trace};
 use protobuf::MessageFull;
 use reqwest::blocking::multipart::Form;
+use reqwest::header::HeaderMap;
 
 use super::{Transport, TransportError};
 use crate::steamapi::{ApiRequest, ApiResponse, BuildableRequest, EResult};
@@ -61,56 +62,9 @@ impl Transport for WebApiTransport {
 
 		let resp = req.send()?;
 		let status = resp.status();
-		debug!("Response HTTP status: {}", status);
-
-		let eresult = if let Some(eresult) = resp.headers().get("x-eresult") {
-			let s = eresult
-				.to_str()
-				.map_err(|err| TransportError::HeaderParseFailure {
-					header: "x-eresult".to_owned(),
-					source: err.into(),
-				})?;
-			debug!("HTTP Header x-eresult: {}", s);
-			s.parse::<i32>()
-				.map_err(|err| TransportError::HeaderParseFailure {
-					header: "x-eresult".to_owned(),
-					source: err.into(),
-				})?
-				.into()
-		} else {
-			EResult::Invalid
-		};
-		let error_msg = if let Some(error_message) = resp.headers().get("x-error_message") {
-			let s = error_message
-				.to_str()
-				.map_err(|err| TransportError::HeaderParseFailure {
-					header: "x-error_message".to_owned(),
-					source: err.into(),
-				})?;
-			debug!("HTTP Header x-error_message: {}", s);
-			Some(s.to_owned())
-		} else {
-			None
-		};
-
+		let headers = resp.headers().clone();
 		let bytes = resp.bytes()?;
-		if !status.is_success() {
-			trace!("Response body (raw): {:?}", bytes);
-
-			if status == reqwest::StatusCode::UNAUTHORIZED {
-				return Err(TransportError::Unauthorized);
-			}
-		}
-
-		let res = decode_msg::<Res>(bytes.as_ref())?;
-		trace!("Response body (decoded): {:#?}", res);
-		let api_resp = ApiResponse {
-			result: eresult,
-			error_message: error_msg,
-			response_data: res,
-		};
-
-		Ok(api_resp)
+		parse_response(status, &headers, bytes.as_ref())
 	}
 
 	fn close(&mut self) {}
@@ -120,7 +74,67 @@ impl Transport for WebApiTransport {
 	}
 }
 
-fn encode_msg<T: MessageFull>(msg: &T, engine: impl base64::Engine) -> anyhow::Result<String> {
+/// Parses the response to a Steam Web API request. Shared with the async transport.
+pub(crate) fn parse_response<Res: MessageFull>(
+	status: reqwest::StatusCode,
+	headers: &HeaderMap,
+	bytes: &[u8],
+) -> Result<ApiResponse<Res>, TransportError> {
+	debug!("Response HTTP status: {}", status);
+
+	let eresult = if let Some(eresult) = headers.get("x-eresult") {
+		let s = eresult
+			.to_str()
+			.map_err(|err| TransportError::HeaderParseFailure {
+				header: "x-eresult".to_owned(),
+				source: err.into(),
+			})?;
+		debug!("HTTP Header x-eresult: {}", s);
+		s.parse::<i32>()
+			.map_err(|err| TransportError::HeaderParseFailure {
+				header: "x-eresult".to_owned(),
+				source: err.into(),
+			})?
+			.into()
+	} else {
+		EResult::Invalid
+	};
+	let error_msg = if let Some(error_message) = headers.get("x-error_message") {
+		let s = error_message
+			.to_str()
+			.map_err(|err| TransportError::HeaderParseFailure {
+				header: "x-error_message".to_owned(),
+				source: err.into(),
+			})?;
+		debug!("HTTP Header x-error_message: {}", s);
+		Some(s.to_owned())
+	} else {
+		None
+	};
+
+	if !status.is_success() {
+		trace!("Response body (raw): {:?}", bytes);
+
+		if status == reqwest::StatusCode::UNAUTHORIZED {
+			return Err(TransportError::Unauthorized);
+		}
+	}
+
+	let res = decode_msg::<Res>(bytes)?;
+	trace!("Response body (decoded): {:#?}", res);
+	let api_resp = ApiResponse {
+		result: eresult,
+		error_message: error_msg,
+		response_data: res,
+	};
+
+	Ok(api_resp)
+}
+
+pub(crate) fn encode_msg<T: MessageFull>(
+	msg: &T,
+	engine: impl base64::Engine,
+) -> anyhow::Result<String> {
 	let bytes = msg.write_to_bytes()?;
 	let b64 = engine.encode(bytes);
 	Ok(b64)
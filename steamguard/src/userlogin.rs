@@ -79,6 +79,19 @@ pub struct BeginQrLoginResponse {
 	confirmation_methonds: Vec<AllowedConfirmation>,
 }
 
+impl From<&CAuthentication_BeginAuthSessionViaQR_Response> for BeginQrLoginResponse {
+	fn from(data: &CAuthentication_BeginAuthSessionViaQR_Response) -> Self {
+		Self {
+			challenge_url: data.challenge_url().into(),
+			confirmation_methonds: data
+				.allowed_confirmations
+				.iter()
+				.map(|c| c.clone().into())
+				.collect(),
+		}
+	}
+}
+
 impl BeginQrLoginResponse {
 	pub fn challenge_url(&self) -> &String {
 		&self.challenge_url
@@ -124,17 +137,12 @@ where
 		trace!("UserLogin::begin_auth_via_credentials");
 
 		let rsa = self.client.fetch_rsa_key(account_name.to_owned())?;
-
-		let mut req = CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData::new();
-		req.set_account_name(account_name.to_owned());
-		let rsa_resp = rsa.into_response_data();
-		req.set_encryption_timestamp(rsa_resp.timestamp());
-		let encrypted_password = encrypt_password(rsa_resp, password);
-		req.set_encrypted_password(encrypted_password);
-		req.set_persistence(ESessionPersistence::k_ESessionPersistence_Persistent);
-		req.device_details = self.device_details.clone().into_message_field();
-		req.set_language(0); // english, probably
-		req.set_qos_level(2); // value from observed traffic
+		let req = build_credentials_request(
+			&self.device_details,
+			account_name,
+			rsa.into_response_data(),
+			password,
+		);
 
 		let resp = self.client.begin_auth_session_via_credentials(req)?;
 
@@ -160,24 +168,15 @@ where
 			return Err(LoginError::AuthAlreadyStarted);
 		}
 
-		let mut req = CAuthentication_BeginAuthSessionViaQR_Request::new();
-		req.set_platform_type(self.device_details.platform_type);
-		req.set_device_friendly_name(self.device_details.friendly_name.clone());
-		let resp = self.client.begin_auth_session_via_qr(req)?;
+		let resp = self
+			.client
+			.begin_auth_session_via_qr(build_qr_request(&self.device_details))?;
 
 		if resp.result != EResult::OK {
 			return Err(resp.result.into());
 		}
 
-		let data = resp.response_data();
-		let return_resp = BeginQrLoginResponse {
-			challenge_url: data.challenge_url().into(),
-			confirmation_methonds: data
-				.allowed_confirmations
-				.iter()
-				.map(|c| c.clone().into())
-				.collect(),
-		};
+		let return_resp = BeginQrLoginResponse::from(resp.response_data());
 
 		debug!("auth session started");
 		self.started_auth = Some(resp.into_response_data().into());
@@ -193,27 +192,15 @@ where
 		};
 
 		loop {
-			let mut req = CAuthentication_PollAuthSessionStatus_Request::new();
-			req.set_client_id(started_auth.client_id());
-			req.set_request_id(started_auth.request_id().to_vec());
-
-			let resp = self.client.poll_auth_session(req)?;
+			let resp = self
+				.client
+				.poll_auth_session(started_auth.build_poll_request())?;
 			if resp.result != EResult::OK {
 				// EResult::FileNotFound is returned when the server couldn't find the auth session
 				return Err(anyhow::anyhow!("poll failed: {:?}", resp.result));
 			}
 
-			let data = resp.response_data();
-			let has_data = data.has_access_token()
-				|| data.has_account_name()
-				|| data.has_agreement_session_url()
-				|| data.has_had_remote_interaction()
-				|| data.has_new_challenge_url()
-				|| data.has_new_client_id()
-				|| data.has_new_guard_data()
-				|| data.has_refresh_token();
-
-			if has_data {
+			if has_poll_data(resp.response_data()) {
 				return Ok(resp.into_response_data());
 			}
 
@@ -222,9 +209,28 @@ where
 	}
 
 	pub fn poll_until_tokens(&mut self) -> anyhow::Result<Tokens> {
+		self.poll_until_tokens_with(|_| {})
+	}
+
+	/// Like [`Self::poll_until_tokens`], but calls `on_new_challenge_url` whenever Steam rotates the challenge URL of a QR login. The QR code has to be updated to show the new URL, or scanning it won't work.
+	pub fn poll_until_tokens_with(
+		&mut self,
+		mut on_new_challenge_url: impl FnMut(&str),
+	) -> anyhow::Result<Tokens> {
 		loop {
 			let mut next_poll = self.poll_until_info()?;
 
+			if next_poll.has_new_client_id() {
+				debug!("auth session has a new client id");
+				if let Some(started_auth) = self.started_auth.as_mut() {
+					started_auth.set_client_id(next_poll.new_client_id());
+				}
+			}
+			if next_poll.has_new_challenge_url() {
+				debug!("got new challenge url");
+				on_new_challenge_url(next_poll.new_challenge_url());
+			}
+
 			if next_poll.has_access_token() || next_poll.has_refresh_token() {
 				// On 2023-09-12, Steam stopped issuing access tokens alongside refresh tokens for newly authenticated sessions.
 				// If they decide to revert this change, we'll accept the access token if it's present.
@@ -249,6 +255,11 @@ where
 					return Ok(Tokens::new(access_token, next_poll.take_refresh_token()));
 				};
 			}
+
+			// `had_remote_interaction` stays set until the login is approved, so don't hammer Steam while we wait.
+			if let Some(started_auth) = self.started_auth.as_ref() {
+				std::thread::sleep(Duration::from_secs_f32(started_auth.interval()));
+			}
 		}
 	}
 
@@ -262,25 +273,7 @@ where
 		let Some(started_auth) = self.started_auth.as_ref() else {
 			return Err(UpdateAuthSessionError::SessionNotStarted);
 		};
-
-		if guard_type != EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode
-			&& guard_type != EAuthSessionGuardType::k_EAuthSessionGuardType_EmailCode
-		{
-			return Err(UpdateAuthSessionError::InvalidGuardType);
-		}
-
-		let mut req = CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request::new();
-		req.set_client_id(started_auth.client_id());
-		req.set_code_type(guard_type);
-		req.set_code(code);
-		match started_auth {
-			StartAuth::BeginAuthSessionViaCredentials(ref resp) => {
-				req.set_steamid(resp.steamid());
-			}
-			StartAuth::BeginAuthSessionViaQR(_) => {
-				return Err(anyhow::anyhow!("qr auth not supported").into());
-			}
-		}
+		let req = started_auth.build_steam_guard_code_request(guard_type, code)?;
 
 		let resp = self.client.update_session_with_steam_guard_code(req)?;
 
@@ -292,6 +285,45 @@ where
 	}
 }
 
+pub(crate) fn build_credentials_request(
+	device_details: &DeviceDetails,
+	account_name: &str,
+	rsa_resp: CAuthentication_GetPasswordRSAPublicKey_Response,
+	password: &str,
+) -> CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData {
+	let mut req = CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData::new();
+	req.set_account_name(account_name.to_owned());
+	req.set_encryption_timestamp(rsa_resp.timestamp());
+	let encrypted_password = encrypt_password(rsa_resp, password);
+	req.set_encrypted_password(encrypted_password);
+	req.set_persistence(ESessionPersistence::k_ESessionPersistence_Persistent);
+	req.device_details = device_details.clone().into_message_field();
+	req.set_language(0); // english, probably
+	req.set_qos_level(2); // value from observed traffic
+	req
+}
+
+pub(crate) fn build_qr_request(
+	device_details: &DeviceDetails,
+) -> CAuthentication_BeginAuthSessionViaQR_Request {
+	let mut req = CAuthentication_BeginAuthSessionViaQR_Request::new();
+	req.set_platform_type(device_details.platform_type);
+	req.set_device_friendly_name(device_details.friendly_name.clone());
+	req
+}
+
+/// Whether a poll response has anything interesting in it, or if we should keep polling.
+pub(crate) fn has_poll_data(data: &CAuthentication_PollAuthSessionStatus_Response) -> bool {
+	data.has_access_token()
+		|| data.has_account_name()
+		|| data.has_agreement_session_url()
+		|| data.has_had_remote_interaction()
+		|| data.has_new_challenge_url()
+		|| data.has_new_client_id()
+		|| data.has_new_guard_data()
+		|| data.has_refresh_token()
+}
+
 fn encrypt_password(
 	rsa_resp: CAuthentication_GetPasswordRSAPublicKey_Response,
 	password: impl AsRef<[u8]>,
@@ -311,7 +343,7 @@ fn encrypt_password(
 }
 
 #[derive(Debug)]
-enum StartAuth {
+pub(crate) enum StartAuth {
 	BeginAuthSessionViaCredentials(CAuthentication_BeginAuthSessionViaCredentials_Response),
 	BeginAuthSessionViaQR(CAuthentication_BeginAuthSessionViaQR_Response),
 }
@@ -324,6 +356,14 @@ impl StartAuth {
 		}
 	}
 
+	/// Steam can assign a new client id while polling, which has to be used for the rest of the session.
+	pub(crate) fn set_client_id(&mut self, client_id: u64) {
+		match self {
+			StartAuth::BeginAuthSessionViaCredentials(resp) => resp.set_client_id(client_id),
+			StartAuth::BeginAuthSessionViaQR(resp) => resp.set_client_id(client_id),
+		}
+	}
+
 	pub(crate) fn request_id(&self) -> &[u8] {
 		match self {
 			StartAuth::BeginAuthSessionViaCredentials(resp) => resp.request_id(),
@@ -344,6 +384,40 @@ impl StartAuth {
 			StartAuth::BeginAuthSessionViaQR(resp) => &resp.allowed_confirmations,
 		}
 	}
+
+	pub(crate) fn build_poll_request(&self) -> CAuthentication_PollAuthSessionStatus_Request {
+		let mut req = CAuthentication_PollAuthSessionStatus_Request::new();
+		req.set_client_id(self.client_id());
+		req.set_request_id(self.request_id().to_vec());
+		req
+	}
+
+	pub(crate) fn build_steam_guard_code_request(
+		&self,
+		guard_type: EAuthSessionGuardType,
+		code: String,
+	) -> Result<CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request, UpdateAuthSessionError>
+	{
+		if guard_type != EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode
+			&& guard_type != EAuthSessionGuardType::k_EAuthSessionGuardType_EmailCode
+		{
+			return Err(UpdateAuthSessionError::InvalidGuardType);
+		}
+
+		let mut req = CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request::new();
+		req.set_client_id(self.client_id());
+		req.set_code_type(guard_type);
+		req.set_code(code);
+		match self {
+			StartAuth::BeginAuthSessionViaCredentials(resp) => {
+				req.set_steamid(resp.steamid());
+			}
+			StartAuth::BeginAuthSessionViaQR(_) => {
+				return Err(anyhow::anyhow!("qr auth not supported").into());
+			}
+		}
+		Ok(req)
+	}
 }
 
 impl From<CAuthentication_BeginAuthSessionViaCredentials_Response> for StartAuth {
@@ -499,4 +573,56 @@ mod tests {
 		let result = encrypt_password(rsa_resp, "foo");
 		assert_eq!(result, "jmlMXmhbweWn+wJnnf96W3Lsh0dRmzrBfMxREUuEW11rRYcfXWupBIT3eK1fmQHMZmyJeMhZiRpgIaZ7DafojQT6djJr+RKeREJs0ys9hKwxD5FGlqsTLXXEeuyopyd2smHBbmmF47voe59KEoiZZapP+eYnpJy3O2k7e1P9BH9LsKIN/nWF1ogM2jjJ328AejUpM64tPl/kInFJ1CHrLiAAKDPk42fLAAKs97xIi0JkosG6yp+8HhFqQxxZ8/bNI1IVkQC1Hdc2AN0QlNKxbDXquAn6ARgw/4b5DwUpnOb9de+Q6iX3v1/M07Se7JV8/4tuz8Thy2Chbxsf9E1TuQ==");
 	}
+
+	#[test]
+	fn test_qr_login_follows_new_challenge_url() {
+		use crate::transport::{mock::MockRequest, MockTransport};
+
+		let transport = MockTransport::new();
+		let mut begin = CAuthentication_BeginAuthSessionViaQR_Response::new();
+		begin.set_client_id(1);
+		begin.set_request_id(vec![1, 2, 3]);
+		begin.set_interval(0.0);
+		begin.set_challenge_url("https://s.team/q/1/1".to_owned());
+		transport.respond_with("IAuthenticationService", "BeginAuthSessionViaQR", begin);
+		let mut rotated = CAuthentication_PollAuthSessionStatus_Response::new();
+		rotated.set_new_client_id(2);
+		rotated.set_new_challenge_url("https://s.team/q/1/2".to_owned());
+		transport.respond_with("IAuthenticationService", "PollAuthSessionStatus", rotated);
+		let mut done = CAuthentication_PollAuthSessionStatus_Response::new();
+		done.set_access_token("access".to_owned());
+		done.set_refresh_token("refresh".to_owned());
+		transport.respond_with("IAuthenticationService", "PollAuthSessionStatus", done);
+
+		let mut login = UserLogin::new(
+			transport.clone(),
+			DeviceDetails {
+				friendly_name: "test".to_owned(),
+				platform_type: EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp,
+				os_type: -500,
+				gaming_device_type: 528,
+			},
+		);
+		let started = login.begin_auth_via_qr().unwrap();
+		assert_eq!(started.challenge_url(), "https://s.team/q/1/1");
+
+		let mut urls = vec![];
+		let tokens = login
+			.poll_until_tokens_with(|url| urls.push(url.to_owned()))
+			.unwrap();
+		assert_eq!(urls, vec!["https://s.team/q/1/2"]);
+		assert_eq!(tokens.refresh_token().expose_secret(), "refresh");
+
+		let client_ids: Vec<u64> = transport
+			.requests()
+			.iter()
+			.filter(|req| req.endpoint() == "IAuthenticationService/PollAuthSessionStatus")
+			.map(|req: &MockRequest| {
+				req.decode::<CAuthentication_PollAuthSessionStatus_Request>()
+					.unwrap()
+					.client_id()
+			})
+			.collect();
+		assert_eq!(client_ids, vec![1, 2]);
+	}
 }